@@ -1,8 +1,10 @@
 use super::{ FunctionInfo, InsData };
 use crate::asmgen::Context;
+use crate::irgen::TRAP_FUNC;
 use std::io::Result;
 use koopa::ir::entities::ValueData;
 use koopa::ir::{ BasicBlock, BinaryOp, FunctionData, TypeKind, Value, ValueKind };
+use std::collections::HashSet;
 use std::ops::Deref;
 use std::{ fs::File, io::Write };
 // koopa IR => ASM
@@ -36,12 +38,21 @@ impl GenerateAsm for koopa::ir::Program {
             // 函数声明内部没有block,不需要翻译为机器码,skip;
             // Koopa IR 的函数声明和普通函数的区别是: 函数声明的基本块列表是空的
             if let None = func_data.layout().entry_bb() {
+                // 加固模式的陷阱函数只有声明, 在这里生成: 执行非法指令使程序确定地终止
+                if func_data.name()[1..] == *TRAP_FUNC {
+                    writeln!(file, "  .text")?;
+                    writeln!(file, "{}:", ctx.asm_symbol(TRAP_FUNC))?;
+                    writeln!(file, "  unimp")?;
+                    writeln!(file)?;
+                }
                 continue;
             }
             writeln!(file, "  .text")?;
 
             let name = func_data.name()[1..].to_string();
-            writeln!(file, "  .global {}", name)?;
+            if !ctx.is_internal(&name) {
+                writeln!(file, "  .global {}", ctx.asm_symbol(&name))?;
+            }
             ctx.func = Some(func);
             func_data.generate(file, ctx)?;
             writeln!(file)?;
@@ -54,16 +65,22 @@ impl GenerateAsm for koopa::ir::FunctionData {
     type Out = ();
     fn generate(&self, file: &mut File, ctx: &mut Context) -> Result<Self::Out> {
         let name = self.name()[1..].to_string();
-        writeln!(file, "{}:", name)?;
+        writeln!(file, "{}:", ctx.asm_symbol(&name))?;
         ctx.alloc_on_stack(self);
         inc_reg(file, &"sp".to_string(), -ctx.cur_func_info.as_ref().unwrap().stack_allocation)?;
         if !ctx.cur_func_info.as_ref().unwrap().is_leaf_func {
             let offset = ctx.cur_func_info.as_ref().unwrap().stack_allocation - 4;
             write_by_offset(file, "ra", "sp", offset)?;
         }
+        for (i, param) in self.params().iter().enumerate().take(8) {
+            if let Some(offset) = ctx.find_value_stack_offset(*param) {
+                write_by_offset(file, format!("a{}", i), "sp".to_string(), offset)?;
+            }
+        }
         for (bb, node) in self.layout().bbs() {
-            if let Some(name) = ctx.look_up_label(*bb) {
-                writeln!(file, "{}:", name)?;
+            // 作为跳转目标的块需要标签; 优化后的块可能在跳转到它的指令之前出现, 此时在这里分配标签
+            if !self.dfg().bb(*bb).used_by().is_empty() {
+                writeln!(file, "{}:", ctx.label(*bb))?;
             }
             for &inst in node.insts().keys() {
                 println!("generating value data {:#?}", inst);
@@ -72,6 +89,13 @@ impl GenerateAsm for koopa::ir::FunctionData {
                 // 先生成1%,再生成2%;
                 let value_data = self.dfg().value(inst);
                 ctx.cur_value = Some(inst);
+                // 尾调用之后的ret不再生成
+                if let ValueKind::Call(call) = value_data.kind() {
+                    if ctx.is_tail_call(inst) {
+                        generate_tail_call(file, ctx, call)?;
+                        break;
+                    }
+                }
                 value_data.generate(file, ctx)?;
             }
         }
@@ -91,6 +115,10 @@ impl GenerateAsm for koopa::ir::values::Aggregate {
                 ValueKind::Aggregate(agg) => {
                     agg.generate(file, ctx)?;
                 }
+                // 全为0的子数组
+                ValueKind::ZeroInit(_) => {
+                    writeln!(file, "  .zero {}", sub_value.ty().size())?;
+                }
                 _ => unreachable!(),
             }
         }
@@ -105,8 +133,15 @@ impl GenerateAsm for koopa::ir::entities::ValueData {
                 let value = ctx.cur_value.unwrap();
                 let value_data = ctx.prog.borrow_value(value);
                 if let Some(global_name) = value_data.name().as_ref() {
-                    let var_name = global_name[1..].to_string();
-                    writeln!(file, "  .globl {}", var_name)?;
+                    let var_name = ctx.asm_symbol(&global_name[1..]);
+                    // extern声明的变量由其他编译单元定义, 这里只记录符号名
+                    if ctx.linkage.external.contains(&global_name[1..]) {
+                        ctx.global_value_to_data_name.insert(value, var_name);
+                        return Ok(());
+                    }
+                    if !ctx.is_internal(&global_name[1..]) {
+                        writeln!(file, "  .globl {}", var_name)?;
+                    }
                     writeln!(file, "{}:", var_name)?;
                     let init = ctx.prog.borrow_value(global_alloc.init());
                     match init.kind() {
@@ -177,6 +212,9 @@ impl GenerateAsm for koopa::ir::entities::ValueData {
                         writeln!(file, "  la    t1, {}", name)?;
                         writeln!(file, "  sw    {}, 0(t1)", left_reg)?;
                     }
+                    InsData::Reg(reg) => {
+                        writeln!(file, "  sw    {}, 0({})", left_reg, reg)?;
+                    }
                     _ => unreachable!(),
                 }
 
@@ -188,7 +226,8 @@ impl GenerateAsm for koopa::ir::entities::ValueData {
             // load指令,获取目标的值,并写入到本指令对应的逻辑内存位置中
             ValueKind::Load(load) => {
                 load.src().generate(ctx)?.write_to(file, "t0")?;
-                if ctx.is_ptr(load.src()) {
+                // 全局变量在write_to中已经通过la + lw读出了值,不需要再解引用
+                if ctx.is_ptr(load.src()) && !ctx.is_global_value(&load.src()) {
                     load_by_offset(file, "t0", "t0", 0)?;
                 }
 
@@ -222,22 +261,11 @@ impl GenerateAsm for koopa::ir::entities::ValueData {
             }
 
             ValueKind::Call(func_call) => {
-                for i in 0..func_call.args().len() {
-                    if i < 8 {
-                        let dst = format!("a{}", i).to_owned();
-                        let ins_data = func_call.args()[i].generate(ctx)?;
-                        ins_data.write_to(file, dst)?;
-                    } else {
-                        let dst = (i - 8) * 4;
-                        let ins_data = func_call.args()[i].generate(ctx)?;
-                        ins_data.write_to(file, "t0")?;
-                        write_by_offset(file, "t0", "sp", dst as i32)?;
-                    }
-                }
+                write_call_args(file, ctx, func_call.args())?;
                 writeln!(
                     file,
                     "  call  {}",
-                    ctx.prog.func(func_call.callee()).name()[1..].to_string()
+                    ctx.asm_symbol(&ctx.prog.func(func_call.callee()).name()[1..])
                 )?;
                 // 注意funcall可能会返回void,对于unit type,此时不用写回逻辑位置
                 if let Some(_) = ctx.find_value_stack_offset(ctx.cur_value.unwrap()) {
@@ -258,15 +286,19 @@ impl GenerateAsm for koopa::ir::values::Branch {
     fn generate(&self, file: &mut File, ctx: &mut Context) -> Result<Self::Out> {
         let value = self.cond().generate(ctx)?;
         value.write_to(file, &"t0".to_string())?;
-        let true_bb = self.true_bb();
-        let false_bb = self.false_bb();
-        let true_block_name = ctx.cur_func().dfg().bb(true_bb).name().as_ref().unwrap().clone();
-        let true_label_name = ctx.register_label(true_bb, label_name(true_block_name));
-        writeln!(file, "  bnez {}, {}", "t0", true_label_name)?;
-
-        let false_block_name = ctx.cur_func().dfg().bb(false_bb).name().as_ref().unwrap().clone();
-        let false_label_name = ctx.register_label(false_bb, label_name(false_block_name));
-        writeln!(file, "  j {}", false_label_name)?;
+        if self.true_args().is_empty() && self.false_args().is_empty() {
+            writeln!(file, "  bnez t0, {}", ctx.label(self.true_bb()))?;
+            writeln!(file, "  j {}", ctx.label(self.false_bb()))?;
+            return Ok(());
+        }
+        // 两条边传递的参数不同, 为真分支单独生成一段写入参数的代码
+        let true_edge = ctx.new_label("branch_args");
+        writeln!(file, "  bnez t0, {}", true_edge)?;
+        write_block_args(file, ctx, self.false_bb(), self.false_args())?;
+        writeln!(file, "  j {}", ctx.label(self.false_bb()))?;
+        writeln!(file, "{}:", true_edge)?;
+        write_block_args(file, ctx, self.true_bb(), self.true_args())?;
+        writeln!(file, "  j {}", ctx.label(self.true_bb()))?;
 
         Ok(())
     }
@@ -275,17 +307,8 @@ impl GenerateAsm for koopa::ir::values::Branch {
 impl GenerateAsm for koopa::ir::values::Jump {
     type Out = ();
     fn generate(&self, file: &mut File, ctx: &mut Context) -> Result<Self::Out> {
-        let func_data = ctx.cur_func();
-        let label_name = if let None = ctx.look_up_label(self.target()) {
-            let target_block_name: &Option<String> = func_data.dfg().bb(self.target()).name();
-            ctx.register_label(
-                self.target(),
-                label_name(target_block_name.as_ref().unwrap().clone())
-            )
-        } else {
-            ctx.look_up_label(self.target()).unwrap()
-        };
-        writeln!(file, "  j {}", label_name)?;
+        write_block_args(file, ctx, self.target(), self.args())?;
+        writeln!(file, "  j {}", ctx.label(self.target()))?;
         Ok(())
     }
 }
@@ -375,7 +398,9 @@ impl<'a> GenerateInsData<'a> for koopa::ir::Value {
             ValueKind::Integer(v) => Ok(InsData::Int(v.value())),
             // 理论上来说可以先处理prologue然后这里就不用加本函数栈的偏移？再想想！
             ValueKind::FuncArgRef(func_arg) => {
-                if func_arg.index() < 8 {
+                if let Some(offset) = ctx.find_value_stack_offset(*self) {
+                    Ok(InsData::StackSlot(offset))
+                } else if func_arg.index() < 8 {
                     Ok(InsData::Reg(format!("a{}", func_arg.index())))
                 } else {
                     Ok(
@@ -470,11 +495,22 @@ impl<'a> Context<'a> {
             );
         }
     }
+    // static修饰的符号只在本文件内可见
+    fn is_internal(&self, name: &str) -> bool {
+        self.linkage.internal.contains(name)
+    }
+    // 符号在汇编中的名字; 合并多个编译单元时内部链接的符号需要改名
+    pub(super) fn asm_symbol(&self, name: &str) -> String {
+        match &self.internal_suffix {
+            Some(suffix) if self.is_internal(name) => format!("{}{}", name, suffix),
+            _ => name.to_string(),
+        }
+    }
     fn is_global_value(&self, value: &Value) -> bool {
         self.global_value_to_data_name.get(value).is_some()
     }
     // 扫描函数的所有指令,按需在栈上分配内存
-    fn alloc_on_stack(&mut self, func_data: &FunctionData) {
+    pub(super) fn alloc_on_stack(&mut self, func_data: &FunctionData) {
         let mut offset = 0;
         let mut is_leaf_func = true;
         let mut longest_call_func_args = 0;
@@ -497,7 +533,22 @@ impl<'a> Context<'a> {
             offset = offset + 4 * (longest_call_func_args - 8);
         }
 
-        for (_, bbd) in func_data.layout().bbs().iter() {
+        // 可能被调用覆盖的寄存器参数, 在函数开头存到栈上
+        for &param in func_data.params().iter().take(8) {
+            if Self::need_spill(func_data, param) {
+                self.value_2_stack_offset.insert(param, offset as i32);
+                offset += 4;
+            }
+        }
+        let mut max_block_params = 0;
+        for (&bb, bbd) in func_data.layout().bbs().iter() {
+            // 基本块参数由跳转到该块的指令写入
+            let params = func_data.dfg().bb(bb).params();
+            max_block_params = max_block_params.max(params.len());
+            for &param in params {
+                self.value_2_stack_offset.insert(param, offset as i32);
+                offset += 4;
+            }
             for (&val, _) in bbd.insts() {
                 let value_data = func_data.dfg().value(val);
                 // 本条指令需要分配内存,则为返回值
@@ -510,6 +561,9 @@ impl<'a> Context<'a> {
                 }
             }
         }
+        // 传递基本块参数时的暂存区, 参数之间互相赋值时先全部读出再写入
+        let block_args_offset = offset as i32;
+        offset += 4 * max_block_params;
         // alloc ra
         if !is_leaf_func {
             offset = offset + 4;
@@ -518,10 +572,59 @@ impl<'a> Context<'a> {
         let info = FunctionInfo {
             stack_allocation,
             is_leaf_func,
+            block_args_offset,
         };
         self.cur_func_info = Some(info);
     }
 
+    // call之后紧接着返回它的结果(或者都没有返回值)时可以作为尾调用:
+    // 栈上传递的参数要能放进本函数的传入参数区, 且参数中不能有指向本函数栈帧的指针
+    fn is_tail_call(&self, inst: Value) -> bool {
+        let func_data = self.cur_func();
+        let ValueKind::Call(call) = func_data.dfg().value(inst).kind() else {
+            return false;
+        };
+        let bb = func_data.layout().parent_bb(inst).unwrap();
+        let mut insts = func_data.layout().bbs().node(&bb).unwrap().insts().keys();
+        let Some(&next) = insts.by_ref().find(|&&other| other == inst).and_then(|_| insts.next()) else {
+            return false;
+        };
+        let returns_result = match func_data.dfg().value(next).kind() {
+            ValueKind::Return(ret) => ret.value().is_none_or(|value| value == inst),
+            _ => false,
+        };
+        let stack_args_fit = call.args().len().saturating_sub(8) <= func_data.params().len().saturating_sub(8);
+        returns_result && stack_args_fit && !call.args().iter().any(|&arg| self.points_to_frame(arg))
+    }
+
+    // 地址是否由本函数栈上的局部变量计算得到
+    fn points_to_frame(&self, mut value: Value) -> bool {
+        let func_data = self.cur_func();
+        while !self.is_global_value(&value) {
+            match func_data.dfg().value(value).kind() {
+                ValueKind::GetElemPtr(ptr) => value = ptr.src(),
+                ValueKind::GetPtr(ptr) => value = ptr.src(),
+                ValueKind::Alloc(_) => return true,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    // 寄存器传入的参数只在入口块第一个call之前被非call指令使用时, 直接从寄存器读取;
+    // 否则其寄存器可能已被调用或准备调用参数时覆盖, 需要在函数开头存到栈上
+    fn need_spill(func_data: &FunctionData, param: Value) -> bool {
+        let entry = func_data.layout().entry_bb().unwrap();
+        let mut before_call = HashSet::new();
+        for &inst in func_data.layout().bbs().node(&entry).unwrap().insts().keys() {
+            if matches!(func_data.dfg().value(inst).kind(), ValueKind::Call(_)) {
+                break;
+            }
+            before_call.insert(inst);
+        }
+        !func_data.dfg().value(param).used_by().iter().all(|user| before_call.contains(user))
+    }
+
     // todo 记一下
     fn need_alloc(&self, value_data: &ValueData) -> bool {
         !value_data.ty().is_unit() || matches!(value_data.kind(), ValueKind::Alloc(_))
@@ -550,18 +653,82 @@ impl<'a> Context<'a> {
         self.prog.func(self.func.unwrap())
     }
 
-    fn register_label(&mut self, bb: BasicBlock, mut name: String) -> &String {
-        name.push_str(self.label_counter.to_string().as_str());
-        self.label_counter = self.label_counter + 1;
-        self.basic_block_to_label_name.insert(bb, name);
-        self.basic_block_to_label_name.get(&bb).unwrap()
+    // 不对应基本块的标签
+    fn new_label(&mut self, prefix: &str) -> String {
+        let name = format!("{}{}", prefix, self.label_counter);
+        self.label_counter += 1;
+        name
     }
 
-    fn look_up_label(&self, bb: BasicBlock) -> Option<&String> {
-        self.basic_block_to_label_name.get(&bb)
+    // 基本块的标签, 第一次用到时按块名和全局计数器分配
+    fn label(&mut self, bb: BasicBlock) -> String {
+        if let Some(name) = self.basic_block_to_label_name.get(&bb) {
+            return name.clone();
+        }
+        let block_name = self.cur_func().dfg().bb(bb).name().as_ref().unwrap().clone();
+        let name = format!("{}{}", label_name(block_name), self.label_counter);
+        self.label_counter += 1;
+        self.basic_block_to_label_name.insert(bb, name.clone());
+        name
     }
 }
 
+// 前8个参数写入a0-a7, 其余的写到栈顶的传出参数区
+fn write_call_args(file: &mut File, ctx: &mut Context, args: &[Value]) -> Result<()> {
+    for (i, arg) in args.iter().enumerate() {
+        if i < 8 {
+            let dst = format!("a{}", i).to_owned();
+            let ins_data = arg.generate(ctx)?;
+            ins_data.write_to(file, dst)?;
+        } else {
+            let dst = (i - 8) * 4;
+            let ins_data = arg.generate(ctx)?;
+            ins_data.write_to(file, "t0")?;
+            write_by_offset(file, "t0", "sp", dst as i32)?;
+        }
+    }
+    Ok(())
+}
+
+// 尾调用: 准备好参数后恢复ra、释放栈帧, 再跳转到被调用的函数, 由它直接返回到本函数的调用者
+// 栈上的参数先按普通调用写到传出参数区, 全部读出后再复制到本函数的传入参数区,
+// 以免覆盖还要作为参数读取的传入参数
+fn generate_tail_call(file: &mut File, ctx: &mut Context, call: &koopa::ir::values::Call) -> Result<()> {
+    write_call_args(file, ctx, call.args())?;
+    let stack_allocation = ctx.cur_func_info.as_ref().unwrap().stack_allocation;
+    for i in 8..call.args().len() {
+        let offset = ((i - 8) * 4) as i32;
+        load_by_offset(file, "t0", "sp", offset)?;
+        write_by_offset(file, "t0", "sp", stack_allocation + offset)?;
+    }
+    if !ctx.cur_func_info.as_ref().unwrap().is_leaf_func {
+        load_by_offset(file, "ra", "sp", stack_allocation - 4)?;
+    }
+    inc_reg(file, "sp", stack_allocation)?;
+    writeln!(file, "  tail  {}", ctx.asm_symbol(&ctx.prog.func(call.callee()).name()[1..]))?;
+    Ok(())
+}
+
+// 把参数写入目标块的参数所在的栈位置
+// 参数可能读取目标块自己的参数(如循环中交换两个变量), 此时先全部写入暂存区, 再复制到参数的位置
+fn write_block_args(file: &mut File, ctx: &mut Context, target: BasicBlock, args: &[Value]) -> Result<()> {
+    let params = ctx.cur_func().dfg().bb(target).params().to_vec();
+    let overlap = args.iter().enumerate().any(|(i, arg)| params[..i].contains(arg));
+    let scratch = ctx.cur_func_info.as_ref().unwrap().block_args_offset;
+    for (i, (&arg, &param)) in args.iter().zip(&params).enumerate() {
+        arg.generate(ctx)?.write_to(file, "t0")?;
+        let dst = if overlap { scratch + 4 * (i as i32) } else { ctx.find_value_stack_offset(param).unwrap() };
+        write_by_offset(file, "t0", "sp", dst)?;
+    }
+    if overlap {
+        for (i, &param) in params.iter().enumerate() {
+            load_by_offset(file, "t0", "sp", scratch + 4 * (i as i32))?;
+            write_by_offset(file, "t0", "sp", ctx.find_value_stack_offset(param).unwrap())?;
+        }
+    }
+    Ok(())
+}
+
 fn label_name(str: String) -> String {
     str[1..].to_string()
 }
@@ -602,6 +769,10 @@ impl<'a> InsData<'a> {
             InsData::GlobalVar(name) => {
                 writeln!(file, "  la    {}, {}", dst_reg, name)?;
             }
+            // 寄存器中传入的数组参数, 优化后可能直接作为基址使用
+            InsData::Reg(reg) if is_ptr => {
+                writeln!(file, "  mv    {}, {}", dst_reg, reg)?;
+            }
             _ => unreachable!(),
         }
         Ok(())
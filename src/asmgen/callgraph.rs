@@ -0,0 +1,257 @@
+use std::collections::{ HashMap, VecDeque };
+use std::fmt::Write;
+
+use koopa::ir::{ Program, ValueKind };
+
+use super::Context;
+use crate::irgen::Linkage;
+
+// 调用图与栈使用分析: 节点为汇编中的函数符号, 栈帧大小与asmgen生成代码时的分配一致
+// 递归调用所在的强连通分量内部的边不计入最坏栈深度, 单独列出
+
+pub struct CallGraph {
+    nodes: Vec<Node>,
+    // 强连通分量编号, 与nodes一一对应
+    scc: Vec<usize>,
+    // 每个函数沿非递归路径的最坏栈使用, 以及对应路径上的下一个函数
+    worst: Vec<(i32, Option<usize>)>,
+}
+
+struct Node {
+    name: String,
+    // 本程序中没有定义的函数(库函数或其他编译单元之外的函数)栈帧未知, 记为0
+    defined: bool,
+    frame: i32,
+    callees: Vec<usize>,
+}
+
+/// Builds the call graph of the given compile units from direct `call`
+/// instructions. Functions are identified by their assembly symbol, so
+/// calls across units are resolved by name.
+pub fn build(units: &[(Program, Linkage)]) -> CallGraph {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut nodes: Vec<Node> = vec![];
+    let mut node_of = |name: String, nodes: &mut Vec<Node>| -> usize {
+        *index.entry(name.clone()).or_insert_with(|| {
+            nodes.push(Node { name, defined: false, frame: 0, callees: vec![] });
+            nodes.len() - 1
+        })
+    };
+    for (idx, (program, linkage)) in units.iter().enumerate() {
+        let mut ctx = Context::new(program, linkage, units.len(), idx);
+        for &func in program.func_layout() {
+            let func_data = program.func(func);
+            // 只声明的函数在被调用时才加入调用图
+            if func_data.layout().entry_bb().is_none() {
+                continue;
+            }
+            let caller = node_of(ctx.asm_symbol(&func_data.name()[1..]), &mut nodes);
+            ctx.alloc_on_stack(func_data);
+            nodes[caller].defined = true;
+            nodes[caller].frame = ctx.cur_func_info.as_ref().unwrap().stack_allocation;
+            for (_, bb) in func_data.layout().bbs() {
+                for &inst in bb.insts().keys() {
+                    if let ValueKind::Call(call) = func_data.dfg().value(inst).kind() {
+                        let name = ctx.asm_symbol(&program.func(call.callee()).name()[1..]);
+                        let callee = node_of(name, &mut nodes);
+                        if !nodes[caller].callees.contains(&callee) {
+                            nodes[caller].callees.push(callee);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let scc = strongly_connected(&nodes);
+    let mut graph = CallGraph { nodes, scc, worst: vec![] };
+    graph.compute_worst();
+    graph
+}
+
+// Tarjan算法, 返回每个节点所在的强连通分量编号; 分量按逆拓扑序编号(被调用者在前)
+fn strongly_connected(nodes: &[Node]) -> Vec<usize> {
+    struct State {
+        counter: usize,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        scc: Vec<usize>,
+        scc_count: usize,
+    }
+    fn visit(nodes: &[Node], v: usize, st: &mut State) {
+        st.index[v] = Some(st.counter);
+        st.low[v] = st.counter;
+        st.counter += 1;
+        st.stack.push(v);
+        st.on_stack[v] = true;
+        for &w in &nodes[v].callees {
+            match st.index[w] {
+                None => {
+                    visit(nodes, w, st);
+                    st.low[v] = st.low[v].min(st.low[w]);
+                }
+                Some(index) if st.on_stack[w] => {
+                    st.low[v] = st.low[v].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+        if Some(st.low[v]) == st.index[v] {
+            while let Some(w) = st.stack.pop() {
+                st.on_stack[w] = false;
+                st.scc[w] = st.scc_count;
+                if w == v {
+                    break;
+                }
+            }
+            st.scc_count += 1;
+        }
+    }
+    let n = nodes.len();
+    let mut st = State {
+        counter: 0,
+        index: vec![None; n],
+        low: vec![0; n],
+        stack: vec![],
+        on_stack: vec![false; n],
+        scc: vec![0; n],
+        scc_count: 0,
+    };
+    for v in 0..n {
+        if st.index[v].is_none() {
+            visit(nodes, v, &mut st);
+        }
+    }
+    st.scc
+}
+
+impl CallGraph {
+    // 被调用者所在的分量编号更小, 按分量编号从小到大计算即可保证被调用者先算完
+    fn compute_worst(&mut self) {
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by_key(|&v| self.scc[v]);
+        self.worst = vec![(0, None); self.nodes.len()];
+        for v in order {
+            let deepest = self.nodes[v].callees
+                .iter()
+                .filter(|&&w| self.scc[w] != self.scc[v])
+                .map(|&w| (self.worst[w].0, Some(w)))
+                .max_by_key(|(usage, _)| *usage)
+                .unwrap_or((0, None));
+            self.worst[v] = (self.nodes[v].frame + deepest.0, deepest.1);
+        }
+    }
+
+    fn is_recursive(&self, v: usize) -> bool {
+        self.nodes[v].callees.contains(&v) ||
+            (0..self.nodes.len()).any(|w| w != v && self.scc[w] == self.scc[v])
+    }
+
+    // 每个递归的强连通分量给出其中的一个环: 从分量中第一个函数出发再回到它
+    fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut cycles = vec![];
+        let mut seen = vec![false; self.nodes.len()];
+        for v in 0..self.nodes.len() {
+            if seen[self.scc[v]] || !self.is_recursive(v) {
+                continue;
+            }
+            seen[self.scc[v]] = true;
+            // 在分量内广度优先搜索回到v的最短路径
+            let mut prev: HashMap<usize, usize> = HashMap::new();
+            let mut queue = VecDeque::from([v]);
+            'search: while let Some(u) = queue.pop_front() {
+                for &w in &self.nodes[u].callees {
+                    if self.scc[w] != self.scc[v] || prev.contains_key(&w) {
+                        continue;
+                    }
+                    prev.insert(w, u);
+                    if w == v {
+                        break 'search;
+                    }
+                    queue.push_back(w);
+                }
+            }
+            let mut cycle = vec![v];
+            let mut u = prev[&v];
+            while u != v {
+                cycle.push(u);
+                u = prev[&u];
+            }
+            cycle[1..].reverse();
+            cycle.push(v);
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    /// Textual report: frame size and worst-case stack usage along
+    /// non-recursive call paths of every defined function, followed by
+    /// the recursive cycles, whose depth cannot be bounded statically.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:<24} {:>8} {:>8}  deepest path", "function", "frame", "worst").unwrap();
+        for v in 0..self.nodes.len() {
+            let node = &self.nodes[v];
+            if !node.defined {
+                continue;
+            }
+            let mut path = vec![node.name.as_str()];
+            let mut next = self.worst[v].1;
+            while let Some(w) = next {
+                path.push(&self.nodes[w].name);
+                next = self.worst[w].1;
+            }
+            let recursive = if self.is_recursive(v) { " (recursive)" } else { "" };
+            writeln!(
+                out,
+                "{:<24} {:>8} {:>8}  {}{}",
+                node.name,
+                node.frame,
+                self.worst[v].0,
+                path.join(" -> "),
+                recursive
+            ).unwrap();
+        }
+        let cycles = self.cycles();
+        if !cycles.is_empty() {
+            writeln!(out, "\nrecursive cycles (stack usage multiplies with recursion depth):").unwrap();
+            for cycle in cycles {
+                let names: Vec<&str> = cycle
+                    .iter()
+                    .map(|&v| self.nodes[v].name.as_str())
+                    .collect();
+                writeln!(out, "  {}", names.join(" -> ")).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Graphviz rendering of the call graph. Recursive edges are red,
+    /// functions not defined in the program are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph callgraph {\n  node [shape=box];\n");
+        for (v, node) in self.nodes.iter().enumerate() {
+            if node.defined {
+                writeln!(
+                    out,
+                    "  n{} [label=\"{}\\nframe {} / worst {}\"];",
+                    v,
+                    node.name,
+                    node.frame,
+                    self.worst[v].0
+                ).unwrap();
+            } else {
+                writeln!(out, "  n{} [label=\"{}\", style=dashed];", v, node.name).unwrap();
+            }
+        }
+        for (v, node) in self.nodes.iter().enumerate() {
+            for &w in &node.callees {
+                let attr = if self.scc[v] == self.scc[w] { " [color=red]" } else { "" };
+                writeln!(out, "  n{} -> n{}{};", v, w, attr).unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
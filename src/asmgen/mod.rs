@@ -2,11 +2,14 @@ use std::{ collections::HashMap, fs::File };
 
 use asmgen::GenerateAsm;
 use koopa::ir::{ BasicBlock, Function, Program, Value };
+use crate::irgen::Linkage;
 mod asmgen;
+pub mod callgraph;
 use std::io::Result;
 
 pub struct Context<'a> {
     prog: &'a Program,
+    linkage: &'a Linkage,
     func: Option<Function>,
     cur_func_info: Option<FunctionInfo>,
     value_2_stack_offset: HashMap<Value, i32>,
@@ -14,11 +17,15 @@ pub struct Context<'a> {
     basic_block_to_label_name: HashMap<BasicBlock, String>,
     global_value_to_data_name: HashMap<Value, String>,
     label_counter: i32,
+    // 多个编译单元汇编到同一文件时, 内部链接的符号加上此后缀以免互相冲突
+    internal_suffix: Option<String>,
 }
 
 pub struct FunctionInfo {
     stack_allocation: i32,
     is_leaf_func: bool,
+    // 传递基本块参数时使用的暂存区在栈上的偏移
+    block_args_offset: i32,
 }
 
 pub enum InsData<'a> {
@@ -28,12 +35,27 @@ pub enum InsData<'a> {
     GlobalVar(&'a str),
     // 本身的内容是一个指针记录栈上的偏移量,返回的I32是指针本身在栈上存储的位置
 }
-/// Generates the given Koopa IR program to RISC-V assembly.
-pub fn generate_asm(program: &Program, path: &str) -> Result<()> {
-    program.generate(
-        &mut File::create(path).unwrap(),
-        &mut (Context {
-            prog: program,
+/// Generates the given Koopa IR programs (one per compile unit) to
+/// RISC-V assembly in a single file.
+pub fn generate_asm(units: &[(Program, Linkage)], path: &str) -> Result<()> {
+    let mut file = File::create(path)?;
+    // 基本块标签在整个汇编文件内编号, 各编译单元共用计数器
+    let mut label_counter = 0;
+    for (idx, (program, linkage)) in units.iter().enumerate() {
+        let mut ctx = Context::new(program, linkage, units.len(), idx);
+        ctx.label_counter = label_counter;
+        program.generate(&mut file, &mut ctx)?;
+        label_counter = ctx.label_counter;
+    }
+    Ok(())
+}
+
+impl<'a> Context<'a> {
+    // 第idx个编译单元(共count个)的上下文
+    fn new(prog: &'a Program, linkage: &'a Linkage, count: usize, idx: usize) -> Self {
+        Context {
+            prog,
+            linkage,
             func: None,
             value_2_stack_offset: HashMap::new(),
             cur_func_info: None,
@@ -41,6 +63,7 @@ pub fn generate_asm(program: &Program, path: &str) -> Result<()> {
             basic_block_to_label_name: HashMap::new(),
             label_counter: 0,
             global_value_to_data_name: HashMap::new(),
-        })
-    )
+            internal_suffix: if count > 1 { Some(format!(".{}", idx)) } else { None },
+        }
+    }
 }
@@ -1,3 +1,10 @@
+// 预处理后文本中的字节区间, 用于诊断信息定位
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug)]
 pub struct CompUnit {
     pub items: Vec<GlobalItem>,
@@ -11,23 +18,34 @@ pub enum FuncType {
 #[derive(Debug)]
 pub enum GlobalItem {
     FuncDef(FuncDef),
+    FuncDecl(FuncDecl),
     Decl(Decl),
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageClass {
+    Default,
+    Static,
+    Extern,
+}
 #[derive(Debug)]
 pub enum Exp {
     LOrExp(LOrExp),
+    // 赋值表达式, 右结合, 值为写入的值
+    Assign(LVal, Box<Exp>),
 }
 
+// 运算表达式的Span为整个表达式所在的区间
 #[derive(Debug)]
 pub enum UnaryExp {
     PrimaryExp(PrimaryExp),
     FuncCall(FuncCall),
-    UnaryExp(UnaryOp, Box<UnaryExp>),
+    UnaryExp(UnaryOp, Box<UnaryExp>, Span),
 }
 #[derive(Debug)]
 pub enum MulExp {
     UnaryExp(UnaryExp),
-    MulExp(Box<MulExp>, MulOp, UnaryExp),
+    MulExp(Box<MulExp>, MulOp, UnaryExp, Span),
 }
 #[derive(Debug)]
 pub enum MulOp {
@@ -38,7 +56,7 @@ pub enum MulOp {
 #[derive(Debug)]
 pub enum AddExp {
     MulExp(MulExp),
-    AddExp(Box<AddExp>, AddOp, MulExp),
+    AddExp(Box<AddExp>, AddOp, MulExp, Span),
 }
 #[derive(Debug)]
 pub enum AddOp {
@@ -69,27 +87,27 @@ pub enum LOrOp {
 #[derive(Debug)]
 pub enum RelExp {
     AddExp(AddExp),
-    RelExp(Box<RelExp>, RelOp, AddExp),
+    RelExp(Box<RelExp>, RelOp, AddExp, Span),
 }
 #[derive(Debug)]
 pub enum EqExp {
     RelExp(RelExp),
-    EqExp(Box<EqExp>, EqOp, RelExp),
+    EqExp(Box<EqExp>, EqOp, RelExp, Span),
 }
 #[derive(Debug)]
 pub enum LAndExp {
     EqExp(EqExp),
-    LAndExp(Box<LAndExp>, LAndOp, EqExp),
+    LAndExp(Box<LAndExp>, LAndOp, EqExp, Span),
 }
 #[derive(Debug)]
 pub enum LOrExp {
     LAndExp(LAndExp),
-    LOrExp(Box<LOrExp>, LOrOp, LAndExp),
+    LOrExp(Box<LOrExp>, LOrOp, LAndExp, Span),
 }
 
 #[derive(Debug)]
 pub enum PrimaryExp {
-    Number(i32),
+    Number(i32, Span),
     Exp(Box<Exp>),
     LVal(LVal),
 }
@@ -105,20 +123,37 @@ pub enum UnaryOp {
 pub struct FuncDef {
     pub func_type: FuncType,
     pub ident: String,
+    pub span: Span,
     pub params: Vec<FuncFParam>,
     pub block: Block,
+    // static函数为内部链接,不导出符号
+    pub storage: StorageClass,
+}
+
+// 函数声明(没有函数体),对应Koopa中的decl
+#[derive(Debug)]
+pub struct FuncDecl {
+    pub func_type: FuncType,
+    pub ident: String,
+    pub span: Span,
+    pub params: Vec<FuncFParam>,
+    pub storage: StorageClass,
 }
 #[derive(Debug)]
 pub struct FuncFParam {
     pub b_type: BType,
     pub name: String,
     pub array_indices: Option<Vec<ConstExp>>,
+    // 参数名所在的位置
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct FuncCall {
     pub func_name: String,
     pub params: Vec<Exp>,
+    // 函数名所在的位置
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -128,8 +163,14 @@ pub struct Block {
 
 #[derive(Debug)]
 pub enum BlockItem {
-    Decl(Decl),
-    Stmt(Stmt),
+    Decl {
+        decl: Decl,
+        span: Span,
+    },
+    Stmt {
+        stmt: Stmt,
+        span: Span,
+    },
 }
 
 #[derive(Debug)]
@@ -141,37 +182,44 @@ pub enum Decl {
 pub struct VarDecl {
     pub b_type: BType,
     pub def_list: Vec<VarDef>,
+    pub storage: StorageClass,
 }
 #[derive(Debug)]
+// Span为变量名所在的位置
 pub enum VarDef {
-    IdOnly(String, Vec<ConstExp>),
-    Assign(String, Vec<ConstExp>, InitVal),
+    IdOnly(String, Vec<ConstExp>, Span),
+    Assign(String, Vec<ConstExp>, InitVal, Span),
 }
+// Span为整个初始值(包括花括号)所在的区间
 #[derive(Debug)]
 pub enum InitVal {
-    List(Vec<InitVal>),
-    Single(Exp),
+    List(Vec<InitVal>, Span),
+    Single(Exp, Span),
 }
 
 #[derive(Debug)]
 pub struct ConstDecl {
     pub b_type: BType,
     pub def_list: Vec<ConstDef>,
+    pub storage: StorageClass,
 }
 #[derive(Debug)]
 pub struct ConstDef {
     pub id: String,
     pub dims: Vec<ConstExp>,
     pub init_val: ConstInitVal,
+    // 常量名所在的位置
+    pub span: Span,
 }
 #[derive(Debug)]
 pub enum ConstInitVal {
     Single(ConstExp),
-    List(Vec<ConstInitVal>),
+    List(Vec<ConstInitVal>, Span),
 }
 #[derive(Debug)]
 pub struct ConstExp {
     pub exp: Exp,
+    pub span: Span,
 }
 #[derive(Debug)]
 pub enum BType {
@@ -181,12 +229,13 @@ pub enum BType {
 pub struct LVal {
     pub id: String,
     pub indices: Vec<Box<Exp>>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub enum Stmt {
-    Ret(Option<Exp>),
-    Assign(LVal, Exp),
+    // Span为return关键字所在的位置
+    Ret(Option<Exp>, Span),
     Exp(Option<Exp>),
     IfStmt(IfStmt),
     Block(Box<Block>),
@@ -194,11 +243,16 @@ pub enum Stmt {
     Break(Break),
     Continue(Continue),
 }
+// Span为关键字所在的位置
 #[derive(Debug)]
-pub struct Break {}
+pub struct Break {
+    pub span: Span,
+}
 
 #[derive(Debug)]
-pub struct Continue {}
+pub struct Continue {
+    pub span: Span,
+}
 #[derive(Debug)]
 pub struct IfStmt {
     pub cond: Exp,
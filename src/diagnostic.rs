@@ -0,0 +1,260 @@
+use std::collections::{ BTreeMap, HashMap };
+use std::fmt;
+
+use crate::ast::Span;
+use crate::preprocess::Source;
+
+// 编译过程中产生的警告/错误; irgen只记录位置(预处理后文本中的偏移), 由main统一渲染
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Warning => write!(f, "warning"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+// 可以单独开关的警告, 名字用于命令行(-Wname)、#pragma lint 以及统计结果, 不应随意修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    Unused,
+    Shadowing,
+    Uninit,
+    Unreachable,
+    ImplicitReturn,
+    ConstantOverflow,
+    DeadStore,
+    ArrayBounds,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 8] = [
+        Lint::Unused,
+        Lint::Shadowing,
+        Lint::Uninit,
+        Lint::Unreachable,
+        Lint::ImplicitReturn,
+        Lint::ConstantOverflow,
+        Lint::DeadStore,
+        Lint::ArrayBounds,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::Unused => "unused",
+            Lint::Shadowing => "shadowing",
+            Lint::Uninit => "uninit",
+            Lint::Unreachable => "unreachable",
+            Lint::ImplicitReturn => "implicit-return",
+            Lint::ConstantOverflow => "constant-overflow",
+            Lint::DeadStore => "dead-store",
+            Lint::ArrayBounds => "array-bounds",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Per-lint levels set by `-W` flags and `#pragma lint`. Lints not
+/// mentioned are warnings.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+    // -Werror: 所有开启的警告都作为错误
+    warnings_as_errors: bool,
+}
+
+impl LintConfig {
+    /// Applies one of `-Wname`, `-Wno-name`, `-Werror` and `-Werror=name`.
+    /// Later flags override earlier ones.
+    pub fn apply_flag(&mut self, flag: &str) -> Result<(), String> {
+        let lint = |name: &str| Lint::from_name(name).ok_or_else(|| format!("unknown lint `{}`", name));
+        let rest = flag.strip_prefix("-W").ok_or_else(|| format!("invalid lint flag `{}`", flag))?;
+        if rest == "error" {
+            self.warnings_as_errors = true;
+        } else if let Some(name) = rest.strip_prefix("error=") {
+            self.levels.insert(lint(name)?, LintLevel::Deny);
+        } else if let Some(name) = rest.strip_prefix("no-") {
+            self.levels.insert(lint(name)?, LintLevel::Allow);
+        } else {
+            self.levels.insert(lint(rest)?, LintLevel::Warn);
+        }
+        Ok(())
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        match self.levels.get(&lint).copied().unwrap_or(LintLevel::Warn) {
+            LintLevel::Warn if self.warnings_as_errors => LintLevel::Deny,
+            level => level,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub level: Level,
+    // 由可开关的检查产生时为其名字, 普通错误为None
+    pub lint: Option<Lint>,
+    pub span: Span,
+    pub message: String,
+    // 附加说明, 例如变量的声明位置
+    pub notes: Vec<(Span, String)>,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, span: Span, message: String) -> Self {
+        Diagnostic { level, lint: None, span, message, notes: vec![] }
+    }
+
+    // 检查产生的警告, 最终的级别由Diagnostics按LintConfig决定
+    pub fn lint(lint: Lint, span: Span, message: String) -> Self {
+        Diagnostic { lint: Some(lint), ..Diagnostic::new(Level::Warning, span, message) }
+    }
+
+    pub fn with_note(mut self, span: Span, note: String) -> Self {
+        self.notes.push((span, note));
+        self
+    }
+}
+
+/// Registry that every analysis reports into. Lint diagnostics are
+/// dropped or turned into errors according to the lint configuration.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    config: LintConfig,
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(config: LintConfig) -> Self {
+        Diagnostics { config, items: vec![] }
+    }
+
+    pub fn report(&mut self, mut diag: Diagnostic) {
+        if let Some(lint) = diag.lint {
+            match self.config.level(lint) {
+                LintLevel::Allow => {
+                    return;
+                }
+                LintLevel::Warn => {}
+                LintLevel::Deny => {
+                    diag.level = Level::Error;
+                }
+            }
+        }
+        self.items.push(diag);
+    }
+
+    // 各项检查分别进行, 最后按源码位置排序
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|diag| diag.span.start);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.items.iter().any(|diag| diag.level == Level::Error)
+    }
+}
+
+impl Extend<Diagnostic> for Diagnostics {
+    fn extend<I: IntoIterator<Item = Diagnostic>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|diag| self.report(diag));
+    }
+}
+
+/// Counts of the diagnostics of a whole compilation, printed as one line
+/// of JSON for tools.
+#[derive(Debug, Default)]
+pub struct Summary {
+    errors: usize,
+    warnings: usize,
+    lints: BTreeMap<&'static str, usize>,
+}
+
+impl Summary {
+    pub fn add(&mut self, diag: &Diagnostic) {
+        match diag.level {
+            Level::Error => self.errors += 1,
+            Level::Warning => self.warnings += 1,
+        }
+        if let Some(lint) = diag.lint {
+            *self.lints.entry(lint.name()).or_default() += 1;
+        }
+    }
+
+    // 致命错误(预处理/语法错误等)没有Diagnostic, 只计数
+    pub fn add_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors == 0 && self.warnings == 0
+    }
+
+    pub fn to_json(&self) -> String {
+        let lints: Vec<String> = self.lints
+            .iter()
+            .map(|(name, count)| format!("\"{}\":{}", name, count))
+            .collect();
+        format!(
+            "{{\"errors\":{},\"warnings\":{},\"lints\":{{{}}}}}",
+            self.errors,
+            self.warnings,
+            lints.join(",")
+        )
+    }
+}
+
+/// Renders a diagnostic against the source it was produced from, in the
+/// usual `file:line:col: level: message` form followed by the offending
+/// line.
+pub fn render(source: &Source, diag: &Diagnostic) -> String {
+    let mut out = String::new();
+    let message = match diag.lint {
+        Some(lint) => format!("{} [-W{}]", diag.message, lint.name()),
+        None => diag.message.clone(),
+    };
+    render_one(&mut out, source, diag.span, &diag.level.to_string(), &message);
+    for (span, note) in &diag.notes {
+        render_one(&mut out, source, *span, "note", note);
+    }
+    out
+}
+
+fn render_one(out: &mut String, source: &Source, span: Span, level: &str, message: &str) {
+    let loc = source.line_map.locate(&source.text, span.start);
+    out.push_str(&format!("{}: {}: {}\n", loc, level, message));
+    // 展示预处理后的那一行, 并在出错的区间下划线
+    let text = &source.text;
+    let start = span.start.min(text.len());
+    let line_start = text[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = text[start..].find('\n').map_or(text.len(), |pos| start + pos);
+    let line = &text[line_start..line_end];
+    let width = span.end.clamp(start + 1, line_end.max(start + 1)) - start;
+    out.push_str(&format!("    {}\n", line));
+    // 保留制表符, 使下划线与原文对齐
+    let indent: String = line[..start - line_start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    out.push_str(&format!("    {}^{}\n", indent, "~".repeat(width - 1)));
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use koopa::ir::{ BasicBlock, FunctionData, Value, ValueKind };
+
+// 基于生成的Koopa IR的控制流图工具
+
+pub fn is_terminator(kind: &ValueKind) -> bool {
+    matches!(kind, ValueKind::Return(_) | ValueKind::Jump(_) | ValueKind::Branch(_))
+}
+
+// 基本块的最后一条指令(如果是终结指令)
+pub fn terminator(func: &FunctionData, bb: BasicBlock) -> Option<Value> {
+    let last = *func.layout().bbs().node(&bb)?.insts().back_key()?;
+    if is_terminator(func.dfg().value(last).kind()) {
+        Some(last)
+    } else {
+        None
+    }
+}
+
+pub fn successors(func: &FunctionData, bb: BasicBlock) -> Vec<BasicBlock> {
+    match terminator(func, bb).map(|inst| func.dfg().value(inst).kind()) {
+        Some(ValueKind::Jump(jump)) => vec![jump.target()],
+        Some(ValueKind::Branch(branch)) => vec![branch.true_bb(), branch.false_bb()],
+        _ => vec![],
+    }
+}
+
+// 从入口块出发可达的基本块
+pub fn reachable_blocks(func: &FunctionData) -> HashSet<BasicBlock> {
+    let mut reachable = HashSet::new();
+    let mut stack: Vec<BasicBlock> = func.layout().entry_bb().into_iter().collect();
+    while let Some(bb) = stack.pop() {
+        if reachable.insert(bb) {
+            stack.extend(successors(func, bb));
+        }
+    }
+    reachable
+}
+
+/// Removes basic blocks unreachable from the entry, together with their
+/// instructions. Returns the number of removed blocks.
+pub fn remove_unreachable_blocks(func: &mut FunctionData) -> usize {
+    let reachable = reachable_blocks(func);
+    let dead: Vec<BasicBlock> = func
+        .layout()
+        .bbs()
+        .keys()
+        .filter(|bb| !reachable.contains(bb))
+        .copied()
+        .collect();
+    let mut values = vec![];
+    for bb in &dead {
+        let (_, node) = func.layout_mut().bbs_mut().remove(bb).unwrap();
+        values.extend(node.insts().keys().copied());
+    }
+    // 不可达的指令之间也可能互相使用, 先删除没有使用者的, 直到全部删除
+    loop {
+        let count = values.len();
+        values.retain(|&value| {
+            if func.dfg().value(value).used_by().is_empty() {
+                func.dfg_mut().remove_value(value);
+                false
+            } else {
+                true
+            }
+        });
+        if values.is_empty() || values.len() == count {
+            break;
+        }
+    }
+    for bb in &dead {
+        if func.dfg().bb(*bb).used_by().is_empty() {
+            func.dfg_mut().remove_bb(*bb);
+        }
+    }
+    dead.len()
+}
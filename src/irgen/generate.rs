@@ -1,630 +1,374 @@
-use super::{ eval::Eval, ASTValue, Error };
-use crate::{ ast::*, irgen::{ Context, Result } };
+use super::{ cfg, hir::*, lint, rewrite, Error };
+use crate::{ diagnostic::{ Diagnostic, Lint }, irgen::{ Context, Result } };
 use koopa::ir::{
     builder::{ BasicBlockBuilder, GlobalInstBuilder, LocalInstBuilder, ValueBuilder },
-    entities::ValueData,
     layout::BasicBlockNode,
     BasicBlock,
-    BinaryOp,
+    BinaryOp as KoopaBinaryOp,
     FunctionData,
     Program,
     Type,
-    TypeKind,
     Value,
 };
+
+// 由HIR生成Koopa IR; 名字与类型已经在降级时解析完毕, 这里只负责分配存储与生成指令
+
 pub trait GenerateProgram {
     type Out;
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out>;
 }
 
-impl GenerateProgram for CompUnit {
+impl GenerateProgram for Module {
     type Out = ();
 
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
         add_sysy_lib_func(program, ctx);
+        // 先为全局变量和函数创建Koopa中的实体, 再生成函数体
+        // 这样函数体中可以直接使用之后才定义的函数与extern声明的变量
         for item in &self.items {
             item.generate(program, ctx)?;
+            // 先声明后定义的函数在第一次声明处创建, 输出的IR中它出现在之前的调用者前面
+            if let Item::FuncDecl(id) = item {
+                if !ctx.funcs.contains_key(id) {
+                    if let Some(func) = self.items.iter().find_map(|item| match item {
+                        Item::Func(func) if func.symbol == *id => Some(func),
+                        _ => None,
+                    }) {
+                        func.declare(program, ctx);
+                    }
+                }
+            }
+        }
+        for item in &self.items {
+            if let Item::Func(func) = item {
+                func.generate(program, ctx)?;
+            }
         }
         Ok(())
     }
 }
 
-impl GenerateProgram for GlobalItem {
+// 为koopaIr注册sysy的库函数定义
+fn add_sysy_lib_func(program: &mut Program, ctx: &mut Context) {
+    let symbols = ctx.symbols;
+    for (id, symbol) in symbols.iter().enumerate() {
+        if symbol.storage != Storage::Builtin {
+            continue;
+        }
+        let params = symbol.params.iter().map(Ty::to_koopa).collect();
+        let decl = FunctionData::new_decl(format!("@{}", symbol.name), params, symbol.ty.to_koopa());
+        ctx.funcs.insert(id, program.new_func(decl));
+    }
+}
+
+impl GenerateProgram for Item {
     type Out = ();
 
+    // 全局的声明与定义: 函数在第一次声明或定义处创建, 函数体之后再生成
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
         match self {
-            GlobalItem::FuncDef(func_def) => func_def.generate(program, ctx),
-            GlobalItem::Decl(decl) => decl.generate(program, ctx),
+            Item::Func(func) => {
+                if !ctx.funcs.contains_key(&func.symbol) {
+                    func.declare(program, ctx);
+                }
+                Ok(())
+            }
+            Item::FuncDecl(id) => {
+                let symbol = ctx.symbol(*id);
+                let params: Vec<Type> = symbol.params.iter().map(Ty::to_koopa).collect();
+                let func_ty = Type::get_function(params.clone(), symbol.ty.to_koopa());
+                if symbol.def_span.is_none() && !ctx.funcs.contains_key(id) {
+                    let data = FunctionData::new_decl(format!("@{}", symbol.name), params, symbol.ty.to_koopa());
+                    ctx.funcs.insert(*id, program.new_func(data));
+                }
+                if symbol.internal {
+                    ctx.linkage.internal.insert(symbol.name.clone());
+                } else {
+                    ctx.linkage.declare(&symbol.name, func_ty);
+                }
+                Ok(())
+            }
+            Item::Decl(decl) => decl.generate(program, ctx),
         }
     }
 }
 
-impl GenerateProgram for FuncFParam {
-    type Out = (Option<String>, Type);
-    fn generate(&self, _: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        let mut p_type = match self.b_type {
-            BType::Int => Type::get_i32(),
-        };
-        if let Some(array_indices) = &self.array_indices {
-            let len = array_indices.len();
-            for i in 0..len {
-                let cur_len_exp = &array_indices[len - 1 - i];
-                let cur_len = cur_len_exp.eval(ctx)?;
-                p_type = Type::get_array(p_type, cur_len as usize);
-            }
-            p_type = Type::get_pointer(p_type);
-            println!("p_type {:?}", p_type);
+impl Func {
+    // 创建函数的Koopa实体并记录链接属性, 函数体之后再生成
+    fn declare(&self, program: &mut Program, ctx: &mut Context) {
+        let symbol = ctx.symbol(self.symbol);
+        let params = self.params
+            .iter()
+            .map(|&param| {
+                let param = ctx.symbol(param);
+                (Some(format!("@{}", param.name)), param.ty.to_koopa())
+            })
+            .collect();
+        let data = FunctionData::with_param_names(
+            format!("@{}", symbol.name),
+            params,
+            symbol.ty.to_koopa()
+        );
+        let koopa_func = program.new_func(data);
+        ctx.funcs.insert(self.symbol, koopa_func);
+        if symbol.internal {
+            ctx.linkage.internal.insert(symbol.name.clone());
+        } else {
+            ctx.linkage.define(&symbol.name, program.func(koopa_func).ty().clone());
         }
-        Ok((Some(format!("@{}", self.name).into()), p_type))
     }
 }
 
-impl GenerateProgram for FuncDef {
+impl GenerateProgram for Func {
     type Out = ();
 
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        if let Some(_) = ctx.scopes.look_up_func(&self.ident) {
-            return Err(Error::DuplicateDecl);
-        }
-        let func_ret_type = match self.func_type {
-            FuncType::Int => Type::get_i32(),
-            FuncType::Void => Type::get_unit(),
-        };
-        let func_params = self.params
-            .iter()
-            .map(|param| param.generate(program, ctx).unwrap())
-            .collect();
-
-        let func = program.new_func(
-            FunctionData::with_param_names(
-                format!("@{}", self.ident).into(),
-                func_params,
-                func_ret_type
-            )
-        );
+        let func = ctx.funcs[&self.symbol];
         ctx.curr_fuc = Some(func);
-        ctx.scopes.register_function(&self.ident, func);
-        let main = program.func_mut(func);
-        let entry1 = main.dfg_mut().new_bb().basic_block(Some("%entry".to_string()));
-        push_block(program, ctx, entry1)?;
-        ctx.new_scope();
-        for i in 0..self.params.len() {
-            let val = cur_func_mut(program, ctx).params()[i].clone();
-            let param_type = value_data_in_cur_func(program, ctx, val).ty().clone();
+        let entry = cur_func_mut(program, ctx)
+            .dfg_mut()
+            .new_bb()
+            .basic_block(Some("%entry".to_string()));
+        push_block(program, ctx, entry)?;
+        // 参数存到栈上, 之后与局部变量一样通过alloc访问
+        for (i, &param) in self.params.iter().enumerate() {
+            let val = cur_func_mut(program, ctx).params()[i];
+            let param_type = ctx.symbols[param].ty.to_koopa();
             let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(param_type);
             let store = cur_func_mut(program, ctx).dfg_mut().new_value().store(val, alloc);
             push_back_values_as_ins(program, ctx, vec![alloc, store]);
-            ctx.insert_symbol(&self.params[i].name, ASTValue::Variable(alloc));
+            ctx.values.insert(param, alloc);
         }
-        self.block.generate(program, ctx)?;
+        self.body.generate(program, ctx)?;
 
-        if let FuncType::Void = self.func_type {
-            if !self.end_with_ret() {
-                let ret = cur_func_mut(program, ctx).dfg_mut().new_value().ret(None);
-                push_back_value_as_ins(program, ctx, ret)?;
+        self.terminate_blocks(program, ctx)?;
+        cfg::remove_unreachable_blocks(cur_func_mut(program, ctx));
+        let traps = lint::constant_traps(program.func(func), &ctx.value_spans);
+        if ctx.options.hardened && !traps.is_empty() {
+            let trap_func = ctx.trap_func(program);
+            // 陷阱函数不会返回, 其后的运算不会执行
+            for (inst, _) in &traps {
+                rewrite::insert_call_before(program.func_mut(func), *inst, trap_func);
             }
         }
-        remove_useless_block(program, ctx);
-        ctx.leave_scope();
+        ctx.diagnostics.extend(traps.into_iter().map(|(_, diag)| diag));
+        let dead_stores = lint::dead_stores(program.func(func), &ctx.value_spans);
+        ctx.diagnostics.extend(dead_stores);
+        let uninit_uses = lint::uninit_uses(program.func(func), &ctx.value_spans);
+        ctx.diagnostics.extend(uninit_uses);
+        ctx.value_spans.clear();
         ctx.curr_fuc = None;
         Ok(())
     }
 }
 
-impl FuncDef {
-    fn end_with_ret(&self) -> bool {
-        self.block.items
-            .last()
-            .map_or(false, |item| { matches!(item, BlockItem::Stmt(Stmt::Ret(_))) })
-    }
-}
-
-impl GenerateProgram for Block {
-    type Out = ();
-
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        ctx.new_scope();
-        for ele in self.items.iter() {
-            ele.generate(program, ctx)?;
+impl Func {
+    // 函数体生成完毕后, 可达但没有以ret/jump/br结尾的基本块意味着控制流会从函数末尾离开
+    // void函数补上ret; int函数报告缺少返回值, 并补上ret 0使IR保持合法
+    fn terminate_blocks(&self, program: &mut Program, ctx: &mut Context) -> Result<()> {
+        let func_data = cur_func_mut(program, ctx);
+        let reachable = cfg::reachable_blocks(func_data);
+        let open_bbs: Vec<BasicBlock> = func_data
+            .layout()
+            .bbs()
+            .keys()
+            .filter(|bb| reachable.contains(bb) && cfg::terminator(func_data, **bb).is_none())
+            .copied()
+            .collect();
+        if open_bbs.is_empty() {
+            return Ok(());
+        }
+        let symbol = ctx.symbol(self.symbol);
+        for &bb in &open_bbs {
+            let func_data = cur_func_mut(program, ctx);
+            let ret_val = match symbol.ty {
+                Ty::Int => Some(func_data.dfg_mut().new_value().integer(0)),
+                _ => None,
+            };
+            let ret = func_data.dfg_mut().new_value().ret(ret_val);
+            func_data
+                .layout_mut()
+                .bb_mut(bb)
+                .insts_mut()
+                .push_key_back(ret)
+                .map_err(|_| Error::PushInstructionFailed)?;
+        }
+        // main函数从末尾离开时返回0, 与C语言一致
+        if symbol.ty == Ty::Int && symbol.name != "main" {
+            let message = format!(
+                "control reaches end of non-void function `{}` without returning a value",
+                symbol.name
+            );
+            let span = symbol.def_span.unwrap_or_default();
+            ctx.diagnostics.report(Diagnostic::lint(Lint::ImplicitReturn, span, message));
         }
-        ctx.leave_scope();
         Ok(())
     }
 }
 
-impl GenerateProgram for BlockItem {
+impl GenerateProgram for Block {
     type Out = ();
+
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            BlockItem::Stmt(stmt) => {
-                stmt.generate(program, ctx)?;
-            }
-            BlockItem::Decl(decl) => {
-                decl.generate(program, ctx)?;
-            }
-        }
-        Ok(())
+        self.stmts.iter().try_for_each(|stmt| stmt.generate(program, ctx))
     }
 }
 
 impl GenerateProgram for Decl {
     type Out = ();
+
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
         match self {
-            Decl::ConstDecl(const_decl) => {
-                const_decl.generate(program, ctx)?;
-            }
-            Decl::VarDecl(var_decl) => {
-                var_decl.generate(program, ctx)?;
+            Decl::Var(def) => def.generate(program, ctx),
+            Decl::Extern(id) => {
+                declare_extern_var(program, ctx, *id);
+                Ok(())
             }
         }
-        Ok(())
     }
 }
 
-impl GenerateProgram for VarDecl {
-    type Out = ();
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        for def in self.def_list.iter() {
-            def.generate(program, ctx)?;
-        }
-        Ok(())
+// extern声明的全局变量在本单元中没有定义时, 由其他编译单元定义
+// 以零初始化的全局变量占位, asmgen不为其分配空间
+fn declare_extern_var(program: &mut Program, ctx: &mut Context, id: SymbolId) {
+    let symbol = ctx.symbol(id);
+    if symbol.def_span.is_some() || ctx.values.contains_key(&id) {
+        return;
     }
-}
-pub trait GenType {
-    fn gen_type(&self, ctx: &mut Context) -> Type;
+    let ty = symbol.ty.to_koopa();
+    let init = program.new_value().zero_init(ty.clone());
+    let var = program.new_value().global_alloc(init);
+    program.set_value_name(var, Some(format!("@{}", symbol.name)));
+    ctx.linkage.external.insert(symbol.name.clone());
+    ctx.linkage.declare(&symbol.name, ty);
+    ctx.values.insert(id, var);
 }
 
-impl GenType for Vec<ConstExp> {
-    fn gen_type(&self, ctx: &mut Context) -> Type {
-        let mut ty = Type::get_i32();
-        for i in 0..self.len() {
-            let len_i = &self[self.len() - 1 - i];
-            let parsed_len = len_i.eval(ctx).unwrap();
-            ty = Type::get_array(ty, parsed_len as usize);
-        }
-        ty
-    }
-}
 impl GenerateProgram for VarDef {
     type Out = ();
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            VarDef::IdOnly(id, len) => {
-                let prev_def = ctx.look_up_in_curr_scope(id);
-                match prev_def {
-                    Some(_) => Err(Error::DuplicateDecl),
-                    None => {
-                        let alloc = if ctx.in_global_scope() {
-                            let ty = len.gen_type(ctx);
-                            let init = program.new_value().zero_init(ty);
-                            let alloc = program.new_value().global_alloc(init);
-                            program.set_value_name(alloc, Some(format!("@{}", id)));
-                            alloc
-                        } else {
-                            let func_data = program.func_mut(ctx.curr_fuc.unwrap());
-                            let ty = len.gen_type(ctx);
-                            let alloc = func_data.dfg_mut().new_value().alloc(ty);
-                            func_data.dfg_mut().set_value_name(alloc, Some(format!("@{}", id)));
-                            push_back_value_as_ins(program, ctx, alloc)?;
-                            alloc
-                        };
-                        ctx.insert_symbol(&id, ASTValue::Variable(alloc));
-                        Ok(())
-                    }
-                }
-            }
-
-            VarDef::Assign(id, len, init_val) => {
-                let prev_def = ctx.look_up_in_curr_scope(id);
-                match prev_def {
-                    Some(_) => Err(Error::DuplicateDecl),
-                    None => {
-                        let dims_result: Result<Vec<i32>> = len
-                            .iter()
-                            .map(|exp| exp.eval(ctx))
-                            .collect();
-                        let mut dim_vec = dims_result?;
-
-                        if ctx.in_global_scope() {
-                            let parsed_init_val = init_val.generate_init_val(
-                                program,
-                                ctx,
-                                &dim_vec,
-                                0
-                            )?;
-                            let init = if len.is_empty() {
-                                if let InitValResult::Value(val) = parsed_init_val {
-                                    val
-                                } else {
-                                    unreachable!();
-                                }
-                            } else {
-                                // 初始化list, folden flattened list
-                                if let InitValResult::List(list) = parsed_init_val {
-                                    let mut part_list = list;
-                                    let mut cur_agg;
-                                    let mut cur_idx = dim_vec.len() - 1;
-                                    while cur_idx > 0 {
-                                        let mut res_list = vec![];
-                                        cur_agg = vec![];
-                                        for i in 0..part_list.len() {
-                                            cur_agg.push(part_list[i]);
-                                            if
-                                                (i as i32) % dim_vec[cur_idx] ==
-                                                dim_vec[cur_idx] - 1
-                                            {
-                                                res_list.push(
-                                                    program.new_value().aggregate(cur_agg)
-                                                );
-                                                cur_agg = vec![];
-                                            }
-                                        }
-                                        cur_idx = cur_idx - 1;
-                                        part_list = res_list;
-                                    }
-                                    program.new_value().aggregate(part_list)
-                                } else {
-                                    unreachable!();
-                                }
-                            };
-                            let alloc = program.new_value().global_alloc(init);
-                            program.set_value_name(alloc, Some(format!("@{}", id)));
-                            ctx.insert_symbol(
-                                &format!("{}", id).to_owned(),
-                                ASTValue::Variable(alloc)
-                            );
-                            return Ok(());
-                        }
-
-                        let init_res = init_val.generate_init_val(program, ctx, &dim_vec, 0)?;
-                        // 处理普通变量赋值
-                        if len.is_empty() {
-                            let alloc = cur_func_mut(program, ctx)
-                                .dfg_mut()
-                                .new_value()
-                                .alloc(Type::get_i32());
-
-                            if let InitValResult::Value(val) = init_res {
-                                let store = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .store(val, alloc);
-                                push_back_values_as_ins(program, ctx, vec![alloc, store]);
-                                cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .set_value_name(alloc, Some(format!("@{}", id)));
 
-                                ctx.insert_symbol(
-                                    &format!("{}", id).to_owned(),
-                                    ASTValue::Variable(alloc)
-                                );
-                            } else {
-                                unreachable!();
-                            }
-                            return Ok(());
-                        }
-
-                        // 处理多维数组赋值
-                        // let mut ptr_array = vec![];
-                        let ty = len.gen_type(ctx);
-                        let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(ty);
-
-                        push_back_value_as_ins(program, ctx, alloc)?;
-                        cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .set_value_name(alloc, Some(format!("@{}", id)));
-
-                        let mut cur_idx_array: Vec<i32> = len
-                            .iter()
-                            .map(|_| 0)
-                            .collect();
-                        // 每次inc一个多维数组的idx;直到赋值完所有
-                        while cur_idx_array[0] < dim_vec[0] {
-                            let mut ptr = alloc;
-                            let mut ptrs = vec![];
-                            for i in 0..cur_idx_array.len() {
-                                let idx = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .integer(cur_idx_array[i]);
-                                ptr = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .get_elem_ptr(ptr, idx);
-                                ptrs.push(ptr);
-                            }
-                            let mut idx = cur_idx_array[0];
-                            for i in 1..cur_idx_array.len() {
-                                idx = idx * dim_vec[i] + cur_idx_array[i];
-                            }
-                            let store = cur_func_mut(program, ctx)
-                                .dfg_mut()
-                                .new_value()
-                                .store(init_res.get_index(idx as usize).clone(), ptr);
-                            ptrs.push(store);
-                            push_back_values_as_ins(program, ctx, ptrs);
-                            inc(&mut dim_vec, &mut cur_idx_array);
-                        }
-                        ctx.insert_symbol(&format!("{}", id).to_owned(), ASTValue::Variable(alloc));
-
-                        Ok(())
-                    }
-                }
-            }
-        }
-    }
-}
-
-fn inc(len: &mut Vec<i32>, idx: &mut Vec<i32>) -> bool {
-    let mut cur_idx = idx.len() - 1;
-    loop {
-        idx[cur_idx] = idx[cur_idx] + 1;
-        if idx[cur_idx] == len[cur_idx] {
-            if cur_idx == 0 {
-                return true;
-            } else {
-                idx[cur_idx] = 0;
-                cur_idx = cur_idx - 1;
+    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
+        let symbol = ctx.symbol(self.symbol);
+        // 全局变量与static局部变量分配在全局数据段, 初始值已在编译期求值
+        if ctx.in_global_scope() || symbol.storage == Storage::Static {
+            let init = global_init_value(program, &symbol.ty, &self.init);
+            let alloc = program.new_value().global_alloc(init);
+            let name = ctx.global_symbol_name(program, symbol);
+            program.set_value_name(alloc, Some(name));
+            if ctx.in_global_scope() && !symbol.internal {
+                ctx.linkage.define(&symbol.name, symbol.ty.to_koopa());
             }
-        } else {
-            return false;
+            ctx.values.insert(self.symbol, alloc);
+            return Ok(());
         }
-    }
-}
 
-#[derive(Debug)]
-pub enum InitValResult {
-    Value(Value),
-    List(Vec<Value>),
-}
-
-impl InitValResult {
-    fn get_index(&self, idx: usize) -> Value {
-        if let InitValResult::List(list) = self { list[idx] } else { unreachable!() }
-    }
-}
-
-impl ConstInitVal {
-    fn generate_init_val(
-        &self,
-        program: &mut Program,
-        ctx: &mut Context,
-        dims: &[i32],
-        cur_idx: i32
-    ) -> Result<InitValResult> {
-        let mut idx = cur_idx;
-        match self {
-            ConstInitVal::Single(exp) => {
-                let res = exp.eval(ctx)?;
-                if ctx.in_global_scope() {
-                    Ok(InitValResult::Value(program.new_value().integer(res)))
-                } else {
-                    Ok(
-                        InitValResult::Value(
-                            cur_func_mut(program, ctx).dfg_mut().new_value().integer(res)
-                        )
-                    )
-                }
+        let name = Some(format!("@{}", symbol.name));
+        let alloc = match &self.init {
+            // 没有初始值的局部变量, 供未初始化使用的检查
+            None => {
+                let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(symbol.ty.to_koopa());
+                push_back_value_as_ins(program, ctx, alloc)?;
+                ctx.value_spans.insert(alloc, self.span);
+                alloc
             }
-            ConstInitVal::List(list) => {
-                let size: i32 = dims.iter().fold(1, |acc, &x| acc * x);
-                if idx % dims[dims.len() - 1] != 0 || idx >= size {
-                    panic!("FuckedUp");
-                }
-                let mut res: Vec<Value> = vec![];
-                for sub in list {
-                    let mut remain = idx;
-                    // 从数组最后一列开始,向前整除,看看当前状态下,如果生成list,那么对齐的是哪一维
-                    // int[2][3][4] {1, 2, 3, 4, {5}} ,idx = 4,对应int[4]
-                    // int[2][3][4] {1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4, {5}}, idx=12,对应 int[3][4]
-                    // int[2][3][4] {{5}} ,idx = 0,对应 int[3][4]
-                    let mut current = dims.len() - 1;
-                    while remain % dims[current] == 0 && current >= 1 {
-                        current = current - 1;
-                        remain = remain / dims[current];
-                    }
-                    current = current + 1;
-                    let sub_res = sub.generate_init_val(program, ctx, &dims[current..], cur_idx)?;
-                    match sub_res {
-                        InitValResult::List(mut sub_list) => {
-                            res.append(&mut sub_list);
-                            idx = idx + (sub_list.len() as i32);
-                        }
-                        InitValResult::Value(val) => {
-                            res.push(val);
-                            idx = idx + 1;
-                        }
-                    }
-                }
-                // 补零
-                let zero = if ctx.in_global_scope() {
-                    program.new_value().integer(0)
-                } else {
-                    cur_func_mut(program, ctx).dfg_mut().new_value().integer(0)
-                };
-                while res.len() < (size as usize) {
-                    res.push(zero);
-                }
-                Ok(InitValResult::List(res))
+            // 标量先计算初始值, 再分配并写入
+            Some(Init::Exprs(elems)) if symbol.ty == Ty::Int => {
+                let val = elems[0].1.generate(program, ctx)?;
+                let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(Type::get_i32());
+                let store = cur_func_mut(program, ctx).dfg_mut().new_value().store(val, alloc);
+                push_back_values_as_ins(program, ctx, vec![alloc, store]);
+                ctx.value_spans.insert(store, self.span);
+                alloc
             }
-        }
-    }
-}
-
-impl InitVal {
-    fn generate_init_val(
-        &self,
-        program: &mut Program,
-        ctx: &mut Context,
-        dims: &[i32],
-        cur_idx: i32
-    ) -> Result<InitValResult> {
-        let mut idx = cur_idx;
-        match self {
-            InitVal::Single(exp) =>
-                Ok(
-                    if ctx.in_global_scope() {
-                        let res = exp.eval(ctx)?;
-                        InitValResult::Value(program.new_value().integer(res))
-                    } else {
-                        InitValResult::Value(exp.generate(program, ctx)?)
-                    }
-                ),
-            InitVal::List(list) => {
-                let size: i32 = dims.iter().fold(1, |acc, &x| acc * x);
-                if idx % dims[dims.len() - 1] != 0 || idx >= size {
-                    panic!("FuckedUp");
-                }
-                let mut res: Vec<Value> = vec![];
-                for sub in list {
-                    let mut remain = idx;
-                    // 从数组最后一列开始,向前整除,看看当前状态下,如果生成list,那么对齐的是哪一维
-                    // int[2][3][4] {1, 2, 3, 4, {5}} ,idx = 4,对应int[4]
-                    // int[2][3][4] {1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4, {5}}, idx=12,对应 int[3][4]
-                    // int[2][3][4] {{5}} ,idx = 0,对应 int[3][4]
-                    let mut current = dims.len() - 1;
-                    while remain % dims[current] == 0 && current >= 1 {
-                        current = current - 1;
-                        remain = remain / dims[current];
-                    }
-                    current = current + 1;
-                    let sub_res = sub.generate_init_val(program, ctx, &dims[current..], cur_idx)?;
-                    match sub_res {
-                        InitValResult::List(mut sub_list) => {
-                            res.append(&mut sub_list);
-                            idx = idx + (sub_list.len() as i32);
-                        }
-                        InitValResult::Value(val) => {
-                            res.push(val);
-                            idx = idx + 1;
-                        }
-                    }
-                }
-                // 补零
-                let zero = if ctx.in_global_scope() {
-                    program.new_value().integer(0)
-                } else {
-                    cur_func_mut(program, ctx).dfg_mut().new_value().integer(0)
-                };
-                while res.len() < (size as usize) {
-                    res.push(zero);
+            Some(init) => {
+                let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(symbol.ty.to_koopa());
+                push_back_value_as_ins(program, ctx, alloc)?;
+                let dims = symbol.ty.dims();
+                match init {
+                    Init::Exprs(elems) =>
+                        init_local_array(program, ctx, alloc, &dims, elems, |program, ctx, exp|
+                            exp.generate(program, ctx)
+                        )?,
+                    Init::Const(vals) =>
+                        init_local_array(program, ctx, alloc, &dims, vals, |program, ctx, val| {
+                            Ok(cur_func_mut(program, ctx).dfg_mut().new_value().integer(*val))
+                        })?,
                 }
-                Ok(InitValResult::List(res))
+                alloc
             }
-        }
-    }
-}
-
-impl GenerateProgram for ConstDecl {
-    type Out = ();
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        for ele in self.def_list.iter() {
-            ele.generate(program, ctx)?;
-        }
+        };
+        cur_func_mut(program, ctx).dfg_mut().set_value_name(alloc, name);
+        ctx.values.insert(self.symbol, alloc);
         Ok(())
     }
 }
 
-impl GenerateProgram for ConstDef {
-    type Out = ();
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match &self.dims.len() {
-            0 => {
-                let eval_val = self.init_val.eval(ctx)?;
-                ctx.insert_symbol(&self.id.clone(), ASTValue::Const(eval_val));
-            }
-            _ => {
-                let dims_result: Result<Vec<i32>> = self.dims
-                    .iter()
-                    .map(|exp| exp.eval(ctx))
-                    .collect();
-                let mut dim_vec = dims_result?;
-                let parsed_init_val = self.init_val.generate_init_val(program, ctx, &dim_vec, 0)?;
-                // 处理多维数组赋值
-                // let mut ptr_array = vec![];
-
-                let ty = self.dims.gen_type(ctx);
-                if ctx.in_global_scope() {
-                    if let InitValResult::List(list) = parsed_init_val {
-                        // 初始化list, folden flattened list
-                        let mut part_list = list;
-                        let mut cur_agg;
-                        let mut cur_idx = dim_vec.len() - 1;
-                        while cur_idx > 0 {
-                            let mut res_list = vec![];
-                            cur_agg = vec![];
-                            for i in 0..part_list.len() {
-                                cur_agg.push(part_list[i]);
-                                if (i as i32) % dim_vec[cur_idx] == dim_vec[cur_idx] - 1 {
-                                    res_list.push(program.new_value().aggregate(cur_agg));
-                                    cur_agg = vec![];
-                                }
-                            }
-                            cur_idx = cur_idx - 1;
-                            part_list = res_list;
-                        }
-                        let init = program.new_value().aggregate(part_list);
-
-                        let alloc = program.new_value().global_alloc(init);
-                        program.set_value_name(alloc, Some(format!("@{}", self.id)));
-                        ctx.insert_symbol(
-                            &format!("{}", self.id).to_owned(),
-                            ASTValue::Variable(alloc)
-                        );
-                        return Ok(());
-                    } else {
-                        unreachable!();
-                    }
-                }
-
-                let alloc = cur_func_mut(program, ctx).dfg_mut().new_value().alloc(ty);
-
-                push_back_value_as_ins(program, ctx, alloc)?;
-                let mut cur_idx_array: Vec<i32> = self.dims
-                    .iter()
-                    .map(|_| 0)
-                    .collect();
-                // 每次inc一个多维数组的idx;直到赋值完所有
-                while cur_idx_array[0] < dim_vec[0] {
-                    let mut ptr = alloc;
-                    let mut ptrs = vec![];
-                    for i in 0..cur_idx_array.len() {
-                        let idx = cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .new_value()
-                            .integer(cur_idx_array[i]);
-                        ptr = cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .new_value()
-                            .get_elem_ptr(ptr, idx);
-                        ptrs.push(ptr);
-                    }
-                    let mut idx = cur_idx_array[0];
-                    for i in 1..cur_idx_array.len() {
-                        idx = idx * dim_vec[i] + cur_idx_array[i];
-                    }
-                    let store = cur_func_mut(program, ctx)
-                        .dfg_mut()
-                        .new_value()
-                        .store(parsed_init_val.get_index(idx as usize).clone(), ptr);
-                    ptrs.push(store);
-                    push_back_values_as_ins(program, ctx, ptrs);
-                    inc(&mut dim_vec, &mut cur_idx_array);
-                }
-                ctx.insert_symbol(&format!("{}", self.id).to_owned(), ASTValue::Variable(alloc));
-            }
+// 全局变量的初始值: 没有初始值时为zeroinit, 全为0的子数组同样用zeroinit表示
+fn global_init_value(program: &mut Program, ty: &Ty, init: &Option<Init>) -> Value {
+    let vals = match init {
+        None => {
+            return program.new_value().zero_init(ty.to_koopa());
         }
-        Ok(())
-    }
+        Some(Init::Const(vals)) => vals,
+        Some(Init::Exprs(_)) => unreachable!("global initializers are evaluated during lowering"),
+    };
+    build_aggregate(program, &ty.dims(), 0, vals)
+}
+
+// vals为落在[base, base + 子数组大小)内的非稀疏部分
+fn build_aggregate(program: &mut Program, dims: &[usize], base: usize, vals: &[(usize, i32)]) -> Value {
+    if dims.is_empty() {
+        return program.new_value().integer(vals.first().map_or(0, |(_, val)| *val));
+    }
+    if vals.iter().all(|(_, val)| *val == 0) {
+        return program.new_value().zero_init(Ty::array(dims).to_koopa());
+    }
+    let stride: usize = dims[1..].iter().product();
+    let elems = (0..dims[0])
+        .map(|i| {
+            let start = base + i * stride;
+            let lo = vals.partition_point(|(idx, _)| *idx < start);
+            let hi = vals.partition_point(|(idx, _)| *idx < start + stride);
+            build_aggregate(program, &dims[1..], start, &vals[lo..hi])
+        })
+        .collect();
+    program.new_value().aggregate(elems)
 }
 
-impl GenerateProgram for ConstExp {
-    type Out = i32;
-    fn generate(&self, _: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        self.exp.eval(ctx)
+// 局部数组的初始化: 按下标顺序依次计算显式给出的元素并store, 其余位置store 0
+fn init_local_array<E>(
+    program: &mut Program,
+    ctx: &mut Context,
+    alloc: Value,
+    dims: &[usize],
+    elems: &[(usize, E)],
+    gen: impl Fn(&mut Program, &mut Context, &E) -> Result<Value>
+) -> Result<()> {
+    let size: usize = dims.iter().product();
+    let mut explicit = elems.iter().peekable();
+    for pos in 0..size {
+        let val = match explicit.next_if(|(idx, _)| *idx == pos) {
+            Some((_, exp)) => gen(program, ctx, exp)?,
+            None => cur_func_mut(program, ctx).dfg_mut().new_value().integer(0),
+        };
+        // 由展平的下标还原各维下标, 逐维getelemptr
+        let mut ptr = alloc;
+        let mut ptrs = vec![];
+        let mut stride = size;
+        for &len in dims {
+            stride /= len;
+            let idx = cur_func_mut(program, ctx)
+                .dfg_mut()
+                .new_value()
+                .integer(((pos / stride) % len) as i32);
+            ptr = cur_func_mut(program, ctx).dfg_mut().new_value().get_elem_ptr(ptr, idx);
+            ptrs.push(ptr);
+        }
+        let store = cur_func_mut(program, ctx).dfg_mut().new_value().store(val, ptr);
+        ptrs.push(store);
+        push_back_values_as_ins(program, ctx, ptrs);
     }
+    Ok(())
 }
 
 impl GenerateProgram for Stmt {
@@ -632,120 +376,44 @@ impl GenerateProgram for Stmt {
 
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
         match self {
-            Stmt::Ret(exp_op) => {
+            Stmt::Return(exp) => {
                 // todo 优化: 基本块的出口是唯一的,
                 // 翻译完return后可以在ctx中关闭基本块, 这样一些递归后序操作（比如if-else的尾部跳转指令）就不用加进去
-                let res_val = exp_op
-                    .as_ref()
-                    .map(|exp| { exp.generate(program, ctx).unwrap() })
-                    .or(None);
+                let res_val = match exp {
+                    Some(exp) => Some(exp.generate(program, ctx)?),
+                    None => None,
+                };
                 let ret = cur_func_mut(program, ctx).dfg_mut().new_value().ret(res_val);
                 push_back_value_as_ins(program, ctx, ret)?;
-                next_bb(program, ctx)?;
-                Ok(())
-            }
-            Stmt::IfStmt(if_stmt) => if_stmt.generate(program, ctx),
-
-            Stmt::Assign(lval, exp) => {
-                let sym_val = ctx.look_up_symbol(&lval.id);
-                match sym_val {
-                    Some(ASTValue::Const(_)) => Err(Error::RedefineConstValue),
-                    None => Err(Error::UnknownSymbol),
-                    Some(ASTValue::Variable(var)) => {
-                        let mut dst = var.clone();
-                        let mut is_ptr_ptr = false;
-                        if !dst.is_global() {
-                            if
-                                let TypeKind::Pointer(base) = value_data_in_cur_func(
-                                    program,
-                                    ctx,
-                                    dst
-                                )
-                                    .ty()
-                                    .kind()
-                            {
-                                if let TypeKind::Pointer(_) = base.kind() {
-                                    is_ptr_ptr = true;
-                                    dst = cur_func_mut(program, ctx)
-                                        .dfg_mut()
-                                        .new_value()
-                                        .load(dst);
-                                    push_back_value_as_ins(program, ctx, dst)?;
-                                }
-                            }
-                        }
-
-                        for i in 0..lval.indices.len() {
-                            let idx = lval.indices[i].generate(program, ctx)?;
-                            dst = if is_ptr_ptr && i == 0 {
-                                let dst = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .get_ptr(dst, idx);
-                                is_ptr_ptr = false;
-                                dst
-                            } else {
-                                let dst = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .get_elem_ptr(dst, idx);
-                                dst
-                            };
-                            push_back_value_as_ins(program, ctx, dst)?;
-                        }
-                        let rval = exp.generate(program, ctx)?;
-                        let store_ins: Value = cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .new_value()
-                            .store(rval, dst);
-                        push_back_value_as_ins(program, ctx, store_ins)?;
-                        Ok(())
-                    }
-                }
+                next_bb(program, ctx)
             }
+            Stmt::If(if_stmt) => if_stmt.generate(program, ctx),
             Stmt::While(while_stmt) => while_stmt.generate(program, ctx),
             Stmt::Exp(exp) => {
-                exp.as_ref().map(|e| e.generate(program, ctx));
+                if let Some(exp) = exp {
+                    exp.generate(program, ctx)?;
+                }
                 Ok(())
             }
+            Stmt::Decl(decl) => decl.generate(program, ctx),
             Stmt::Block(block) => block.generate(program, ctx),
-            Stmt::Break(break_stmt) => break_stmt.generate(program, ctx),
-            Stmt::Continue(cont) => cont.generate(program, ctx),
-        }
-    }
-}
-
-impl GenerateProgram for Break {
-    type Out = ();
-
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match ctx.peek_break_dst() {
-            None => Err(Error::InvalidBreak),
-            Some(bb) => {
-                let jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(bb);
+            // break/continue是否在循环中已在降级时检查
+            Stmt::Break => {
+                let dst = ctx.peek_break_dst().ok_or(Error::InvalidBreak)?;
+                let jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(dst);
                 push_back_value_as_ins(program, ctx, jump)?;
-                next_bb(program, ctx)?;
-                Ok(())
+                next_bb(program, ctx)
             }
-        }
-    }
-}
-
-impl GenerateProgram for Continue {
-    type Out = ();
-
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match ctx.peek_cont_dst() {
-            None => Err(Error::InvalidContinue),
-            Some(bb) => {
-                let jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(bb);
+            Stmt::Continue => {
+                let dst = ctx.peek_cont_dst().ok_or(Error::InvalidContinue)?;
+                let jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(dst);
                 push_back_value_as_ins(program, ctx, jump)?;
-                next_bb(program, ctx)?;
-                Ok(())
+                next_bb(program, ctx)
             }
         }
     }
 }
+
 impl GenerateProgram for While {
     type Out = ();
 
@@ -787,7 +455,8 @@ impl GenerateProgram for While {
         Ok(())
     }
 }
-impl GenerateProgram for IfStmt {
+
+impl GenerateProgram for If {
     type Out = ();
 
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
@@ -824,7 +493,9 @@ impl GenerateProgram for IfStmt {
 
         // 生产else_block的语句。主体stmt + jump end指令
         push_block(program, ctx, else_block)?;
-        self.else_stmt.as_ref().map(|stmt| stmt.generate(program, ctx));
+        if let Some(stmt) = &self.else_stmt {
+            stmt.generate(program, ctx)?;
+        }
         let else_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
         push_back_value_as_ins(program, ctx, else_jump)?;
 
@@ -833,378 +504,176 @@ impl GenerateProgram for IfStmt {
         Ok(())
     }
 }
-impl GenerateProgram for Exp {
-    type Out = Value;
-
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            Exp::LOrExp(lor_exp) => lor_exp.generate(program, ctx),
-        }
-    }
-}
-
-impl GenerateProgram for AddExp {
-    type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            AddExp::MulExp(mul_exp) => mul_exp.generate(program, ctx),
-            AddExp::AddExp(left, op, right) => {
-                let left_value = left.generate(program, ctx)?;
-                let right_value = right.generate(program, ctx)?;
-                let koopa_op: BinaryOp = match op {
-                    AddOp::Add => BinaryOp::Add,
-                    AddOp::Minus => BinaryOp::Sub,
-                };
-                register_binary(program, ctx, left_value, right_value, koopa_op)
-            }
-        }
-    }
-}
 
-impl GenerateProgram for MulExp {
+impl GenerateProgram for Expr {
     type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            MulExp::UnaryExp(unary_exp) => unary_exp.generate(program, ctx),
-            MulExp::MulExp(left, op, right) => {
-                let left_value: Value = left.generate(program, ctx)?;
-                let right_value = right.generate(program, ctx)?;
-                let koopa_op: BinaryOp = match op {
-                    MulOp::Multi => BinaryOp::Mul,
-                    MulOp::Divide => BinaryOp::Div,
-                    MulOp::Mod => BinaryOp::Mod,
-                };
-                register_binary(program, ctx, left_value, right_value, koopa_op)
-            }
-        }
-    }
-}
 
-impl GenerateProgram for RelExp {
-    type Out = Value;
+    // 表达式的值
     fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            RelExp::AddExp(add) => add.generate(program, ctx),
-            RelExp::RelExp(left, op, right) => {
-                let left_value: Value = left.generate(program, ctx)?;
-                let right_value = right.generate(program, ctx)?;
-                let koopa_op: BinaryOp = match op {
-                    RelOp::Ge => BinaryOp::Ge,
-                    RelOp::Gt => BinaryOp::Gt,
-                    RelOp::Le => BinaryOp::Le,
-                    RelOp::Lt => BinaryOp::Lt,
-                };
-                register_binary(program, ctx, left_value, right_value, koopa_op)
-            }
-        }
-    }
-}
-
-impl GenerateProgram for EqExp {
-    type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            EqExp::RelExp(rel) => rel.generate(program, ctx),
-            EqExp::EqExp(left, op, right) => {
-                let left_value: Value = left.generate(program, ctx)?;
-                let right_value = right.generate(program, ctx)?;
-                let koopa_op: BinaryOp = match op {
-                    EqOp::Eq => BinaryOp::Eq,
-                    EqOp::Ne => BinaryOp::NotEq,
-                };
-                register_binary(program, ctx, left_value, right_value, koopa_op)
+        match &self.kind {
+            // num是一个dfg中的value,但不对应指令
+            ExprKind::Number(num) => Ok(cur_func_mut(program, ctx).dfg_mut().new_value().integer(*num)),
+            ExprKind::Var(_) | ExprKind::Index(..) => {
+                unreachable!("places are only used through load, decay or assignment")
             }
-        }
-    }
-}
-
-impl GenerateProgram for LAndExp {
-    type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            LAndExp::EqExp(eq) => eq.generate(program, ctx),
-            LAndExp::LAndExp(left, _, right) => {
-                //  当前块 1.申请一个临时变量 2.val left条件 3.branch
-                // then_block 此分支说明短路, 那么直接往临时变量上写结果
-                // else_block 此分支说明未短路，eval right,结果写到变量上
-                // end_block  读取临时变量,作为此表达式的返回结果
-                let left_value: Value = left.generate(program, ctx)?;
-                let func_data = program.func_mut(ctx.curr_fuc.unwrap());
-                let zero = func_data.dfg_mut().new_value().integer(0);
-                let res: Value = func_data.dfg_mut().new_value().alloc(Type::get_i32());
-                let left_false = register_binary(program, ctx, left_value, zero, BinaryOp::Eq)?;
-                let then_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%then_block".into()));
-                let else_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%else".into()));
-                let end_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%ifend".into()));
-                let branch = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .branch(left_false, then_block, else_block);
-                push_back_values_as_ins(program, ctx, vec![res, branch]);
-
-                push_block(program, ctx, then_block)?;
-                let inst_1 = cur_func_mut(program, ctx).dfg_mut().new_value().integer(0);
-                let store_res: Value = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .store(inst_1, res);
-                let then_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
-                push_back_values_as_ins(program, ctx, vec![store_res, then_jump]);
-
-                push_block(program, ctx, else_block)?;
-
-                let right_value = right.generate(program, ctx)?;
-                let right_bool = register_binary(program, ctx, right_value, zero, BinaryOp::NotEq)?;
-                let store_res: Value = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .store(right_bool, res);
-                let then_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
-                push_back_values_as_ins(program, ctx, vec![store_res, then_jump]);
-
-                push_block(program, ctx, end_block)?;
-                let load = cur_func_mut(program, ctx).dfg_mut().new_value().load(res);
+            ExprKind::Load(place) => {
+                let src = place.generate_place(program, ctx)?;
+                let load = cur_func_mut(program, ctx).dfg_mut().new_value().load(src);
                 push_back_value_as_ins(program, ctx, load)?;
-
+                ctx.value_spans.insert(load, self.span);
                 Ok(load)
             }
-        }
-    }
-}
-
-impl GenerateProgram for LOrExp {
-    type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            LOrExp::LAndExp(and) => and.generate(program, ctx),
-            LOrExp::LOrExp(left, _, right) => {
-                let left_value: Value = left.generate(program, ctx)?;
-                let func_data = program.func_mut(ctx.curr_fuc.unwrap());
-                let zero = func_data.dfg_mut().new_value().integer(0);
-                let res: Value = func_data.dfg_mut().new_value().alloc(Type::get_i32());
-                let left_bool = register_binary(program, ctx, left_value, zero, BinaryOp::NotEq)?;
-                let then_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%then_block".into()));
-                let else_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%else".into()));
-                let end_block = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_bb()
-                    .basic_block(Some("%ifend".into()));
-                let branch = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .branch(left_bool, then_block, else_block);
-
-                push_back_values_as_ins(program, ctx, vec![res, branch]);
-                push_block(program, ctx, then_block)?;
-                let inst_1 = cur_func_mut(program, ctx).dfg_mut().new_value().integer(1);
-                let store_res: Value = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .store(inst_1, res);
-                let then_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
-                push_back_values_as_ins(program, ctx, vec![store_res, then_jump]);
-
-                push_block(program, ctx, else_block)?;
-
-                let right_value = right.generate(program, ctx)?;
-                let right_bool = register_binary(program, ctx, right_value, zero, BinaryOp::NotEq)?;
-                let store_res: Value = cur_func_mut(program, ctx)
-                    .dfg_mut()
-                    .new_value()
-                    .store(right_bool, res);
-                let then_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
-                push_back_values_as_ins(program, ctx, vec![store_res, then_jump]);
-
-                push_block(program, ctx, end_block)?;
-                let load = cur_func_mut(program, ctx).dfg_mut().new_value().load(res);
-                push_back_value_as_ins(program, ctx, load)?;
-                Ok(load)
+            ExprKind::Decay(place) => {
+                let src = place.generate_place(program, ctx)?;
+                let zero = cur_func_mut(program, ctx).dfg_mut().new_value().integer(0);
+                let ptr = cur_func_mut(program, ctx).dfg_mut().new_value().get_elem_ptr(src, zero);
+                push_back_value_as_ins(program, ctx, ptr)?;
+                Ok(ptr)
             }
-        }
-    }
-}
-impl GenerateProgram for LVal {
-    type Out = Value;
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        let is_array = !self.indices.is_empty();
-        // lval作为表达式（即出现在等号右边时),此时需要求值
-        // 1. name => 内存位置 (查env//符号表)
-        // 2. 内存位置 => 内存值 (koopa中的load, 读出val作为指针实际指向的栈值，作为表达式的返回)
-        let value: Option<&ASTValue> = ctx.look_up_symbol(&self.id);
-        match value {
-            None => Err(super::Error::UnknownSymbol),
-            Some(ASTValue::Const(val)) => {
-                assert!(!is_array);
-                let local_val = val.clone();
-                // 表达式中的左值,如果是常量,直接取解析结果
-                Ok(cur_func_mut(program, ctx).dfg_mut().new_value().integer(local_val))
+            ExprKind::Unary(op, operand) => {
+                let rhs = operand.generate(program, ctx)?;
+                let koopa_op = match op {
+                    UnaryOp::Neg => KoopaBinaryOp::Sub,
+                    UnaryOp::Not => KoopaBinaryOp::Eq,
+                };
+                let zero = cur_func_mut(program, ctx).dfg_mut().new_value().integer(0);
+                let res = register_binary(program, ctx, zero, rhs, koopa_op)?;
+                ctx.value_spans.insert(res, self.span);
+                Ok(res)
             }
-            Some(ASTValue::Variable(var)) => {
-                let mut dst = var.clone();
-                // 存在这两种情况
-                // 1.var本身是一个本地栈上alloc的array,此时var是一个pointer(array) (at see alloc)
-                // 2.函数的形参为一个数组,此时函数形参本身是一个pointer(i32),
-                // 但是scope中存的name对应的var 是本地栈上alloc出的一个栈帧,逻辑上类型是一个pointer(pointer(i32)) (at see funcParam.generate)
-                //
-                let mut is_ptr_ptr = false;
-                // 处理下函数形参为数组的情况,此时符号表中存的是一个二阶指针,需要先load一次
-                if !dst.is_global() {
-                    if
-                        let TypeKind::Pointer(base) = value_data_in_cur_func(program, ctx, dst)
-                            .ty()
-                            .kind()
-                    {
-                        if let TypeKind::Pointer(_) = base.kind() {
-                            is_ptr_ptr = true;
-                            dst = cur_func_mut(program, ctx).dfg_mut().new_value().load(dst);
-                            push_back_value_as_ins(program, ctx, dst)?;
-                        }
-                    }
-                }
-
-                for i in 0..self.indices.len() {
-                    let idx = self.indices[i].generate(program, ctx)?;
-                    dst = if is_ptr_ptr && i == 0 {
-                        let dst = cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .new_value()
-                            .get_ptr(dst, idx);
-                        is_ptr_ptr = false;
-                        dst
-                    } else {
-                        let dst = cur_func_mut(program, ctx)
-                            .dfg_mut()
-                            .new_value()
-                            .get_elem_ptr(dst, idx);
-                        dst
-                    };
-                    push_back_value_as_ins(program, ctx, dst)?;
-                }
-
-                // if dst.is_global() {
-                //     let load = cur_func_mut(program, ctx).dfg_mut().new_value().load(dst);
-                //     push_back_value_as_ins(program, ctx, load)?;
-                //     return Ok(load);
-                // }
-                // 是上层传递的指针 且没有经过任何一次解引用,那么透传load过一次的值
-                // int arr[][10] => arr 直接透传传进来的arr指针的值 *i32
-                if is_ptr_ptr {
-                    return Ok(dst);
-                }
-                // 至少经过一次解引用
-                // 如果是数组指针,那么返回首元素地址
-                // int arr[10][10] => arr 透传 *int[10]; arr[0] 透传 *i32
-                // int arr[][10] => arr[1] 得到的是一个 *i32[10],取首元素地址 ,透传*i32
-                // 如果是整数指针,load出值
-                // int arr[10][10] => arr[0][0] 得到 *i32,load
-                // int arr[][10] => arr[0] 得到 *(i32[10]),再getEle得到 *i32,再load出来
-                // int arr[] => arr, 栈上的arr是**i32,默认第一次load后是*i32,但是没有解引用过,is_ptr_ptr为false,在上面的分支中就返回 *i32
-
-                let kind: &TypeKind = if dst.is_global() {
-                    &program.borrow_value(dst).ty().kind().clone()
-                } else {
-                    value_data_in_cur_func(program, ctx, dst).ty().kind()
+            ExprKind::Binary(op, left, right) => {
+                let left_value = left.generate(program, ctx)?;
+                let right_value = right.generate(program, ctx)?;
+                let koopa_op = match op {
+                    BinaryOp::Add => KoopaBinaryOp::Add,
+                    BinaryOp::Sub => KoopaBinaryOp::Sub,
+                    BinaryOp::Mul => KoopaBinaryOp::Mul,
+                    BinaryOp::Div => KoopaBinaryOp::Div,
+                    BinaryOp::Mod => KoopaBinaryOp::Mod,
+                    BinaryOp::Lt => KoopaBinaryOp::Lt,
+                    BinaryOp::Gt => KoopaBinaryOp::Gt,
+                    BinaryOp::Le => KoopaBinaryOp::Le,
+                    BinaryOp::Ge => KoopaBinaryOp::Ge,
+                    BinaryOp::Eq => KoopaBinaryOp::Eq,
+                    BinaryOp::Ne => KoopaBinaryOp::NotEq,
                 };
-                match kind {
-                    TypeKind::Pointer(base) =>
-                        match base.kind() {
-                            TypeKind::Int32 => {
-                                let load = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .load(dst);
-                                push_back_value_as_ins(program, ctx, load)?;
-                                Ok(load)
-                            }
-                            TypeKind::Array(_, _) => {
-                                let zero = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .integer(0);
-                                dst = cur_func_mut(program, ctx)
-                                    .dfg_mut()
-                                    .new_value()
-                                    .get_elem_ptr(dst, zero);
-                                push_back_value_as_ins(program, ctx, dst)?;
-                                Ok(dst)
-                            }
-                            _ => unreachable!(),
-                        }
-                    _ => unreachable!(),
+                let res = register_binary(program, ctx, left_value, right_value, koopa_op)?;
+                ctx.value_spans.insert(res, self.span);
+                Ok(res)
+            }
+            ExprKind::Logical(op, left, right) => generate_logical(program, ctx, *op, left, right),
+            ExprKind::Call(func, args) => {
+                let mut call_params = vec![];
+                for arg in args {
+                    call_params.push(arg.generate(program, ctx)?);
                 }
+                let callee = ctx.funcs[func];
+                let call = cur_func_mut(program, ctx).dfg_mut().new_value().call(callee, call_params);
+                push_back_value_as_ins(program, ctx, call)?;
+                Ok(call)
+            }
+            ExprKind::Assign(place, value) => {
+                // 先计算左值地址(包括下标表达式), 再计算右值
+                let dst = place.generate_place(program, ctx)?;
+                let rval = value.generate(program, ctx)?;
+                let store = cur_func_mut(program, ctx).dfg_mut().new_value().store(rval, dst);
+                push_back_value_as_ins(program, ctx, store)?;
+                ctx.value_spans.insert(store, self.span);
+                Ok(rval)
             }
         }
     }
 }
-impl GenerateProgram for UnaryExp {
-    type Out = Value;
 
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        match self {
-            UnaryExp::PrimaryExp(prim_exp) =>
-                match prim_exp {
-                    PrimaryExp::Number(num) => {
-                        // num作为primary_exp,是一个dfg中的value,但不对应指令
-                        let func_data = program.func_mut(ctx.curr_fuc.unwrap());
-                        let val = func_data.dfg_mut().new_value().integer(*num);
-                        Ok(val)
+impl Expr {
+    // 左值的地址: 变量对应的alloc或全局变量, 以及逐个下标计算出的元素地址
+    fn generate_place(&self, program: &mut Program, ctx: &mut Context) -> Result<Value> {
+        match &self.kind {
+            ExprKind::Var(symbol) => ctx.values.get(symbol).copied().ok_or(Error::UnknownSymbol),
+            ExprKind::Index(base, index) => {
+                // 数组取元素用getelemptr; 数组形参是指针, 第一个下标用getptr
+                let ptr = match base.ty {
+                    Ty::Pointer(_) => {
+                        let src = base.generate(program, ctx)?;
+                        let idx = index.generate(program, ctx)?;
+                        cur_func_mut(program, ctx).dfg_mut().new_value().get_ptr(src, idx)
                     }
-                    PrimaryExp::Exp(exp) => exp.generate(program, ctx),
-                    PrimaryExp::LVal(lval) => { lval.generate(program, ctx) }
-                }
-            UnaryExp::UnaryExp(op, rexp) => {
-                let rhs = rexp.generate(program, ctx)?;
-                match op {
-                    // 一元加号直接丢弃
-                    UnaryOp::POSITIVE => Ok(rhs),
                     _ => {
-                        let func_data = program.func_mut(ctx.curr_fuc.unwrap());
-                        let koopa_op: BinaryOp = match op {
-                            UnaryOp::NEGATIVE => BinaryOp::Sub,
-                            UnaryOp::NOT => BinaryOp::Eq,
-                            _ => unreachable!(),
-                        };
-                        let zero = func_data.dfg_mut().new_value().integer(0);
-                        let res = func_data.dfg_mut().new_value().binary(koopa_op, zero, rhs);
-                        push_back_value_as_ins(program, ctx, res)?;
-                        Ok(res)
+                        let src = base.generate_place(program, ctx)?;
+                        let idx = index.generate(program, ctx)?;
+                        cur_func_mut(program, ctx).dfg_mut().new_value().get_elem_ptr(src, idx)
                     }
-                }
+                };
+                push_back_value_as_ins(program, ctx, ptr)?;
+                Ok(ptr)
             }
-            UnaryExp::FuncCall(func_call) => func_call.generate(program, ctx),
+            _ => unreachable!("not a place expression"),
         }
     }
 }
 
-impl GenerateProgram for FuncCall {
-    type Out = Value;
+// 短路求值:
+// 当前块 1.申请一个临时变量 2.计算left 3.branch
+// then_block 此分支说明短路, 那么直接往临时变量上写结果(&&为0, ||为1)
+// else_block 此分支说明未短路，计算right, 结果写到变量上
+// end_block  读取临时变量,作为此表达式的返回结果
+fn generate_logical(
+    program: &mut Program,
+    ctx: &mut Context,
+    op: LogicalOp,
+    left: &Expr,
+    right: &Expr
+) -> Result<Value> {
+    let (short_circuit_op, short_circuit_val) = match op {
+        LogicalOp::And => (KoopaBinaryOp::Eq, 0),
+        LogicalOp::Or => (KoopaBinaryOp::NotEq, 1),
+    };
+    let left_value: Value = left.generate(program, ctx)?;
+    let func_data = program.func_mut(ctx.curr_fuc.unwrap());
+    let zero = func_data.dfg_mut().new_value().integer(0);
+    let res: Value = func_data.dfg_mut().new_value().alloc(Type::get_i32());
+    let short_circuit = register_binary(program, ctx, left_value, zero, short_circuit_op)?;
+    let then_block = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_bb()
+        .basic_block(Some("%then_block".into()));
+    let else_block = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_bb()
+        .basic_block(Some("%else".into()));
+    let end_block = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_bb()
+        .basic_block(Some("%ifend".into()));
+    let branch = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_value()
+        .branch(short_circuit, then_block, else_block);
+    push_back_values_as_ins(program, ctx, vec![res, branch]);
 
-    fn generate(&self, program: &mut Program, ctx: &mut Context) -> Result<Self::Out> {
-        let mut call_params = vec![];
-        for ele in &self.params {
-            let val = ele.generate(program, ctx)?;
-            call_params.push(val);
-        }
-        let func = ctx.scopes.look_up_func(&self.func_name).unwrap().clone();
-        let call = cur_func_mut(program, ctx).dfg_mut().new_value().call(func, call_params);
-        push_back_value_as_ins(program, ctx, call)?;
-        Ok(call)
-    }
+    push_block(program, ctx, then_block)?;
+    let short_circuit_res = cur_func_mut(program, ctx).dfg_mut().new_value().integer(short_circuit_val);
+    let store_res: Value = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_value()
+        .store(short_circuit_res, res);
+    let then_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
+    push_back_values_as_ins(program, ctx, vec![store_res, then_jump]);
+
+    push_block(program, ctx, else_block)?;
+    let right_value = right.generate(program, ctx)?;
+    let right_bool = register_binary(program, ctx, right_value, zero, KoopaBinaryOp::NotEq)?;
+    let store_res: Value = cur_func_mut(program, ctx)
+        .dfg_mut()
+        .new_value()
+        .store(right_bool, res);
+    let else_jump = cur_func_mut(program, ctx).dfg_mut().new_value().jump(end_block);
+    push_back_values_as_ins(program, ctx, vec![store_res, else_jump]);
+
+    push_block(program, ctx, end_block)?;
+    let load = cur_func_mut(program, ctx).dfg_mut().new_value().load(res);
+    push_back_value_as_ins(program, ctx, load)?;
+    Ok(load)
 }
 
 fn register_binary(
@@ -1212,7 +681,7 @@ fn register_binary(
     ctx: &mut Context,
     left: Value,
     right: Value,
-    op: BinaryOp
+    op: KoopaBinaryOp
 ) -> Result<Value> {
     let res = cur_func_mut(program, ctx).dfg_mut().new_value().binary(op, left, right);
     cur_func_mut(program, ctx)
@@ -1230,21 +699,6 @@ fn cur_func_mut<'a, 'b>(program: &'a mut Program, ctx: &'b mut Context) -> &'a m
     program.func_mut(ctx.curr_fuc.unwrap())
 }
 
-fn remove_useless_block<'a, 'b>(program: &'a mut Program, ctx: &'b mut Context) {
-    let func = cur_func_mut(program, ctx);
-    let bbs = func.layout_mut().bbs_mut();
-
-    let empty_bbs: std::collections::HashSet<_> = bbs
-        .iter()
-        .filter(|(_, v)| v.insts().is_empty())
-        .map(|(k, _)| k.clone())
-        .collect();
-
-    for ele in empty_bbs {
-        bbs.remove(&ele);
-    }
-}
-
 /**
  * 创建一个匿名块并设置为当前块,一般用于Ret,break,continue等对应 基本块出口指令翻译完后
  *
@@ -1287,42 +741,3 @@ fn push_back_values_as_ins(program: &mut Program, ctx: &mut Context, vals: Vec<V
 fn cur_block_mut<'a, 'b>(program: &'a mut Program, ctx: &'b mut Context) -> &'a mut BasicBlockNode {
     cur_func_mut(program, ctx).layout_mut().bb_mut(ctx.curr_block.unwrap())
 }
-
-fn value_data_in_cur_func<'a, 'b>(
-    program: &'a mut Program,
-    ctx: &'b mut Context,
-    value: Value
-) -> &'a ValueData {
-    cur_func_mut(program, ctx).dfg().value(value)
-}
-
-// 为koopaIr注册sysy的库函数定义;同时在上下文中注册库函数name->func的映射,使得后文funcall能找到对应func句柄
-fn add_sysy_lib_func(program: &mut Program, ctx: &mut Context) {
-    let dec1 = FunctionData::new_decl("@getint".to_owned(), vec![], Type::get_i32());
-    let dec2 = FunctionData::new_decl("@getch".to_owned(), vec![], Type::get_i32());
-    let dec3 = FunctionData::new_decl(
-        "@getarray".to_owned(),
-        vec![Type::get_pointer(Type::get_i32())],
-        Type::get_i32()
-    );
-    let dec4 = FunctionData::new_decl(
-        "@putint".to_owned(),
-        vec![Type::get_i32()],
-        Type::get_unit()
-    );
-    let dec5 = FunctionData::new_decl("@putch".to_owned(), vec![Type::get_i32()], Type::get_unit());
-    let dec6 = FunctionData::new_decl(
-        "@putarray".to_owned(),
-        vec![Type::get_i32(), Type::get_pointer(Type::get_i32())],
-        Type::get_unit()
-    );
-    let dec7 = FunctionData::new_decl("@starttime".to_owned(), vec![], Type::get_unit());
-
-    let dec8 = FunctionData::new_decl("@stoptime".to_owned(), vec![], Type::get_unit());
-    let dec_list = vec![dec1, dec2, dec3, dec4, dec5, dec6, dec7, dec8];
-    for dec in dec_list {
-        let name = &dec.name()[1..].to_string();
-        let func = program.new_func(dec);
-        ctx.scopes.register_function(name, func);
-    }
-}
@@ -0,0 +1,270 @@
+use std::fmt;
+
+use koopa::ir::Type;
+
+use crate::ast::Span;
+
+// 由AST降级得到的中间表示(HIR), 语义检查与Koopa IR生成都基于它进行:
+// 名字已经解析为符号编号, 每个表达式都带有类型, 运算优先级体现在树的结构中, 所有表达式共用一个Expr
+
+// 符号在Module::symbols中的下标
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Int,
+    Void,
+    Array(Box<Ty>, usize),
+    // 数组形参: 指向第一维元素的指针
+    Pointer(Box<Ty>),
+}
+
+impl Ty {
+    // 各维长度为dims的int数组, dims为空时为int
+    pub fn array(dims: &[usize]) -> Ty {
+        dims.iter()
+            .rev()
+            .fold(Ty::Int, |ty, &len| Ty::Array(Box::new(ty), len))
+    }
+
+    // 数组各维的长度, 非数组为空
+    pub fn dims(&self) -> Vec<usize> {
+        let mut dims = vec![];
+        let mut ty = self;
+        while let Ty::Array(elem, len) = ty {
+            dims.push(*len);
+            ty = elem;
+        }
+        dims
+    }
+
+    pub fn to_koopa(&self) -> Type {
+        match self {
+            Ty::Int => Type::get_i32(),
+            Ty::Void => Type::get_unit(),
+            Ty::Array(elem, len) => Type::get_array(elem.to_koopa(), *len),
+            Ty::Pointer(elem) => Type::get_pointer(elem.to_koopa()),
+        }
+    }
+}
+
+// 按SysY源码中的写法输出, 用于诊断信息: int、int[2][3], 数组形参为int[][3]
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Void => write!(f, "void"),
+            Ty::Pointer(elem) => write!(f, "int[]{}", &elem.to_string()[3..]),
+            ty => {
+                write!(f, "int")?;
+                ty.dims().iter().try_for_each(|len| write!(f, "[{}]", len))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Constant,
+    Function,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Storage {
+    // 函数内的局部变量与参数
+    Local,
+    // 函数内的static变量, 分配在全局数据段
+    Static,
+    // 全局变量与函数
+    Global,
+    // SysY库函数, 没有出现在源码中
+    Builtin,
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // 变量为其类型, 函数为返回值类型
+    pub ty: Ty,
+    // 函数的参数类型
+    pub params: Vec<Ty>,
+    pub storage: Storage,
+    // static全局变量/函数为内部链接
+    pub internal: bool,
+    // 第一次声明的位置, 用于诊断信息中的说明
+    pub span: Option<Span>,
+    // 本单元中定义的位置; 只有extern声明的全局变量与只有声明的函数为None
+    pub def_span: Option<Span>,
+    // 标量常量的值, 使用处直接替换为该值
+    pub value: Option<i32>,
+    pub used: bool,
+}
+
+impl Symbol {
+    pub fn new(name: &str, kind: SymbolKind, ty: Ty, storage: Storage, span: Option<Span>) -> Self {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            ty,
+            params: vec![],
+            storage,
+            internal: false,
+            span,
+            def_span: None,
+            value: None,
+            used: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub items: Vec<Item>,
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Debug)]
+pub enum Item {
+    Func(Func),
+    // 函数声明, 只影响链接属性; 重复的声明同样保留
+    FuncDecl(SymbolId),
+    Decl(Decl),
+}
+
+#[derive(Debug)]
+pub struct Func {
+    pub symbol: SymbolId,
+    pub params: Vec<SymbolId>,
+    pub body: Block,
+}
+
+#[derive(Debug)]
+pub enum Decl {
+    Var(VarDef),
+    // 不带初始值的extern声明, 引用全局变量
+    Extern(SymbolId),
+}
+
+// 变量或常量数组的定义; 标量常量在降级时已经求值, 不产生VarDef
+#[derive(Debug)]
+pub struct VarDef {
+    pub symbol: SymbolId,
+    pub init: Option<Init>,
+    // 变量名所在的位置
+    pub span: Span,
+}
+
+// 展平后的稀疏初始化列表, 下标严格递增, 其余元素为0
+#[derive(Debug)]
+pub enum Init {
+    // 编译期求值的初始值: 全局变量、static变量与常量
+    Const(Vec<(usize, i32)>),
+    // 运行时计算的初始值: 局部变量
+    Exprs(Vec<(usize, Expr)>),
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Decl(Decl),
+    Exp(Option<Expr>),
+    Return(Option<Expr>),
+    If(If),
+    While(While),
+    Break,
+    Continue,
+    Block(Block),
+}
+
+#[derive(Debug)]
+pub struct If {
+    pub cond: Expr,
+    pub then: Box<Stmt>,
+    pub else_stmt: Option<Box<Stmt>>,
+}
+
+#[derive(Debug)]
+pub struct While {
+    pub cond: Expr,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Ty,
+    // 运算为整个表达式的区间, 左值及其读取为变量名的区间, 函数调用为函数名的区间
+    pub span: Span,
+}
+
+// Var与Index表示左值的位置(地址), 只出现在Load/Decay/Assign之下以及Index的数组操作数中
+#[derive(Debug)]
+pub enum ExprKind {
+    Number(i32),
+    Var(SymbolId),
+    // 数组取元素(操作数为数组的位置), 或指针偏移(操作数为指针的值)
+    Index(Box<Expr>, Box<Expr>),
+    // 读取左值的值
+    Load(Box<Expr>),
+    // 数组转为指向首元素的指针
+    Decay(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    // 短路求值的逻辑运算
+    Logical(LogicalOp, Box<Expr>, Box<Expr>),
+    Call(SymbolId, Vec<Expr>),
+    // 值为写入的值
+    Assign(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, ty: Ty, span: Span) -> Self {
+        Expr { kind, ty, span }
+    }
+
+    pub fn number(num: i32, span: Span) -> Self {
+        Expr::new(ExprKind::Number(num), Ty::Int, span)
+    }
+
+    // 左值的位置所属的变量
+    pub fn place_symbol(&self) -> Option<SymbolId> {
+        match &self.kind {
+            ExprKind::Var(id) => Some(*id),
+            ExprKind::Index(base, _) | ExprKind::Load(base) => base.place_symbol(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::ast::{ ConstInitVal, Exp, InitVal, Span };
+use crate::diagnostic::{ Diagnostic, Level };
+
+// 初始化列表的规范化, InitVal与ConstInitVal共用
+// 按SysY的对齐规则把嵌套的初始化列表展平, 结果是稀疏的: 只记录显式给出的元素及其展平后的下标,
+// 其余元素均为0, 由使用者(全局变量的aggregate或局部数组的store)自行补零
+
+pub enum InitShape<'a, T> {
+    Single(&'a Exp),
+    List(&'a [T]),
+}
+
+pub trait Initializer: Sized {
+    fn shape(&self) -> InitShape<'_, Self>;
+    fn span(&self) -> Span;
+}
+
+impl Initializer for InitVal {
+    fn shape(&self) -> InitShape<'_, Self> {
+        match self {
+            InitVal::Single(exp, _) => InitShape::Single(exp),
+            InitVal::List(list, _) => InitShape::List(list),
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            InitVal::Single(_, span) | InitVal::List(_, span) => *span,
+        }
+    }
+}
+
+impl Initializer for ConstInitVal {
+    fn shape(&self) -> InitShape<'_, Self> {
+        match self {
+            ConstInitVal::Single(exp) => InitShape::Single(&exp.exp),
+            ConstInitVal::List(list, _) => InitShape::List(list),
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            ConstInitVal::Single(exp) => exp.span,
+            ConstInitVal::List(_, span) => *span,
+        }
+    }
+}
+
+// 展平后的初始化列表, 下标严格递增
+pub type SparseInit<'a> = Vec<(usize, &'a Exp)>;
+
+// 初始化列表的错误, 指向出错的元素
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+fn error<T: Initializer>(item: &T, message: &str) -> Diagnostic {
+    Diagnostic::new(Level::Error, item.span(), message.to_string())
+}
+
+/// Flattens an initializer for an object of the given dimensions
+/// (empty for scalars) following the SysY brace alignment rules.
+/// Errors point at the offending element.
+pub fn normalize<'a, T: Initializer>(init: &'a T, dims: &[usize]) -> Result<SparseInit<'a>> {
+    match (init.shape(), dims.is_empty()) {
+        (InitShape::Single(exp), true) => Ok(vec![(0, exp)]),
+        (InitShape::List(_), true) => Err(error(init, "braces around scalar initializer")),
+        (InitShape::Single(_), false) =>
+            Err(error(init, "array must be initialized with a brace-enclosed initializer list")),
+        (InitShape::List(list), false) => {
+            let mut out = vec![];
+            fill(list, dims, 0, &mut out)?;
+            Ok(out)
+        }
+    }
+}
+
+// 用list初始化从base开始, 形状为dims的子数组
+fn fill<'a, T: Initializer>(
+    list: &'a [T],
+    dims: &[usize],
+    base: usize,
+    out: &mut SparseInit<'a>
+) -> Result<()> {
+    let size: usize = dims.iter().product();
+    let mut pos = 0;
+    for item in list {
+        if pos >= size {
+            return Err(error(item, "excess elements in array initializer"));
+        }
+        match item.shape() {
+            InitShape::Single(exp) => {
+                out.push((base + pos, exp));
+                pos += 1;
+            }
+            InitShape::List(sub_list) => {
+                // 当前元素已经是标量, 不能再用花括号初始化
+                if dims.len() == 1 {
+                    return Err(error(item, "braces around scalar initializer"));
+                }
+                // 嵌套的列表初始化能与当前位置对齐的最大子数组(不含当前数组本身)
+                // int[2][3][4] {1, 2, 3, 4, {5}}: pos = 4, 对应 int[4]
+                // int[2][3][4] {{5}}: pos = 0, 对应 int[3][4]
+                // 连最后一维都无法对齐时, 花括号的位置是错误的
+                let sub = (1..dims.len())
+                    .find(|&k| pos % dims[k..].iter().product::<usize>() == 0)
+                    .ok_or_else(|| error(item, "braced initializer is not aligned to a sub-array boundary"))?;
+                fill(sub_list, &dims[sub..], base + pos, out)?;
+                pos += dims[sub..].iter().product::<usize>();
+            }
+        }
+    }
+    Ok(())
+}
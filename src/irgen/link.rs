@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{ LinkSymbol, Linkage };
+
+// 多个编译单元之间的符号解析: 按名字匹配外部链接的符号, 检查重复定义与类型冲突
+// 每个编译单元生成各自的Program, 这里只检查, 实际的符号引用由汇编器/链接器完成
+
+#[derive(Debug)]
+pub enum LinkError {
+    MultipleDefinition {
+        name: String,
+        first: String,
+        second: String,
+    },
+    ConflictingTypes {
+        name: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::MultipleDefinition { name, first, second } =>
+                write!(f, "multiple definition of `{}` (in {} and {})", name, first, second),
+            LinkError::ConflictingTypes { name, first, second } =>
+                write!(f, "conflicting types for `{}` (in {} and {})", name, first, second),
+        }
+    }
+}
+
+/// Resolves external symbols of all units by name, returning every
+/// conflict found.
+pub fn check_linkage<'a>(units: impl IntoIterator<Item = (&'a str, &'a Linkage)>) -> Vec<LinkError> {
+    let mut errors = vec![];
+    // 符号名 -> (首次出现的文件, 符号), 以及定义所在的文件
+    let mut seen: HashMap<&str, (&str, &LinkSymbol)> = HashMap::new();
+    let mut defined_in: HashMap<&str, &str> = HashMap::new();
+    for (file, linkage) in units {
+        // 按名字排序, 保证报错顺序稳定
+        let mut symbols: Vec<_> = linkage.symbols.iter().collect();
+        symbols.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, symbol) in symbols {
+            match seen.get(name.as_str()) {
+                Some((first, prev)) if prev.ty != symbol.ty => {
+                    errors.push(LinkError::ConflictingTypes {
+                        name: name.clone(),
+                        first: first.to_string(),
+                        second: file.to_string(),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    seen.insert(name, (file, symbol));
+                }
+            }
+            if symbol.defined {
+                if let Some(first) = defined_in.insert(name, file) {
+                    errors.push(LinkError::MultipleDefinition {
+                        name: name.clone(),
+                        first: first.to_string(),
+                        second: file.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    errors
+}
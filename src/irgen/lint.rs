@@ -0,0 +1,344 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::{ BasicBlock, BinaryOp, FunctionData, TypeKind, Value, ValueKind };
+
+use crate::ast::Span;
+use crate::diagnostic::{ Diagnostic, Lint };
+use super::{ cfg, hir::{ Stmt, Storage, Symbol, SymbolKind } };
+
+// 帮助发现逻辑错误的警告, 不影响生成的代码
+
+// 语句执行后控制流是否可能继续到下一条语句
+pub fn can_complete(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) | Stmt::Break | Stmt::Continue => false,
+        Stmt::Exp(_) | Stmt::Decl(_) => true,
+        Stmt::Block(block) => block.stmts.iter().all(can_complete),
+        Stmt::If(if_stmt) =>
+            match &if_stmt.else_stmt {
+                None => true,
+                Some(else_stmt) => can_complete(&if_stmt.then) || can_complete(else_stmt),
+            }
+        // 条件恒为真且循环体内没有break的while不会结束
+        Stmt::While(while_stmt) =>
+            !matches!(while_stmt.cond.eval(), Ok(cond) if cond != 0) || contains_break(&while_stmt.body),
+    }
+}
+
+// 语句中是否有跳出当前循环的break(不含内层循环中的)
+fn contains_break(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Break => true,
+        Stmt::Block(block) => block.stmts.iter().any(contains_break),
+        Stmt::If(if_stmt) =>
+            contains_break(&if_stmt.then) ||
+                if_stmt.else_stmt.as_ref().is_some_and(|stmt| contains_break(stmt)),
+        _ => false,
+    }
+}
+
+// 只分析地址没有逃逸的标量局部变量: 所有使用者都是load, 或以它为目标地址的store
+fn scalar_locals(func: &FunctionData) -> HashSet<Value> {
+    let dfg = func.dfg();
+    dfg.values()
+        .iter()
+        .filter(|(_, data)| {
+            matches!(data.kind(), ValueKind::Alloc(_)) &&
+                matches!(data.ty().kind(), TypeKind::Pointer(base) if base.is_i32())
+        })
+        .filter(|(&alloc, data)| {
+            data.used_by()
+                .iter()
+                .all(|user| {
+                    match dfg.value(*user).kind() {
+                        ValueKind::Load(_) => true,
+                        ValueKind::Store(store) => store.value() != alloc,
+                        _ => false,
+                    }
+                })
+        })
+        .map(|(&alloc, _)| alloc)
+        .collect()
+}
+
+// 逆序扫描一条指令, 更新活跃的局部变量集合; 返回被覆盖时不活跃的store的目标
+fn live_transfer(
+    func: &FunctionData,
+    locals: &HashSet<Value>,
+    inst: Value,
+    live: &mut HashSet<Value>
+) -> Option<Value> {
+    match func.dfg().value(inst).kind() {
+        ValueKind::Load(load) if locals.contains(&load.src()) => {
+            live.insert(load.src());
+            None
+        }
+        ValueKind::Store(store) if locals.contains(&store.dest()) => {
+            if live.remove(&store.dest()) { None } else { Some(store.dest()) }
+        }
+        _ => None,
+    }
+}
+
+fn block_insts(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect()
+}
+
+fn live_out(
+    func: &FunctionData,
+    live_in: &HashMap<BasicBlock, HashSet<Value>>,
+    bb: BasicBlock
+) -> HashSet<Value> {
+    cfg::successors(func, bb)
+        .iter()
+        .filter_map(|succ| live_in.get(succ))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Reports stores to scalar locals whose value is never read afterwards.
+/// Only stores with a recorded source span (assignments and initializers)
+/// are reported.
+pub fn dead_stores(func: &FunctionData, spans: &HashMap<Value, Span>) -> Vec<Diagnostic> {
+    let locals = scalar_locals(func);
+    if locals.is_empty() {
+        return vec![];
+    }
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    // 活跃变量分析, 迭代到不动点
+    let mut live_in: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in bbs.iter().rev() {
+            let mut live = live_out(func, &live_in, bb);
+            for &inst in block_insts(func, bb).iter().rev() {
+                live_transfer(func, &locals, inst, &mut live);
+            }
+            if live_in.get(&bb) != Some(&live) {
+                live_in.insert(bb, live);
+                changed = true;
+            }
+        }
+    }
+
+    let mut diags = vec![];
+    for &bb in &bbs {
+        let mut live = live_out(func, &live_in, bb);
+        for &inst in block_insts(func, bb).iter().rev() {
+            let dead = live_transfer(func, &locals, inst, &mut live);
+            if let (Some(alloc), Some(span)) = (dead, spans.get(&inst)) {
+                let name = func.dfg().value(alloc).name().clone().unwrap_or_default();
+                let message = format!("value stored to `{}` is never read", name.trim_start_matches('@'));
+                diags.push(Diagnostic::lint(Lint::DeadStore, *span, message));
+            }
+        }
+    }
+    diags.sort_by_key(|diag| diag.span.start);
+    diags
+}
+
+// 顺序扫描一条指令, 更新可能未初始化的局部变量集合; 返回读取了可能未初始化变量的load的来源
+fn uninit_transfer(
+    func: &FunctionData,
+    tracked: &HashSet<Value>,
+    inst: Value,
+    uninit: &mut HashSet<Value>
+) -> Option<Value> {
+    match func.dfg().value(inst).kind() {
+        ValueKind::Alloc(_) if tracked.contains(&inst) => {
+            uninit.insert(inst);
+            None
+        }
+        ValueKind::Store(store) => {
+            uninit.remove(&store.dest());
+            None
+        }
+        // 报告之后视为已初始化, 同一路径上之后的读取不再重复报告
+        ValueKind::Load(load) if uninit.remove(&load.src()) => Some(load.src()),
+        _ => None,
+    }
+}
+
+/// Reports reads of scalar locals declared without an initializer that
+/// may happen before any store on some path from the declaration.
+pub fn uninit_uses(func: &FunctionData, spans: &HashMap<Value, Span>) -> Vec<Diagnostic> {
+    let tracked: HashSet<Value> = scalar_locals(func)
+        .into_iter()
+        .filter(|alloc| spans.contains_key(alloc))
+        .collect();
+    if tracked.is_empty() {
+        return vec![];
+    }
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let mut preds: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+    for &bb in &bbs {
+        for succ in cfg::successors(func, bb) {
+            preds.entry(succ).or_default().push(bb);
+        }
+    }
+    let uninit_in = |out: &HashMap<BasicBlock, HashSet<Value>>, bb: BasicBlock| -> HashSet<Value> {
+        preds
+            .get(&bb)
+            .into_iter()
+            .flatten()
+            .filter_map(|pred| out.get(pred))
+            .flatten()
+            .copied()
+            .collect()
+    };
+    // 可能未初始化是"存在一条路径"的性质, 在汇合处取并集, 迭代到不动点
+    let mut uninit_out: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in &bbs {
+            let mut uninit = uninit_in(&uninit_out, bb);
+            for inst in block_insts(func, bb) {
+                uninit_transfer(func, &tracked, inst, &mut uninit);
+            }
+            if uninit_out.get(&bb) != Some(&uninit) {
+                uninit_out.insert(bb, uninit);
+                changed = true;
+            }
+        }
+    }
+
+    let mut diags = vec![];
+    for &bb in &bbs {
+        let mut uninit = uninit_in(&uninit_out, bb);
+        for inst in block_insts(func, bb) {
+            let alloc = uninit_transfer(func, &tracked, inst, &mut uninit);
+            if let (Some(alloc), Some(span)) = (alloc, spans.get(&inst)) {
+                let name = func.dfg().value(alloc).name().clone().unwrap_or_default();
+                let name = name.trim_start_matches('@');
+                let diag = Diagnostic::lint(
+                    Lint::Uninit,
+                    *span,
+                    format!("`{}` may be used uninitialized", name)
+                ).with_note(spans[&alloc], format!("`{}` declared here", name));
+                diags.push(diag);
+            }
+        }
+    }
+    diags.sort_by_key(|diag| diag.span.start);
+    diags
+}
+
+// 下划线开头的名字表示有意不使用
+fn unused_warning(what: &str, name: &str, span: Span) -> Option<Diagnostic> {
+    if name.starts_with('_') {
+        return None;
+    }
+    Some(Diagnostic::lint(Lint::Unused, span, format!("unused {} `{}`", what, name)))
+}
+
+/// Reports variables, parameters, constants and functions (other than
+/// `main`) that are defined in the unit but never used. Global symbols
+/// with external linkage may be used by other compile units, so they are
+/// only reported when compiling a whole program.
+pub fn unused_symbols(symbols: &[Symbol], whole_program: bool) -> Vec<Diagnostic> {
+    symbols
+        .iter()
+        .filter(|symbol| !symbol.used)
+        .filter(|symbol| symbol.storage != Storage::Global || whole_program || symbol.internal)
+        .filter(|symbol| !(symbol.kind == SymbolKind::Function && symbol.name == "main"))
+        .filter_map(|symbol| {
+            let what = match symbol.kind {
+                SymbolKind::Variable => "variable",
+                SymbolKind::Parameter => "parameter",
+                SymbolKind::Constant => "constant",
+                SymbolKind::Function => "function",
+            };
+            unused_warning(what, &symbol.name, symbol.def_span?)
+        })
+        .collect()
+}
+
+// 按补码回绕计算常量运算的结果, 除零时无法确定
+fn fold_binary(op: BinaryOp, lhs: i32, rhs: i32) -> Option<i32> {
+    if rhs == 0 && matches!(op, BinaryOp::Div | BinaryOp::Mod) {
+        return None;
+    }
+    let res = match op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => ((lhs as u32).wrapping_shr(rhs as u32)) as i32,
+        BinaryOp::Sar => lhs.wrapping_shr(rhs as u32),
+    };
+    Some(res)
+}
+
+// 运算是否是未定义行为; 只知道部分操作数时也可能确定(如除数为0)
+fn undefined_binary(op: BinaryOp, lhs: Option<i32>, rhs: Option<i32>) -> Option<String> {
+    let symbol = match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Shl => "<<",
+        _ => "",
+    };
+    let overflow = |lhs: i32, rhs: i32| format!("integer overflow in `{} {} {}`", lhs, symbol, rhs);
+    match (op, lhs, rhs) {
+        (BinaryOp::Div | BinaryOp::Mod, _, Some(0)) => Some("division by zero".to_string()),
+        (BinaryOp::Div | BinaryOp::Mod, Some(i32::MIN), Some(-1)) => Some(overflow(i32::MIN, -1)),
+        (BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Sar, _, Some(amount)) if !(0..32).contains(&amount) =>
+            Some(format!("shift amount {} is out of range", amount)),
+        (BinaryOp::Add, Some(a), Some(b)) if a.checked_add(b).is_none() => Some(overflow(a, b)),
+        (BinaryOp::Sub, Some(a), Some(b)) if a.checked_sub(b).is_none() => Some(overflow(a, b)),
+        (BinaryOp::Mul, Some(a), Some(b)) if a.checked_mul(b).is_none() => Some(overflow(a, b)),
+        (BinaryOp::Shl, Some(a), Some(b)) if a.wrapping_shl(b as u32) >> b != a => Some(overflow(a, b)),
+        _ => None,
+    }
+}
+
+/// Finds arithmetic instructions whose constant operands make them
+/// undefined: division by zero, `INT_MIN / -1`, out of range shifts and
+/// overflowing constant arithmetic. Only instructions with a recorded
+/// source span are returned, paired with their warning.
+pub fn constant_traps(func: &FunctionData, spans: &HashMap<Value, Span>) -> Vec<(Value, Diagnostic)> {
+    let dfg = func.dfg();
+    // 操作数都是常量的运算也是常量, 在布局顺序中运算总是出现在其使用者之前
+    let mut consts: HashMap<Value, i32> = HashMap::new();
+    let mut traps = vec![];
+    for &bb in func.layout().bbs().keys() {
+        for inst in block_insts(func, bb) {
+            let ValueKind::Binary(binary) = dfg.value(inst).kind() else {
+                continue;
+            };
+            let operand = |value: Value| {
+                match dfg.value(value).kind() {
+                    ValueKind::Integer(int) => Some(int.value()),
+                    _ => consts.get(&value).copied(),
+                }
+            };
+            let (lhs, rhs) = (operand(binary.lhs()), operand(binary.rhs()));
+            let problem = undefined_binary(binary.op(), lhs, rhs);
+            if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+                if let Some(res) = fold_binary(binary.op(), lhs, rhs) {
+                    consts.insert(inst, res);
+                }
+            }
+            if let (Some(message), Some(span)) = (problem, spans.get(&inst)) {
+                traps.push((inst, Diagnostic::lint(Lint::ConstantOverflow, *span, message)));
+            }
+        }
+    }
+    traps
+}
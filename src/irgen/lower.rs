@@ -0,0 +1,980 @@
+use std::collections::{ HashMap, LinkedList };
+
+use crate::ast::{ self, Span, StorageClass };
+use crate::diagnostic::{ Diagnostic, Diagnostics, Level, Lint };
+use super::hir::*;
+use super::{ init::{ self, Initializer }, lint, Error, Options, Result };
+
+// AST到HIR的降级: 解析名字(作用域、extern声明与定义的合并)、确定表达式的类型、在编译期求值常量,
+// 同时进行只依赖名字与类型的语义检查(重定义、遮蔽、未使用的符号、常量下标越界等)
+
+pub trait Lower {
+    type Out;
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out>;
+}
+
+pub struct Resolver<'a> {
+    symbols: Vec<Symbol>,
+    // 全局作用域: 变量/常量与函数分别查找, 同名时报错但两者都保留
+    globals: HashMap<String, SymbolId>,
+    funcs: HashMap<String, SymbolId>,
+    // 函数内的作用域链, 最内层在前
+    scopes: LinkedList<HashMap<String, SymbolId>>,
+    // 当前声明的存储类型, 由VarDecl/ConstDecl设置
+    storage: StorageClass,
+    // 当前所在循环的层数, break/continue只能出现在循环中
+    loop_depth: usize,
+    // 当前所在的函数, 用于检查return语句
+    func: Option<SymbolId>,
+    diagnostics: &'a mut Diagnostics,
+}
+
+/// Lowers a compile unit into HIR. Names are resolved to symbols,
+/// expressions are typed and constants are evaluated; semantic problems
+/// found on the way are reported into `diagnostics`.
+pub fn lower(comp_unit: &ast::CompUnit, options: &Options, diagnostics: &mut Diagnostics) -> Result<Module> {
+    let mut ctx = Resolver {
+        symbols: vec![],
+        globals: HashMap::new(),
+        funcs: HashMap::new(),
+        scopes: LinkedList::new(),
+        storage: StorageClass::Default,
+        loop_depth: 0,
+        func: None,
+        diagnostics,
+    };
+    ctx.add_sysy_lib_funcs();
+    let mut items = vec![];
+    for item in &comp_unit.items {
+        items.extend(item.lower(&mut ctx)?);
+    }
+    let unused = lint::unused_symbols(&ctx.symbols, options.whole_program);
+    ctx.diagnostics.extend(unused);
+    Ok(Module { items, symbols: ctx.symbols })
+}
+
+impl Resolver<'_> {
+    // 注册sysy的库函数, 使调用它们的代码能找到对应的符号
+    fn add_sysy_lib_funcs(&mut self) {
+        let int_ptr = || Ty::Pointer(Box::new(Ty::Int));
+        let lib_funcs = [
+            ("getint", vec![], Ty::Int),
+            ("getch", vec![], Ty::Int),
+            ("getarray", vec![int_ptr()], Ty::Int),
+            ("putint", vec![Ty::Int], Ty::Void),
+            ("putch", vec![Ty::Int], Ty::Void),
+            ("putarray", vec![Ty::Int, int_ptr()], Ty::Void),
+            ("starttime", vec![], Ty::Void),
+            ("stoptime", vec![], Ty::Void),
+            // starttime()/stoptime() 经预处理展开为带行号的版本
+            ("_sysy_starttime", vec![Ty::Int], Ty::Void),
+            ("_sysy_stoptime", vec![Ty::Int], Ty::Void),
+        ];
+        for (name, params, ret) in lib_funcs {
+            let mut symbol = Symbol::new(name, SymbolKind::Function, ret, Storage::Builtin, None);
+            symbol.params = params;
+            let id = self.add_symbol(symbol);
+            self.funcs.insert(name.to_string(), id);
+        }
+    }
+
+    fn add_symbol(&mut self, symbol: Symbol) -> SymbolId {
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    fn in_global_scope(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    // 变量是否分配在全局数据段: 全局变量, 以及函数内的static变量
+    fn in_global_storage(&self) -> bool {
+        self.in_global_scope() || self.storage == StorageClass::Static
+    }
+
+    fn new_scope(&mut self) {
+        self.scopes.push_front(HashMap::new());
+    }
+
+    fn leave_scope(&mut self) {
+        self.scopes.pop_front();
+    }
+
+    // 在表达式中查找变量或常量, 同时记为已使用
+    fn look_up_symbol(&mut self, name: &str) -> Option<SymbolId> {
+        let id = self.look_up_local(name, 0).or_else(|| self.globals.get(name).copied())?;
+        self.symbols[id].used = true;
+        Some(id)
+    }
+
+    // 在函数内各层作用域中查找(由内向外, 跳过最内的skip层), 不标记为已使用
+    fn look_up_local(&self, name: &str, skip: usize) -> Option<SymbolId> {
+        self.scopes
+            .iter()
+            .skip(skip)
+            .find_map(|scope| scope.get(name))
+            .copied()
+    }
+
+    // 函数内的声明: 同一作用域内重复时报告错误并以新的声明为准, 遮蔽外层的声明时给出警告
+    fn declare_local(&mut self, name: &str, kind: SymbolKind, ty: Ty, span: Span) -> SymbolId {
+        match self.scopes.front().and_then(|scope| scope.get(name)) {
+            Some(&prev) => self.report_conflict(format!("redefinition of `{}`", name), span, prev),
+            None => self.check_shadowing(name, span),
+        }
+        let storage = if self.storage == StorageClass::Static { Storage::Static } else { Storage::Local };
+        let mut symbol = Symbol::new(name, kind, ty, storage, Some(span));
+        symbol.def_span = Some(span);
+        let id = self.add_symbol(symbol);
+        self.scopes.front_mut().unwrap().insert(name.to_string(), id);
+        id
+    }
+
+    // 全局变量/常量的定义; 之前的extern声明与定义是同一个符号
+    fn define_global(&mut self, name: &str, kind: SymbolKind, ty: Ty, span: Span) -> SymbolId {
+        if let Some(&func) = self.funcs.get(name) {
+            self.report_kind_conflict(name, span, func);
+        }
+        let is_static = self.storage == StorageClass::Static;
+        if let Some(&id) = self.globals.get(name) {
+            let symbol = &self.symbols[id];
+            // 标量常量没有存储空间, 不能作为extern声明的变量的定义
+            let scalar_const = kind == SymbolKind::Constant && ty == Ty::Int;
+            let message = if symbol.def_span.is_some() || is_static || scalar_const {
+                format!("redefinition of `{}`", name)
+            } else if symbol.ty != ty {
+                format!("conflicting types for `{}`", name)
+            } else {
+                let symbol = &mut self.symbols[id];
+                symbol.kind = kind;
+                symbol.def_span = Some(span);
+                return id;
+            };
+            // 出错的定义使用单独的符号, 之前的符号不受影响
+            self.report_conflict(message, span, id);
+            let id = self.new_global(name, kind, ty, span);
+            self.symbols[id].used = true;
+            return id;
+        }
+        let id = self.new_global(name, kind, ty, span);
+        self.globals.insert(name.to_string(), id);
+        id
+    }
+
+    fn new_global(&mut self, name: &str, kind: SymbolKind, ty: Ty, span: Span) -> SymbolId {
+        let mut symbol = Symbol::new(name, kind, ty, Storage::Global, Some(span));
+        symbol.def_span = Some(span);
+        symbol.internal = self.storage == StorageClass::Static;
+        self.add_symbol(symbol)
+    }
+
+    fn declare_var(&mut self, name: &str, kind: SymbolKind, ty: Ty, span: Span) -> SymbolId {
+        if self.in_global_scope() {
+            self.define_global(name, kind, ty, span)
+        } else {
+            self.declare_local(name, kind, ty, span)
+        }
+    }
+
+    // extern声明: 引用其他编译单元(或本单元之后)定义的全局变量
+    // 函数内的extern声明在当前作用域引入同一个全局符号
+    fn declare_extern(&mut self, name: &str, ty: Ty, span: Span) -> SymbolId {
+        if let Some(&func) = self.funcs.get(name) {
+            self.report_kind_conflict(name, span, func);
+        }
+        let id = match self.globals.get(name) {
+            Some(&id) => {
+                let symbol = &self.symbols[id];
+                if symbol.value.is_some() || symbol.ty != ty {
+                    self.report_conflict(format!("conflicting types for `{}`", name), span, id);
+                    self.add_symbol(Symbol::new(name, SymbolKind::Variable, ty, Storage::Global, Some(span)))
+                } else {
+                    id
+                }
+            }
+            None => {
+                let symbol = Symbol::new(name, SymbolKind::Variable, ty, Storage::Global, Some(span));
+                let id = self.add_symbol(symbol);
+                self.globals.insert(name.to_string(), id);
+                id
+            }
+        };
+        if let Some(scope) = self.scopes.front() {
+            match scope.get(name) {
+                Some(&prev) if prev != id => {
+                    self.report_conflict(format!("redefinition of `{}`", name), span, prev);
+                }
+                _ => {}
+            }
+            self.scopes.front_mut().unwrap().insert(name.to_string(), id);
+            // 函数内的extern声明通过局部符号使用全局变量, 视为使用了全局变量
+            self.symbols[id].used = true;
+        }
+        id
+    }
+
+    // 函数的声明或定义; 只有之前的声明可以被定义覆盖, 重复定义或与库函数同名均为错误
+    fn declare_func(
+        &mut self,
+        name: &str,
+        ret: Ty,
+        params: Vec<Ty>,
+        span: Span,
+        storage: StorageClass,
+        define: bool
+    ) -> SymbolId {
+        if let Some(&id) = self.funcs.get(name) {
+            let symbol = &self.symbols[id];
+            // 重复声明(或在定义之后声明)只需类型一致
+            let message = if define && (symbol.def_span.is_some() || symbol.storage == Storage::Builtin) {
+                format!("redefinition of `{}`", name)
+            } else if symbol.ty != ret || symbol.params != params {
+                format!("conflicting types for `{}`", name)
+            } else {
+                if define {
+                    self.check_func_name(name, span);
+                    let symbol = &mut self.symbols[id];
+                    symbol.def_span = Some(span);
+                    // 之前的static声明同样使函数成为内部链接
+                    symbol.internal |= storage == StorageClass::Static;
+                }
+                return id;
+            };
+            self.report_conflict(message, span, id);
+            let id = self.new_func(name, ret, params, span, storage, define);
+            self.symbols[id].used = true;
+            return id;
+        }
+        self.check_func_name(name, span);
+        let id = self.new_func(name, ret, params, span, storage, define);
+        self.funcs.insert(name.to_string(), id);
+        id
+    }
+
+    fn new_func(
+        &mut self,
+        name: &str,
+        ret: Ty,
+        params: Vec<Ty>,
+        span: Span,
+        storage: StorageClass,
+        define: bool
+    ) -> SymbolId {
+        let mut symbol = Symbol::new(name, SymbolKind::Function, ret, Storage::Global, Some(span));
+        symbol.params = params;
+        symbol.internal = storage == StorageClass::Static;
+        if define {
+            symbol.def_span = Some(span);
+        }
+        self.add_symbol(symbol)
+    }
+
+    // 函数名不能与全局变量同名
+    fn check_func_name(&mut self, name: &str, span: Span) {
+        if let Some(&var) = self.globals.get(name) {
+            self.report_kind_conflict(name, span, var);
+        }
+    }
+
+    // 局部声明遮蔽外层的局部变量、全局变量或函数时给出警告
+    fn check_shadowing(&mut self, name: &str, span: Span) {
+        let (what, prev) = if let Some(id) = self.look_up_local(name, 1) {
+            ("a previous local declaration", id)
+        } else if let Some(&id) = self.globals.get(name) {
+            ("a global declaration", id)
+        } else if let Some(&id) = self.funcs.get(name) {
+            ("a function", id)
+        } else {
+            return;
+        };
+        let mut diag = Diagnostic::lint(
+            Lint::Shadowing,
+            span,
+            format!("declaration of `{}` shadows {}", name, what)
+        );
+        if let Some(prev) = self.symbols[prev].span {
+            diag = diag.with_note(prev, "shadowed declaration is here".to_string());
+        }
+        self.diagnostics.report(diag);
+    }
+
+    // 全局变量与函数共用一个命名空间, 同名是重定义错误
+    fn report_kind_conflict(&mut self, name: &str, span: Span, prev: SymbolId) {
+        self.report_conflict(format!("`{}` redeclared as a different kind of symbol", name), span, prev);
+    }
+
+    // 与之前的声明冲突的错误, 附上之前声明的位置(库函数没有位置)
+    fn report_conflict(&mut self, message: String, span: Span, prev: SymbolId) {
+        let prev = &self.symbols[prev];
+        let mut diag = Diagnostic::new(Level::Error, span, message);
+        if let Some(prev_span) = prev.def_span.or(prev.span) {
+            diag = diag.with_note(prev_span, format!("previous declaration of `{}` is here", prev.name));
+        }
+        self.diagnostics.report(diag);
+    }
+
+    // 不属于任何检查的语义错误; 报告后用合法的HIR代替出错的部分继续降级, 以便发现更多的错误
+    fn report_error(&mut self, span: Span, message: String) {
+        self.diagnostics.report(Diagnostic::new(Level::Error, span, message));
+    }
+
+    // 编译期求值, 无法求值时报告错误并返回None
+    fn eval(&mut self, exp: &Expr, span: Span) -> Option<i32> {
+        let message = match exp.eval() {
+            Ok(val) => {
+                return Some(val);
+            }
+            Err(Error::DivideByZero) => "division by zero in constant expression",
+            Err(Error::ConstantOverflow) => "overflow in constant expression",
+            Err(_) => "expression is not a compile-time constant",
+        };
+        self.report_error(span, message.to_string());
+        None
+    }
+
+    // 左值所指的位置: 从变量本身开始, 逐个下标取元素; 名字没有声明时报告错误并返回None
+    fn lower_place(&mut self, lval: &ast::LVal) -> Result<Option<Expr>> {
+        let Some(id) = self.look_up_symbol(&lval.id) else {
+            self.report_error(lval.span, format!("use of undeclared identifier `{}`", lval.id));
+            return Ok(None);
+        };
+        let mut place = Expr::new(ExprKind::Var(id), self.symbols[id].ty.clone(), lval.span);
+        for (i, index) in lval.indices.iter().enumerate() {
+            let index = Box::new(lower_value(self, index)?);
+            place = match place.ty.clone() {
+                Ty::Array(elem, len) => {
+                    self.check_index(lval, i, &index, len);
+                    Expr::new(ExprKind::Index(Box::new(place), index), *elem, lval.span)
+                }
+                // 数组形参: 先读出指针再偏移; 第一维长度未知, 不检查越界
+                Ty::Pointer(elem) => {
+                    let ptr = Expr::new(ExprKind::Load(Box::new(place)), Ty::Pointer(elem.clone()), lval.span);
+                    Expr::new(ExprKind::Index(Box::new(ptr), index), *elem, lval.span)
+                }
+                Ty::Int | Ty::Void => {
+                    self.report_error(lval.span, format!("subscripted value `{}` is not an array", lval.id));
+                    break;
+                }
+            };
+        }
+        Ok(Some(place))
+    }
+
+    // 第i个下标是常量且超出数组的长度时给出警告
+    fn check_index(&mut self, lval: &ast::LVal, i: usize, index: &Expr, len: usize) {
+        if let Ok(idx) = index.eval() {
+            if idx < 0 || (idx as usize) >= len {
+                let message = format!(
+                    "index {} is out of bounds for dimension {} of `{}` (size {})",
+                    idx,
+                    i + 1,
+                    lval.id,
+                    len
+                );
+                self.diagnostics.report(Diagnostic::lint(Lint::ArrayBounds, lval.span, message));
+            }
+        }
+    }
+}
+
+impl Lower for ast::GlobalItem {
+    type Out = Vec<Item>;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::GlobalItem::FuncDef(func_def) => Ok(vec![Item::Func(func_def.lower(ctx)?)]),
+            ast::GlobalItem::FuncDecl(func_decl) => Ok(vec![Item::FuncDecl(func_decl.lower(ctx)?)]),
+            ast::GlobalItem::Decl(decl) => Ok(decl.lower(ctx)?.into_iter().map(Item::Decl).collect()),
+        }
+    }
+}
+
+fn ret_type(func_type: &ast::FuncType) -> Ty {
+    match func_type {
+        ast::FuncType::Int => Ty::Int,
+        ast::FuncType::Void => Ty::Void,
+    }
+}
+
+// 数组各维的长度; 出错的维度报告错误后按长度1继续
+fn eval_dims(ctx: &mut Resolver, dims: &[ast::ConstExp]) -> Result<Vec<usize>> {
+    dims.iter()
+        .map(|exp| {
+            let len = exp.exp.lower(ctx)?;
+            match ctx.eval(&len, exp.span) {
+                Some(len) if len > 0 => Ok(len as usize),
+                Some(len) => {
+                    ctx.report_error(exp.span, format!("size of array dimension must be positive, found {}", len));
+                    Ok(1)
+                }
+                None => Ok(1),
+            }
+        })
+        .collect()
+}
+
+impl Lower for ast::FuncFParam {
+    type Out = Ty;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let ty = match self.b_type {
+            ast::BType::Int => Ty::Int,
+        };
+        match &self.array_indices {
+            None => Ok(ty),
+            Some(dims) => Ok(Ty::Pointer(Box::new(Ty::array(&eval_dims(ctx, dims)?)))),
+        }
+    }
+}
+
+impl Lower for ast::FuncDecl {
+    type Out = SymbolId;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let params = self.params
+            .iter()
+            .map(|param| param.lower(ctx))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ctx.declare_func(&self.ident, ret_type(&self.func_type), params, self.span, self.storage, false))
+    }
+}
+
+impl Lower for ast::FuncDef {
+    type Out = Func;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let params_ty = self.params
+            .iter()
+            .map(|param| param.lower(ctx))
+            .collect::<Result<Vec<_>>>()?;
+        let ret = ret_type(&self.func_type);
+        let symbol = ctx.declare_func(&self.ident, ret, params_ty.clone(), self.span, self.storage, true);
+        ctx.func = Some(symbol);
+        // 参数位于函数体外层的作用域, 函数体中的声明遮蔽参数时给出警告
+        ctx.new_scope();
+        let params = self.params
+            .iter()
+            .zip(params_ty)
+            .map(|(param, ty)| ctx.declare_local(&param.name, SymbolKind::Parameter, ty, param.span))
+            .collect();
+        let body = self.block.lower(ctx)?;
+        ctx.leave_scope();
+        Ok(Func { symbol, params, body })
+    }
+}
+
+impl Lower for ast::Block {
+    type Out = Block;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        ctx.new_scope();
+        let mut stmts = vec![];
+        // return/break/continue之后的语句不会被执行, 每个块只报告第一条
+        let mut terminated = false;
+        for item in &self.items {
+            if terminated {
+                let span = match item {
+                    ast::BlockItem::Stmt { span, .. } | ast::BlockItem::Decl { span, .. } => *span,
+                };
+                let message = "unreachable code".to_string();
+                ctx.diagnostics.report(Diagnostic::lint(Lint::Unreachable, span, message));
+                terminated = false;
+            }
+            match item {
+                ast::BlockItem::Decl { decl, .. } => {
+                    stmts.extend(decl.lower(ctx)?.into_iter().map(Stmt::Decl));
+                }
+                ast::BlockItem::Stmt { stmt, .. } => {
+                    let stmt = stmt.lower(ctx)?;
+                    terminated |= !lint::can_complete(&stmt);
+                    stmts.push(stmt);
+                }
+            }
+        }
+        ctx.leave_scope();
+        Ok(Block { stmts })
+    }
+}
+
+impl Lower for ast::Decl {
+    type Out = Vec<Decl>;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::Decl::ConstDecl(const_decl) => const_decl.lower(ctx),
+            ast::Decl::VarDecl(var_decl) => var_decl.lower(ctx),
+        }
+    }
+}
+
+impl Lower for ast::VarDecl {
+    type Out = Vec<Decl>;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        ctx.storage = self.storage;
+        let res = self.def_list
+            .iter()
+            .map(|def| def.lower(ctx))
+            .collect();
+        ctx.storage = StorageClass::Default;
+        res
+    }
+}
+
+// 初始化列表按数组的形状展平, 各元素降级为表达式
+// 列表的形状有误时报告错误, 数组按没有显式给出的元素、标量按初始值0继续
+fn lower_init<T: Initializer>(ctx: &mut Resolver, init: &T, dims: &[usize]) -> Result<Vec<(usize, Expr)>> {
+    let elems = match init::normalize(init, dims) {
+        Ok(elems) => elems,
+        Err(diag) => {
+            ctx.diagnostics.report(diag);
+            let zero = dims.is_empty().then(|| (0, Expr::number(0, init.span())));
+            return Ok(zero.into_iter().collect());
+        }
+    };
+    elems
+        .into_iter()
+        .map(|(idx, exp)| Ok((idx, lower_value(ctx, exp)?)))
+        .collect()
+}
+
+// 无法在编译期求值的元素报告错误后按0继续
+fn eval_init(ctx: &mut Resolver, elems: &[(usize, Expr)]) -> Vec<(usize, i32)> {
+    elems
+        .iter()
+        .map(|(idx, exp)| (*idx, ctx.eval(exp, exp.span).unwrap_or(0)))
+        .collect()
+}
+
+impl Lower for ast::VarDef {
+    type Out = Decl;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let (id, dims, init_val, span) = match self {
+            ast::VarDef::IdOnly(id, dims, span) => (id, dims, None, *span),
+            ast::VarDef::Assign(id, dims, init_val, span) => (id, dims, Some(init_val), *span),
+        };
+        let dims = eval_dims(ctx, dims)?;
+        // 不带初始值的extern只是声明; 带初始值的extern等同于定义
+        if ctx.storage == StorageClass::Extern && init_val.is_none() {
+            return Ok(Decl::Extern(ctx.declare_extern(id, Ty::array(&dims), span)));
+        }
+        // 初始值在变量声明之前解析: int x = x; 中右边的x是外层的变量
+        let init = match init_val {
+            None => None,
+            Some(init_val) => {
+                let elems = lower_init(ctx, init_val, &dims)?;
+                // 全局存储的变量的初始值在编译期求值
+                if ctx.in_global_storage() {
+                    Some(Init::Const(eval_init(ctx, &elems)))
+                } else {
+                    Some(Init::Exprs(elems))
+                }
+            }
+        };
+        let symbol = ctx.declare_var(id, SymbolKind::Variable, Ty::array(&dims), span);
+        Ok(Decl::Var(VarDef { symbol, init, span }))
+    }
+}
+
+impl Lower for ast::ConstDecl {
+    type Out = Vec<Decl>;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        // const 必须带初始值, 不能只是extern声明; 报告后按普通的常量定义处理
+        if self.storage == StorageClass::Extern {
+            ctx.report_error(self.def_list[0].span, "const declaration cannot be `extern`".to_string());
+        } else {
+            ctx.storage = self.storage;
+        }
+        let res: Result<Vec<Option<Decl>>> = self.def_list
+            .iter()
+            .map(|def| def.lower(ctx))
+            .collect();
+        ctx.storage = StorageClass::Default;
+        Ok(res?.into_iter().flatten().collect())
+    }
+}
+
+impl Lower for ast::ConstDef {
+    type Out = Option<Decl>;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let dims = eval_dims(ctx, &self.dims)?;
+        let elems = lower_init(ctx, &self.init_val, &dims)?;
+        let vals = eval_init(ctx, &elems);
+        let symbol = ctx.declare_var(&self.id, SymbolKind::Constant, Ty::array(&dims), self.span);
+        // 标量常量不需要存储空间, 使用处直接替换为其值
+        if dims.is_empty() {
+            ctx.symbols[symbol].value = Some(vals.first().map_or(0, |(_, val)| *val));
+            return Ok(None);
+        }
+        let init = Some(Init::Const(vals));
+        Ok(Some(Decl::Var(VarDef { symbol, init, span: self.span })))
+    }
+}
+
+impl Lower for ast::Stmt {
+    type Out = Stmt;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let lower_opt = |exp: &Option<ast::Exp>, ctx: &mut Resolver| {
+            exp.as_ref()
+                .map(|exp| exp.lower(ctx))
+                .transpose()
+        };
+        match self {
+            ast::Stmt::Ret(exp, span) => {
+                let exp = lower_opt(exp, ctx)?.map(|exp| value(ctx, exp));
+                let func = &ctx.symbols[ctx.func.unwrap()];
+                match (exp, &func.ty) {
+                    (Some(_), Ty::Void) => {
+                        let message = format!("void function `{}` should not return a value", func.name);
+                        ctx.report_error(*span, message);
+                        Ok(Stmt::Return(None))
+                    }
+                    (None, Ty::Int) => {
+                        let message = format!("non-void function `{}` should return a value", func.name);
+                        ctx.report_error(*span, message);
+                        Ok(Stmt::Return(Some(Expr::number(0, *span))))
+                    }
+                    (exp, _) => Ok(Stmt::Return(exp.map(|exp| int_value(ctx, exp)))),
+                }
+            }
+            ast::Stmt::Exp(exp) => Ok(Stmt::Exp(lower_opt(exp, ctx)?)),
+            ast::Stmt::Block(block) => Ok(Stmt::Block(block.lower(ctx)?)),
+            ast::Stmt::IfStmt(if_stmt) => {
+                let cond = lower_value(ctx, &if_stmt.cond)?;
+                let then = Box::new(if_stmt.then.lower(ctx)?);
+                let else_stmt = match &if_stmt.else_stmt {
+                    Some(stmt) => Some(Box::new(stmt.lower(ctx)?)),
+                    None => None,
+                };
+                Ok(Stmt::If(If { cond, then, else_stmt }))
+            }
+            ast::Stmt::While(while_stmt) => {
+                let cond = lower_value(ctx, &while_stmt.cond)?;
+                ctx.loop_depth += 1;
+                let body = while_stmt.body.lower(ctx);
+                ctx.loop_depth -= 1;
+                Ok(Stmt::While(While { cond, body: Box::new(body?) }))
+            }
+            // 循环外的break/continue报告后当作空语句
+            ast::Stmt::Break(stmt) if ctx.loop_depth == 0 => {
+                ctx.report_error(stmt.span, "`break` statement not in a loop".to_string());
+                Ok(Stmt::Exp(None))
+            }
+            ast::Stmt::Continue(stmt) if ctx.loop_depth == 0 => {
+                ctx.report_error(stmt.span, "`continue` statement not in a loop".to_string());
+                Ok(Stmt::Exp(None))
+            }
+            ast::Stmt::Break(_) => Ok(Stmt::Break),
+            ast::Stmt::Continue(_) => Ok(Stmt::Continue),
+        }
+    }
+}
+
+// 作为值使用的表达式不能是void函数的调用, 出错时用0代替
+fn value(ctx: &mut Resolver, exp: Expr) -> Expr {
+    match exp.kind {
+        ExprKind::Call(func, _) if exp.ty == Ty::Void => {
+            let message = format!("result of void function `{}` is used as a value", ctx.symbols[func].name);
+            ctx.report_error(exp.span, message);
+            Expr::number(0, exp.span)
+        }
+        _ => exp,
+    }
+}
+
+// 运算的操作数、条件、下标、初始值等只能是int, 数组(转为指针后)只能作为函数的实参; 出错时用0代替
+fn int_value(ctx: &mut Resolver, exp: Expr) -> Expr {
+    let exp = value(ctx, exp);
+    if exp.ty != Ty::Int {
+        ctx.report_error(exp.span, format!("expected a value of type int, found {}", exp.ty));
+        return Expr::number(0, exp.span);
+    }
+    exp
+}
+
+// 降级作为int值使用的表达式
+fn lower_value(ctx: &mut Resolver, exp: &ast::Exp) -> Result<Expr> {
+    let exp = exp.lower(ctx)?;
+    Ok(int_value(ctx, exp))
+}
+
+fn binary(ctx: &mut Resolver, op: BinaryOp, left: Expr, right: Expr, span: Span) -> Expr {
+    let kind = ExprKind::Binary(op, Box::new(int_value(ctx, left)), Box::new(int_value(ctx, right)));
+    Expr::new(kind, Ty::Int, span)
+}
+
+fn logical(ctx: &mut Resolver, op: LogicalOp, left: Expr, right: Expr, span: Span) -> Expr {
+    let kind = ExprKind::Logical(op, Box::new(int_value(ctx, left)), Box::new(int_value(ctx, right)));
+    Expr::new(kind, Ty::Int, span)
+}
+
+impl Lower for ast::Exp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::Exp::LOrExp(exp) => exp.lower(ctx),
+            ast::Exp::Assign(lval, exp) => {
+                // 出错时赋值表达式只保留右边的值
+                let place = ctx.lower_place(lval)?;
+                let value = lower_value(ctx, exp)?;
+                let Some(place) = place else {
+                    return Ok(value);
+                };
+                let target = &ctx.symbols[place.place_symbol().unwrap()];
+                if target.kind == SymbolKind::Constant {
+                    ctx.report_error(lval.span, format!("cannot assign to constant `{}`", lval.id));
+                    return Ok(value);
+                }
+                if place.ty != Ty::Int {
+                    ctx.report_error(lval.span, format!("cannot assign to an array of type {}", place.ty));
+                    return Ok(value);
+                }
+                let kind = ExprKind::Assign(Box::new(place), Box::new(value));
+                Ok(Expr::new(kind, Ty::Int, lval.span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::LOrExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::LOrExp::LAndExp(exp) => exp.lower(ctx),
+            ast::LOrExp::LOrExp(left, _, right, span) =>
+            {
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(logical(ctx, LogicalOp::Or, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::LAndExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::LAndExp::EqExp(exp) => exp.lower(ctx),
+            ast::LAndExp::LAndExp(left, _, right, span) =>
+            {
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(logical(ctx, LogicalOp::And, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::EqExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::EqExp::RelExp(exp) => exp.lower(ctx),
+            ast::EqExp::EqExp(left, op, right, span) => {
+                let op = match op {
+                    ast::EqOp::Eq => BinaryOp::Eq,
+                    ast::EqOp::Ne => BinaryOp::Ne,
+                };
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(binary(ctx, op, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::RelExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::RelExp::AddExp(exp) => exp.lower(ctx),
+            ast::RelExp::RelExp(left, op, right, span) => {
+                let op = match op {
+                    ast::RelOp::Lt => BinaryOp::Lt,
+                    ast::RelOp::Gt => BinaryOp::Gt,
+                    ast::RelOp::Le => BinaryOp::Le,
+                    ast::RelOp::Ge => BinaryOp::Ge,
+                };
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(binary(ctx, op, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::AddExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::AddExp::MulExp(exp) => exp.lower(ctx),
+            ast::AddExp::AddExp(left, op, right, span) => {
+                let op = match op {
+                    ast::AddOp::Add => BinaryOp::Add,
+                    ast::AddOp::Minus => BinaryOp::Sub,
+                };
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(binary(ctx, op, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::MulExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::MulExp::UnaryExp(exp) => exp.lower(ctx),
+            ast::MulExp::MulExp(left, op, right, span) => {
+                let op = match op {
+                    ast::MulOp::Multi => BinaryOp::Mul,
+                    ast::MulOp::Divide => BinaryOp::Div,
+                    ast::MulOp::Mod => BinaryOp::Mod,
+                };
+                let (left, right) = (left.lower(ctx)?, right.lower(ctx)?);
+                Ok(binary(ctx, op, left, right, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::UnaryExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::UnaryExp::PrimaryExp(exp) => exp.lower(ctx),
+            ast::UnaryExp::FuncCall(call) => call.lower(ctx),
+            ast::UnaryExp::UnaryExp(op, operand, span) => {
+                let operand = operand.lower(ctx)?;
+                let operand = int_value(ctx, operand);
+                let op = match op {
+                    // 一元加号直接丢弃
+                    ast::UnaryOp::POSITIVE => {
+                        return Ok(operand);
+                    }
+                    ast::UnaryOp::NEGATIVE => UnaryOp::Neg,
+                    ast::UnaryOp::NOT => UnaryOp::Not,
+                };
+                Ok(Expr::new(ExprKind::Unary(op, Box::new(operand)), Ty::Int, *span))
+            }
+        }
+    }
+}
+
+impl Lower for ast::PrimaryExp {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        match self {
+            ast::PrimaryExp::Number(num, span) => Ok(Expr::number(*num, *span)),
+            ast::PrimaryExp::Exp(exp) => exp.lower(ctx),
+            ast::PrimaryExp::LVal(lval) => lval.lower(ctx),
+        }
+    }
+}
+
+impl Lower for ast::LVal {
+    type Out = Expr;
+
+    // 作为表达式(而不是赋值目标)的左值
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let Some(place) = ctx.lower_place(self)? else {
+            return Ok(Expr::number(0, self.span));
+        };
+        if let ExprKind::Var(id) = place.kind {
+            if let Some(value) = ctx.symbols[id].value {
+                return Ok(Expr::number(value, self.span));
+            }
+        }
+        // int arr[2][3] => arr[1] 是 *i32, 数组在表达式中转为指向首元素的指针
+        // int arr[][3] => arr 是形参中存放的指针 *[i32, 3], 直接读出
+        let (kind, ty) = match place.ty.clone() {
+            Ty::Array(elem, _) => (ExprKind::Decay(Box::new(place)), Ty::Pointer(elem)),
+            ty => (ExprKind::Load(Box::new(place)), ty),
+        };
+        Ok(Expr::new(kind, ty, self.span))
+    }
+}
+
+impl Lower for ast::FuncCall {
+    type Out = Expr;
+
+    fn lower(&self, ctx: &mut Resolver) -> Result<Self::Out> {
+        let args = self.params
+            .iter()
+            .map(|exp| exp.lower(ctx).map(|exp| value(ctx, exp)))
+            .collect::<Result<Vec<_>>>()?;
+        let name = &self.func_name;
+        // 被同名的局部变量遮蔽的函数不能再调用; 调用全局变量同样是错误
+        let variable = ctx.look_up_local(name, 0).or_else(|| {
+            ctx.globals
+                .get(name)
+                .filter(|_| !ctx.funcs.contains_key(name))
+                .copied()
+        });
+        if let Some(var) = variable {
+            let mut diag = Diagnostic::new(
+                Level::Error,
+                self.span,
+                format!("called object `{}` is not a function", name)
+            );
+            if let Some(span) = ctx.symbols[var].span {
+                diag = diag.with_note(span, format!("`{}` declared here", name));
+            }
+            ctx.diagnostics.report(diag);
+            // 错误已经报告, 用0代替调用结果继续检查
+            if !ctx.funcs.contains_key(name) {
+                return Ok(Expr::number(0, self.span));
+            }
+        }
+        let Some(&func) = ctx.funcs.get(name) else {
+            ctx.report_error(self.span, format!("call to undeclared function `{}`", name));
+            return Ok(Expr::number(0, self.span));
+        };
+        ctx.symbols[func].used = true;
+        let symbol = &ctx.symbols[func];
+        let message = if args.len() != symbol.params.len() {
+            let which = if args.len() < symbol.params.len() { "few" } else { "many" };
+            Some(format!(
+                "too {} arguments to function `{}` (expected {}, found {})",
+                which,
+                name,
+                symbol.params.len(),
+                args.len()
+            ))
+        } else {
+            symbol.params
+                .iter()
+                .zip(&args)
+                .position(|(param, arg)| *param != arg.ty)
+                .map(|i| {
+                    format!(
+                        "argument {} of `{}` has type {}, expected {}",
+                        i + 1,
+                        name,
+                        args[i].ty,
+                        symbol.params[i]
+                    )
+                })
+        };
+        if let Some(message) = message {
+            let mut diag = Diagnostic::new(Level::Error, self.span, message);
+            if let Some(span) = symbol.span {
+                diag = diag.with_note(span, format!("`{}` declared here", name));
+            }
+            ctx.diagnostics.report(diag);
+            return Ok(Expr::number(0, self.span));
+        }
+        let ty = symbol.ty.clone();
+        Ok(Expr::new(ExprKind::Call(func, args), ty, self.span))
+    }
+}
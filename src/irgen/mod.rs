@@ -1,117 +1,154 @@
-use std::collections::{ HashMap, LinkedList };
+use std::collections::{ HashMap, HashSet, LinkedList };
 
-use crate::ast::CompUnit;
+use crate::ast::{ CompUnit, Span };
+use crate::diagnostic::Diagnostics;
 use generate::GenerateProgram;
-use koopa::ir::{ BasicBlock, Function, Program, Value };
+use hir::{ Symbol, SymbolId };
+use koopa::ir::{ BasicBlock, Function, FunctionData, Program, Type, Value };
 
+pub mod cfg;
 mod eval;
 mod generate;
+mod hir;
+mod init;
+mod lint;
+pub mod link;
+mod lower;
+mod rewrite;
 #[derive(Debug)]
 pub enum Error {
     SysError,
     UnknownSymbol,
-    DuplicateDecl,
     VariableEvalAtCompileTime,
-    RedefineConstValue,
     InvalidBreak,
     InvalidContinue,
     PushBlockFailed,
     PushInstructionFailed,
+    // 编译期求值时除数(或模数)为0
+    DivideByZero,
+    // 编译期求值时结果超出int的范围
+    ConstantOverflow,
 }
 
-pub struct Context {
+/// Options controlling how strictly the IR generator treats
+/// questionable code.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // 本单元就是完整的程序: 外部链接的全局变量/函数没有被使用时同样给出警告
+    pub whole_program: bool,
+    // 在常量除零、常量运算溢出等未定义行为之前调用TRAP_FUNC, 使程序确定地终止
+    pub hardened: bool,
+}
+
+/// Function called before operations with undefined behaviour in
+/// hardened mode. It is declared in the IR and defined by asmgen.
+pub const TRAP_FUNC: &str = "__sysy_trap";
+
+pub struct Context<'a> {
+    // HIR中的符号表
+    pub symbols: &'a [Symbol],
     pub curr_fuc: Option<Function>,
     pub curr_block: Option<BasicBlock>,
-    pub scopes: Scopes,
+    // 符号 -> Koopa中对应的变量(alloc或全局变量)与函数
+    pub values: HashMap<SymbolId, Value>,
+    pub funcs: HashMap<SymbolId, Function>,
     pub break_continue_dst: LinkedList<(BasicBlock, BasicBlock)>,
-    // Vardecl生成时,通过上下文判断是否在全局scope
+    // static局部变量改名计数,保证不同函数/作用域下同名的static变量生成的全局符号不冲突
+    pub static_counter: usize,
+    pub linkage: Linkage,
+    pub options: Options,
+    pub diagnostics: Diagnostics,
+    // 当前函数中部分指令对应的源码位置, 供生成完函数后的检查使用
+    pub value_spans: HashMap<Value, Span>,
+    // 加固模式下声明的陷阱函数
+    pub trap_func: Option<Function>,
 }
 
-pub struct Scopes {
-    // 函数内的作用域,是一个scope_chain
-    pub values: LinkedList<HashMap<String, ASTValue>>,
-    // 全局作用域
-    pub global_values: HashMap<String, ASTValue>,
-    // 函数名->program内注册的函数句柄
-    pub func: HashMap<String, Function>,
+// Koopa IR 本身不记录符号的可见性,由irgen收集后交给asmgen
+#[derive(Debug, Default)]
+pub struct Linkage {
+    // 内部链接的符号名(不带@前缀),asmgen不为其生成.globl
+    pub internal: HashSet<String>,
+    // extern声明且本单元内没有定义的全局变量,由其他编译单元定义,asmgen不为其分配空间
+    pub external: HashSet<String>,
+    // 本单元中外部链接的符号,用于多个编译单元之间按名字解析
+    pub symbols: HashMap<String, LinkSymbol>,
 }
 
-impl Scopes {
-    pub fn new() -> Self {
-        Scopes {
-            values: LinkedList::new(),
-            global_values: HashMap::new(),
-            func: HashMap::new(),
-        }
-    }
-    pub fn register_function(&mut self, name: &String, func: Function) {
-        self.func.insert(name.clone(), func);
-    }
-
-    pub fn look_up_func(&mut self, name: &String) -> Option<&Function> {
-        self.func.get(name)
-    }
-
-    pub fn insert_global_symbol(&mut self, name: &str, value: ASTValue) {
-        self.global_values.insert(name.into(), value);
-    }
-
-    pub fn look_up_global_symbol(&self, name: &str) -> Option<&ASTValue> {
-        self.global_values.get(name)
-    }
-
-    pub fn insert_symbol(&mut self, name: &String, value: ASTValue) {
-        self.values.front_mut().unwrap().insert(name.clone(), value);
-    }
-
-    fn look_up_symbol(&self, name: &str) -> Option<&ASTValue> {
-        self.values
-            .iter()
-            .find_map(|symbol_table| symbol_table.get(name))
-            .or_else(|| self.global_values.get(name))
-    }
+#[derive(Debug)]
+pub struct LinkSymbol {
+    // 函数为函数类型,全局变量为其存储的类型(而不是指向它的指针)
+    pub ty: Type,
+    pub defined: bool,
+}
 
-    fn look_up_in_curr_scope(&self, name: &str) -> Option<&ASTValue> {
-        self.values.front().and_then(|symbol_table| symbol_table.get(name))
+impl Linkage {
+    pub fn declare(&mut self, name: &str, ty: Type) {
+        self.symbols
+            .entry(name.to_string())
+            .or_insert(LinkSymbol { ty, defined: false });
     }
 
-    fn new_scope(&mut self) {
-        self.values.push_front(HashMap::new());
-    }
-    fn leave_scope(&mut self) {
-        self.values.pop_front();
+    pub fn define(&mut self, name: &str, ty: Type) {
+        self.symbols.insert(name.to_string(), LinkSymbol { ty, defined: true });
     }
 }
 
-// for each symbol,store parsedVal for const, store value for variable
-#[derive(Debug, Clone, Copy)]
-pub enum ASTValue {
-    Const(i32),
-    Variable(Value),
-}
-
 pub type Result<T> = std::result::Result<T, Error>;
 
-impl Context {
-    pub fn new() -> Self {
+impl<'a> Context<'a> {
+    pub fn new(symbols: &'a [Symbol], options: Options, diagnostics: Diagnostics) -> Self {
         Context {
+            symbols,
             curr_fuc: None,
             curr_block: None,
-            scopes: Scopes::new(),
+            values: HashMap::new(),
+            funcs: HashMap::new(),
             // while程序跳转目标地址;由于while可嵌套,所以应该是个栈
             break_continue_dst: LinkedList::new(),
+            static_counter: 0,
+            linkage: Linkage::default(),
+            options,
+            diagnostics,
+            value_spans: HashMap::new(),
+            trap_func: None,
         }
     }
 
+    // 返回的引用不借用ctx, 持有符号的同时可以继续修改ctx
+    pub fn symbol(&self, id: SymbolId) -> &'a Symbol {
+        &self.symbols[id]
+    }
+
+    // 陷阱函数在第一次使用时声明, 为内部链接, 由asmgen生成其定义
+    pub fn trap_func(&mut self, program: &mut Program) -> Function {
+        *self.trap_func.get_or_insert_with(|| {
+            self.linkage.internal.insert(TRAP_FUNC.to_string());
+            program.new_func(FunctionData::new_decl(format!("@{}", TRAP_FUNC), vec![], Type::get_i32()))
+        })
+    }
+
     pub fn in_global_scope(&self) -> bool {
         matches!(self.curr_fuc, None)
     }
 
-    pub fn insert_symbol(&mut self, name: &String, value: ASTValue) {
-        if self.in_global_scope() {
-            self.scopes.insert_global_symbol(name, value);
-        } else {
-            self.scopes.insert_symbol(name, value);
+    // 为全局存储的变量生成Koopa中的符号名
+    // 函数内的static变量改名为 @__static_函数名_变量名_序号, 并记为内部链接
+    pub fn global_symbol_name(&mut self, program: &Program, symbol: &Symbol) -> String {
+        match self.curr_fuc {
+            None => {
+                if symbol.internal {
+                    self.linkage.internal.insert(symbol.name.clone());
+                }
+                format!("@{}", symbol.name)
+            }
+            Some(func) => {
+                let func_name = &program.func(func).name()[1..];
+                let name = format!("__static_{}_{}_{}", func_name, symbol.name, self.static_counter);
+                self.static_counter += 1;
+                self.linkage.internal.insert(name.clone());
+                format!("@{}", name)
+            }
         }
     }
 
@@ -129,31 +166,25 @@ impl Context {
     pub fn peek_cont_dst(&self) -> Option<BasicBlock> {
         self.break_continue_dst.front().map(|pair| { pair.1 })
     }
-
-    fn look_up_symbol(&self, name: &str) -> Option<&ASTValue> {
-        self.scopes.look_up_symbol(name)
-    }
-
-    fn look_up_in_curr_scope(&self, name: &str) -> Option<&ASTValue> {
-        if self.in_global_scope() {
-            self.scopes.look_up_global_symbol(name)
-        } else {
-            self.scopes.look_up_in_curr_scope(name)
-        }
-    }
-
-    fn new_scope(&mut self) {
-        self.scopes.new_scope();
-    }
-    fn leave_scope(&mut self) {
-        self.scopes.leave_scope();
-    }
 }
 
-/// Generates Koopa IR program for the given compile unit (ASTs).
-pub fn generate_program(comp_unit: &CompUnit) -> Result<Program> {
-    let mut program = Program::new();
-    let mut ctx = Context::new();
-    comp_unit.generate(&mut program, &mut ctx)?;
-    Ok(program)
+/// Generates Koopa IR program for the given compile unit (ASTs),
+/// together with the linkage of its global symbols. The AST is first
+/// lowered into HIR, which both the semantic checks and the generator
+/// consume. Warnings and non-fatal errors are reported into
+/// `diagnostics`.
+pub fn generate_program(
+    comp_unit: &CompUnit,
+    options: &Options,
+    diagnostics: &mut Diagnostics
+) -> Result<(Program, Linkage)> {
+    let res = lower::lower(comp_unit, options, diagnostics).and_then(|module| {
+        let mut program = Program::new();
+        let mut ctx = Context::new(&module.symbols, options.clone(), std::mem::take(diagnostics));
+        let res = module.generate(&mut program, &mut ctx);
+        *diagnostics = std::mem::take(&mut ctx.diagnostics);
+        res.map(|_| (program, ctx.linkage))
+    });
+    diagnostics.sort();
+    res
 }
@@ -0,0 +1,12 @@
+use koopa::ir::{ Function, FunctionData, Value };
+use koopa::ir::builder::LocalInstBuilder;
+
+// 对已生成的Koopa IR做的改写: Koopa中value创建后不能直接修改, replace_value_with
+// 又会清空被替换的值的used_by集合, 因此这里只插入新的指令, 不替换已有的
+
+// 在函数内的指令inst之前插入对无参数函数callee的调用
+pub fn insert_call_before(func: &mut FunctionData, inst: Value, callee: Function) {
+    let call = func.dfg_mut().new_value().call(callee, vec![]);
+    let bb = func.layout().parent_bb(inst).unwrap();
+    func.layout_mut().bb_mut(bb).insts_mut().cursor_mut(inst).insert_key_before(call).unwrap();
+}
@@ -1,51 +1,306 @@
-use asmgen::generate_asm;
-use irgen::generate_program;
+use asmgen::{ callgraph, generate_asm };
+use diagnostic::{ render, Diagnostics, LintConfig, Summary };
+use irgen::{ generate_program, link::check_linkage, Linkage };
 use koopa::back::KoopaGenerator;
-use koopa::ir::Type;
-use lalrpop_util::lalrpop_mod;
+use koopa::ir::{ Program, Type };
+use lalrpop_util::{ lalrpop_mod, ParseError };
+use opt::PassManager;
+use preprocess::{ preprocess, Source };
 use std::env::args;
-use std::fs::read_to_string;
 use std::io::Result;
+use std::path::Path;
 mod asmgen;
 mod ast;
+mod diagnostic;
 mod irgen;
+mod opt;
+mod preprocess;
 
 // 引用 lalrpop 生成的解析器
 // 因为我们刚刚创建了 sysy.lalrpop, 所以模块名是 sysy
 lalrpop_mod!(sysy);
 
+// 命令行: sysy-compiler [-koopa|-riscv] [-c] [-W...] [--missing-return=warn|error] [--hardened]
+//         [--stack-report=<file>] [--call-graph=<file.dot>]
+//         [-O0|-O1|-O2] [--passes=a,b,c] [--time-passes] [--stats] [--inline-threshold=<n>]
+//         [--unroll-factor=<n>] [--print-after=<pass>] <input>... [-o <output>]
+// 不带参数时沿用原来的行为: 编译hello.c, 同时输出hello.koopa和hello.asm
+struct Options {
+    mode: String,
+    // 分别编译: 每个输入文件生成各自的输出文件
+    separate: bool,
+    inputs: Vec<String>,
+    output: Option<String>,
+    ir: irgen::Options,
+    // 命令行上的-W选项, 每个文件可以再用#pragma lint修改
+    lints: LintConfig,
+    // 栈使用报告与Graphviz格式调用图的输出路径
+    stack_report: Option<String>,
+    call_graph: Option<String>,
+    opt: opt::Options,
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        mode: "-riscv".to_owned(),
+        separate: false,
+        inputs: vec![],
+        output: None,
+        ir: irgen::Options::default(),
+        lints: LintConfig::default(),
+        stack_report: None,
+        call_graph: None,
+        opt: opt::Options::default(),
+    };
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-koopa" | "-riscv" => {
+                options.mode = arg;
+            }
+            "-c" => {
+                options.separate = true;
+            }
+            "-o" => {
+                options.output = args.next();
+            }
+            // 等同于-Werror=implicit-return/-Wimplicit-return
+            "--missing-return=error" | "--missing-return=warn" => {
+                let flag = if arg.ends_with("error") { "-Werror=implicit-return" } else { "-Wimplicit-return" };
+                options.lints.apply_flag(flag).unwrap();
+            }
+            "--hardened" => {
+                options.ir.hardened = true;
+            }
+            _ if arg.starts_with("--stack-report=") => {
+                options.stack_report = Some(arg["--stack-report=".len()..].to_owned());
+            }
+            _ if arg.starts_with("--call-graph=") => {
+                options.call_graph = Some(arg["--call-graph=".len()..].to_owned());
+            }
+            "-O0" | "-O1" | "-O2" => {
+                options.opt.level = arg[2..].parse().unwrap();
+            }
+            "--time-passes" => {
+                options.opt.time_passes = true;
+            }
+            "--stats" => {
+                options.opt.stats = true;
+            }
+            _ if arg.starts_with("--passes=") => {
+                let passes = &arg["--passes=".len()..];
+                options.opt.passes = Some(
+                    passes.split(',').filter(|pass| !pass.is_empty()).map(|pass| pass.to_owned()).collect()
+                );
+            }
+            _ if arg.starts_with("--inline-threshold=") => {
+                match arg["--inline-threshold=".len()..].parse() {
+                    Ok(threshold) => options.opt.inline_threshold = Some(threshold),
+                    Err(_) => fatal(&format!("invalid inline threshold in `{}`", arg)),
+                }
+            }
+            _ if arg.starts_with("--unroll-factor=") => {
+                match arg["--unroll-factor=".len()..].parse() {
+                    Ok(factor) => options.opt.unroll_factor = Some(factor),
+                    Err(_) => fatal(&format!("invalid unroll factor in `{}`", arg)),
+                }
+            }
+            _ if arg.starts_with("--print-after=") => {
+                options.opt.print_after.push(arg["--print-after=".len()..].to_owned());
+            }
+            _ if arg.starts_with("-W") => {
+                if let Err(msg) = options.lints.apply_flag(&arg) {
+                    fatal(&msg);
+                }
+            }
+            _ => options.inputs.push(arg),
+        }
+    }
+    if options.inputs.is_empty() {
+        options.mode = "-123".to_owned();
+        options.inputs.push("hello.c".to_owned());
+    }
+    // 单个文件且不是分别编译时, 外部链接的符号也不会被其他文件使用
+    options.ir.whole_program = !options.separate && options.inputs.len() == 1;
+    options
+}
+
 fn main() -> Result<()> {
     Type::set_ptr_size(4);
-    let input = "hello.c";
-    let mode = "-123".to_owned();
-    let output = "hello.koopa";
+    let options = parse_args();
+    let multiple = options.inputs.len() > 1;
+    if multiple && options.output.is_some() && (options.separate || options.mode == "-koopa") {
+        fatal("cannot specify -o with multiple output files");
+    }
+    if multiple && !options.separate && options.mode == "-koopa" {
+        fatal("Koopa IR of multiple inputs cannot be combined, use -c");
+    }
+    let mut pass_manager = PassManager::new(&options.opt).unwrap_or_else(|msg| fatal(&msg));
 
-    // let mut args = args();
-    // args.next();
-    // let mode = args.next().unwrap();
-    // let input = args.next().unwrap();
-    // args.next();
-    // let output: String = args.next().unwrap();
+    // 每个文件独立生成一个Program, 之后按名字检查跨文件的符号
+    // 出错的文件不影响其他文件的检查, 所有文件处理完之后再退出
+    let mut summary = Summary::default();
+    let mut units = vec![];
+    for input in &options.inputs {
+        if let Some(unit) = compile_unit(input, &options, &mut summary) {
+            units.push(unit);
+        }
+    }
+    let failed = units.len() < options.inputs.len();
+    let link_errors = if failed {
+        vec![]
+    } else {
+        check_linkage(
+            options.inputs
+                .iter()
+                .map(|input| input.as_str())
+                .zip(units.iter().map(|(_, linkage)| linkage))
+        )
+    };
+    for err in &link_errors {
+        eprintln!("error: {}", err);
+        summary.add_error();
+    }
+    // 机器可读的统计结果, 在所有诊断信息之后输出
+    if !summary.is_empty() {
+        eprintln!("{}", summary.to_json());
+    }
+    if failed || !link_errors.is_empty() {
+        std::process::exit(1);
+    }
 
-    let output_2 = "hello.asm";
-    let input = read_to_string(input)?;
+    // 各编译单元分别优化, 调用图与输出都基于优化后的IR
+    for (program, _) in &mut units {
+        pass_manager.run(program);
+    }
+    if let Some(report) = pass_manager.timing_report() {
+        eprint!("{}", report);
+    }
+    if let Some(report) = pass_manager.stats_report() {
+        eprint!("{}", report);
+    }
+
+    // 调用图按所有编译单元链接在一起的程序计算
+    if options.stack_report.is_some() || options.call_graph.is_some() {
+        let graph = callgraph::build(&units);
+        if let Some(path) = &options.stack_report {
+            std::fs::write(path, graph.report())?;
+        }
+        if let Some(path) = &options.call_graph {
+            std::fs::write(path, graph.to_dot())?;
+        }
+    }
+
+    match options.mode.as_str() {
+        "-koopa" => {
+            for (input, (prog, _)) in options.inputs.iter().zip(&units) {
+                let output = options.output.clone().unwrap_or_else(|| with_extension(input, "koopa"));
+                KoopaGenerator::from_path(output).unwrap().generate_on(prog).unwrap();
+            }
+        }
+        "-riscv" if options.separate => {
+            for (input, unit) in options.inputs.iter().zip(units) {
+                let output = options.output.clone().unwrap_or_else(|| with_extension(input, "S"));
+                generate_asm(&[unit], &output).expect("failed to generate asm");
+            }
+        }
+        "-riscv" => {
+            let output = options.output.clone().unwrap_or_else(|| {
+                if multiple { "a.S".to_owned() } else { with_extension(&options.inputs[0], "S") }
+            });
+            generate_asm(&units, &output).expect("failed to generate asm");
+        }
+        _ => {
+            KoopaGenerator::from_path("hello.koopa").unwrap().generate_on(&units[0].0).unwrap();
+            generate_asm(&units, "hello.asm").expect("failed to generate asm");
+        }
+    }
+
+    Ok(())
+}
+
+// 预处理, 解析并生成单个编译单元的Koopa IR; 出错时返回None
+fn compile_unit(input: &str, options: &Options, summary: &mut Summary) -> Option<(Program, Linkage)> {
+    // 预处理, 展开#include/#define等指令
+    let source = match preprocess(input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("{}", err);
+            summary.add_error();
+            return None;
+        }
+    };
+    let lints = match file_lints(&options.lints, &source.pragmas) {
+        Ok(lints) => lints,
+        Err(msg) => {
+            eprintln!("{}: error: {}", input, msg);
+            summary.add_error();
+            return None;
+        }
+    };
 
     // parse input file
-    let comp_unit: ast::CompUnit = sysy::CompUnitParser::new().parse(&input).unwrap();
+    let comp_unit: ast::CompUnit = match sysy::CompUnitParser::new().parse(&source.text) {
+        Ok(comp_unit) => comp_unit,
+        Err(err) => {
+            report_parse_error(&source, &err);
+            summary.add_error();
+            return None;
+        }
+    };
 
-    let prog = generate_program(&comp_unit).unwrap();
+    let mut diagnostics = Diagnostics::new(lints);
+    let res = generate_program(&comp_unit, &options.ir, &mut diagnostics);
+    for diag in diagnostics.iter() {
+        eprint!("{}", render(&source, diag));
+        summary.add(diag);
+    }
+    match res {
+        Ok(_) if diagnostics.has_errors() => None,
+        Ok(unit) => Some(unit),
+        Err(err) => {
+            eprintln!("error: {}: {:?}", input, err);
+            summary.add_error();
+            None
+        }
+    }
+}
 
-    if mode.as_str() == "-koopa" {
-        KoopaGenerator::from_path(output).unwrap().generate_on(&prog).unwrap();
-    } else if mode.as_str() == "-riscv" {
-        generate_asm(&prog, &output).expect("failed to generate asm");
-    } else {
-        KoopaGenerator::from_path(output).unwrap().generate_on(&prog).unwrap();
-        generate_asm(&prog, &output_2).expect("failed to generate asm");
+// 文件中的 #pragma lint -Wxxx ... 在命令行选项的基础上修改本文件的检查级别
+fn file_lints(lints: &LintConfig, pragmas: &[String]) -> std::result::Result<LintConfig, String> {
+    let mut lints = lints.clone();
+    for pragma in pragmas {
+        let mut words = pragma.split_whitespace();
+        if words.next() != Some("lint") {
+            continue;
+        }
+        for flag in words {
+            lints.apply_flag(flag).map_err(|msg| format!("{} in #pragma lint", msg))?;
+        }
     }
+    Ok(lints)
+}
 
-    // prog.
-    println!("{:#?}", comp_unit);
+fn with_extension(input: &str, ext: &str) -> String {
+    Path::new(input).with_extension(ext).to_string_lossy().into_owned()
+}
 
-    Ok(())
+fn fatal(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    std::process::exit(1);
+}
+
+// 语法错误的位置是预处理后文本中的偏移, 通过line_map映射回原始文件
+fn report_parse_error<T: std::fmt::Display>(source: &Source, err: &ParseError<usize, T, &str>) {
+    let (offset, msg) = match err {
+        ParseError::InvalidToken { location } => (*location, "invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, expected } =>
+            (*location, format!("unexpected end of file, expected one of {}", expected.join(" "))),
+        ParseError::UnrecognizedToken { token: (start, token, _), expected } =>
+            (*start, format!("unexpected token `{}`, expected one of {}", token, expected.join(" "))),
+        ParseError::ExtraToken { token: (start, token, _) } =>
+            (*start, format!("extra token `{}`", token)),
+        ParseError::User { error } => (0, error.to_string()),
+    };
+    eprintln!("{}: error: {}", source.line_map.locate(&source.text, offset), msg);
 }
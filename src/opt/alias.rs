@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use koopa::ir::{ Function, FunctionData, Program, Value, ValueKind };
+
+// 简单的别名分析: 按地址来源的对象区分全局变量、局部变量与来源未知的指针(数组参数)
+
+// 地址所指向的对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    Global(Value),
+    Local(Value),
+    // 数组参数等, 可能指向任何全局变量或地址被传出的局部变量
+    Unknown(Value),
+}
+
+// 从对象出发的一层地址计算: (是否为getelemptr, 常量下标)
+type Step = (bool, Option<i32>);
+
+/// Finds the object an address points into, together with the path of
+/// `getelemptr`/`getptr` steps from the object to the address.
+pub fn base_of(func: &FunctionData, mut addr: Value) -> (Base, Vec<Step>) {
+    let mut path = vec![];
+    let base = loop {
+        if addr.is_global() {
+            break Base::Global(addr);
+        }
+        match func.dfg().value(addr).kind() {
+            ValueKind::GetElemPtr(ptr) => {
+                path.push((true, const_index(func, ptr.index())));
+                addr = ptr.src();
+            }
+            ValueKind::GetPtr(ptr) => {
+                path.push((false, const_index(func, ptr.index())));
+                addr = ptr.src();
+            }
+            ValueKind::Alloc(_) => break Base::Local(addr),
+            _ => break Base::Unknown(addr),
+        }
+    };
+    path.reverse();
+    (base, path)
+}
+
+fn const_index(func: &FunctionData, index: Value) -> Option<i32> {
+    match func.dfg().value(index).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => None,
+    }
+}
+
+pub struct AliasInfo {
+    // 地址被传给函数或写入内存的局部变量
+    escaped: HashSet<Value>,
+}
+
+impl AliasInfo {
+    pub fn new(func: &FunctionData) -> Self {
+        let mut escaped = HashSet::new();
+        for (_, node) in func.layout().bbs() {
+            for &inst in node.insts().keys() {
+                let pointers: Vec<Value> = match func.dfg().value(inst).kind() {
+                    ValueKind::Call(call) => call.args().to_vec(),
+                    ValueKind::Store(store) => vec![store.value()],
+                    _ => continue,
+                };
+                for pointer in pointers {
+                    if let (Base::Local(local), _) = base_of(func, pointer) {
+                        escaped.insert(local);
+                    }
+                }
+            }
+        }
+        AliasInfo { escaped }
+    }
+
+    pub fn may_alias(&self, func: &FunctionData, a: Value, b: Value) -> bool {
+        let (base_a, path_a) = base_of(func, a);
+        let (base_b, path_b) = base_of(func, b);
+        match (base_a, base_b) {
+            (Base::Local(local), Base::Unknown(_)) | (Base::Unknown(_), Base::Local(local)) => self.escaped.contains(&local),
+            (Base::Unknown(_), _) | (_, Base::Unknown(_)) => true,
+            _ if base_a != base_b => false,
+            // 同一个对象: 访问路径相同而某一层的常量下标不同时, 访问的是不同的元素
+            _ => {
+                path_a.len() != path_b.len()
+                    || path_a.iter().zip(&path_b).all(|(a, b)| {
+                        a.0 != b.0 || !matches!((a.1, b.1), (Some(x), Some(y)) if x != y)
+                    })
+            }
+        }
+    }
+
+    // 被调用的函数能否访问该地址: 只有地址没有传出的局部变量是本函数私有的
+    pub fn visible_to_callee(&self, func: &FunctionData, addr: Value) -> bool {
+        !matches!(base_of(func, addr).0, Base::Local(local) if !self.escaped.contains(&local))
+    }
+}
+
+// 不写内存的运行时库函数
+const PURE_LIB_FUNCS: &[&str] = &[
+    "@getint",
+    "@getch",
+    "@putint",
+    "@putch",
+    "@putarray",
+    "@starttime",
+    "@stoptime",
+    "@_sysy_starttime",
+    "@_sysy_stoptime",
+];
+
+/// Returns the functions that may write memory visible to their callers:
+/// functions storing to globals or through pointer parameters, calling such
+/// functions, or declared without a body (other than the pure runtime ones).
+pub fn clobbering_funcs(program: &Program) -> HashSet<Function> {
+    let mut clobbering = HashSet::new();
+    for &func in program.func_layout() {
+        let data = program.func(func);
+        if data.layout().entry_bb().is_none() && !PURE_LIB_FUNCS.contains(&data.name()) {
+            clobbering.insert(func);
+        }
+    }
+    // 沿调用关系传播, 直到不再变化
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &func in program.func_layout() {
+            if clobbering.contains(&func) {
+                continue;
+            }
+            let data = program.func(func);
+            let writes = data.layout().bbs().nodes().flat_map(|node| node.insts().keys()).any(|&inst| {
+                match data.dfg().value(inst).kind() {
+                    ValueKind::Store(store) => !matches!(base_of(data, store.dest()).0, Base::Local(_)),
+                    ValueKind::Call(call) => clobbering.contains(&call.callee()),
+                    _ => false,
+                }
+            });
+            if writes {
+                clobbering.insert(func);
+                changed = true;
+            }
+        }
+    }
+    clobbering
+}
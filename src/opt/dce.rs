@@ -0,0 +1,169 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::{ BasicBlock, FunctionData, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::{ rewrite, FunctionPass };
+
+// 激进的死代码删除: 先删除不可达的块, 再从有副作用的指令(store、call、ret与跳转)出发
+// 标记它们(间接)用到的值, 其余的指令都被删除。
+// 基本块参数只有被活跃的值使用时才活跃, 此时各前驱传给它的参数也活跃;
+// 不活跃的参数连同前驱中对应的实参一起删除
+#[derive(Default)]
+pub struct Dce {
+    removed_insts: usize,
+    removed_blocks: usize,
+    removed_params: usize,
+}
+
+impl FunctionPass for Dce {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let blocks = cfg::remove_unreachable_blocks(func);
+        let live = mark_live(func);
+
+        let mut dead_params: Vec<(BasicBlock, Vec<usize>)> = vec![];
+        let mut dead_insts: Vec<Value> = vec![];
+        for (&bb, node) in func.layout().bbs() {
+            let params = func.dfg().bb(bb).params();
+            let dead: Vec<usize> = (0..params.len()).filter(|&i| !live.contains(&params[i])).collect();
+            if !dead.is_empty() {
+                dead_params.push((bb, dead));
+            }
+            dead_insts.extend(node.insts().keys().filter(|inst| !live.contains(inst)));
+        }
+
+        // 先从前驱的跳转中删去实参, 死指令与参数才不再被使用
+        let mut params = vec![];
+        for (bb, dead) in &dead_params {
+            let mut index = 0;
+            let removed: Vec<Value> = func.dfg().bb(*bb).params().to_vec();
+            func.dfg_mut().bb_mut(*bb).params_mut().retain(|_| {
+                index += 1;
+                !dead.contains(&(index - 1))
+            });
+            params.extend(dead.iter().map(|&i| removed[i]));
+            let preds: Vec<Value> = func.dfg().bb(*bb).used_by().iter().copied().collect();
+            for pred in preds {
+                remove_jump_args(func, pred, *bb, dead);
+            }
+        }
+        for &inst in &dead_insts {
+            let bb = func.layout().parent_bb(inst).unwrap();
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+        // 死指令之间可能互相使用, 先删除已经没有使用者的
+        let mut pending = dead_insts.clone();
+        while !pending.is_empty() {
+            pending.retain(|&inst| {
+                if func.dfg().value(inst).used_by().is_empty() {
+                    func.dfg_mut().remove_value(inst);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        for &param in &params {
+            func.dfg_mut().remove_value(param);
+        }
+
+        self.removed_blocks += blocks;
+        self.removed_insts += dead_insts.len();
+        self.removed_params += params.len();
+        blocks > 0 || !dead_insts.is_empty() || !params.is_empty()
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("removed instructions", self.removed_insts),
+            ("removed unreachable blocks", self.removed_blocks),
+            ("removed block parameters", self.removed_params),
+        ]
+    }
+}
+
+fn has_side_effect(kind: &ValueKind) -> bool {
+    matches!(kind, ValueKind::Store(_) | ValueKind::Call(_)) || cfg::is_terminator(kind)
+}
+
+fn mark_live(func: &FunctionData) -> HashSet<Value> {
+    // 基本块参数 -> (所在的块, 在参数中的位置)
+    let mut owners: HashMap<Value, (BasicBlock, usize)> = HashMap::new();
+    let mut live = HashSet::new();
+    let mut work = vec![];
+    for (&bb, node) in func.layout().bbs() {
+        for (index, &param) in func.dfg().bb(bb).params().iter().enumerate() {
+            owners.insert(param, (bb, index));
+        }
+        for &inst in node.insts().keys() {
+            if has_side_effect(func.dfg().value(inst).kind()) {
+                live.insert(inst);
+                work.push(inst);
+            }
+        }
+    }
+    while let Some(value) = work.pop() {
+        let data = func.dfg().value(value);
+        let operands: Vec<Value> = match data.kind() {
+            // 跳转的实参随目标块的参数一起标记
+            ValueKind::Jump(_) => vec![],
+            ValueKind::Branch(branch) => vec![branch.cond()],
+            // 参数活跃时, 每个前驱传入的对应实参活跃
+            ValueKind::BlockArgRef(_) => {
+                let (bb, index) = owners[&value];
+                incoming_args(func, bb, index)
+            }
+            kind => kind.value_uses().collect(),
+        };
+        for operand in operands {
+            if !operand.is_global() && live.insert(operand) {
+                work.push(operand);
+            }
+        }
+    }
+    live
+}
+
+// 各前驱传给基本块bb的第index个参数的实参
+fn incoming_args(func: &FunctionData, bb: BasicBlock, index: usize) -> Vec<Value> {
+    let mut args = vec![];
+    for &pred in func.dfg().bb(bb).used_by() {
+        match func.dfg().value(pred).kind() {
+            ValueKind::Jump(jump) => args.push(jump.args()[index]),
+            ValueKind::Branch(branch) => {
+                if branch.true_bb() == bb {
+                    args.push(branch.true_args()[index]);
+                }
+                if branch.false_bb() == bb {
+                    args.push(branch.false_args()[index]);
+                }
+            }
+            _ => {}
+        }
+    }
+    args
+}
+
+fn remove_jump_args(func: &mut FunctionData, inst: Value, target: BasicBlock, dead: &[usize]) {
+    let mut kind = func.dfg().value(inst).kind().clone();
+    let remove = |args: &mut Vec<Value>| {
+        let mut index = 0;
+        args.retain(|_| {
+            index += 1;
+            !dead.contains(&(index - 1))
+        });
+    };
+    match &mut kind {
+        ValueKind::Jump(jump) => remove(jump.args_mut()),
+        ValueKind::Branch(branch) => {
+            if branch.true_bb() == target {
+                remove(branch.true_args_mut());
+            }
+            if branch.false_bb() == target {
+                remove(branch.false_args_mut());
+            }
+        }
+        _ => unreachable!("not a terminator"),
+    }
+    rewrite::rebuild(func, inst, kind);
+}
@@ -0,0 +1,159 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::{ BasicBlock, FunctionData };
+
+use crate::irgen::cfg;
+
+// 支配树, 只包含从入口可达的基本块
+// 算法见 Cooper, Harvey, Kennedy: A Simple, Fast Dominance Algorithm
+pub struct DomTree {
+    // 可达块的逆后序, 入口在最前
+    order: Vec<BasicBlock>,
+    idom: HashMap<BasicBlock, BasicBlock>,
+    children: HashMap<BasicBlock, Vec<BasicBlock>>,
+    preds: HashMap<BasicBlock, Vec<BasicBlock>>,
+}
+
+impl DomTree {
+    pub fn new(func: &FunctionData) -> Self {
+        let entry = func.layout().entry_bb().unwrap();
+        let order = reverse_postorder(func, entry);
+        let index: HashMap<BasicBlock, usize> = order.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+        let mut preds: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+        for &bb in &order {
+            for succ in cfg::successors(func, bb) {
+                let list = preds.entry(succ).or_default();
+                if !list.contains(&bb) {
+                    list.push(bb);
+                }
+            }
+        }
+
+        let mut idom: HashMap<BasicBlock, BasicBlock> = HashMap::new();
+        idom.insert(entry, entry);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in order.iter().skip(1) {
+                let mut new_idom: Option<BasicBlock> = None;
+                for &pred in preds.get(&bb).into_iter().flatten() {
+                    if !idom.contains_key(&pred) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(cur) => intersect(&idom, &index, pred, cur),
+                    });
+                }
+                let new_idom = new_idom.unwrap();
+                if idom.get(&bb) != Some(&new_idom) {
+                    idom.insert(bb, new_idom);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+        for &bb in order.iter().skip(1) {
+            children.entry(idom[&bb]).or_default().push(bb);
+        }
+        idom.remove(&entry);
+        DomTree { order, idom, children, preds }
+    }
+
+    pub fn reverse_postorder(&self) -> &[BasicBlock] {
+        &self.order
+    }
+
+    pub fn is_reachable(&self, bb: BasicBlock) -> bool {
+        bb == self.order[0] || self.idom.contains_key(&bb)
+    }
+
+    // 直接支配者, 入口块为None
+    pub fn idom(&self, bb: BasicBlock) -> Option<BasicBlock> {
+        self.idom.get(&bb).copied()
+    }
+
+    pub fn children(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.children.get(&bb).map_or(&[], |children| children.as_slice())
+    }
+
+    // 可达前驱, 每个前驱只出现一次
+    pub fn preds(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.preds.get(&bb).map_or(&[], |preds| preds.as_slice())
+    }
+
+    // a是否支配b(包括a == b)
+    pub fn dominates(&self, a: BasicBlock, b: BasicBlock) -> bool {
+        let mut cur = b;
+        loop {
+            if cur == a {
+                return true;
+            }
+            match self.idom(cur) {
+                Some(idom) => cur = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// Computes the dominance frontier of every reachable block.
+    pub fn frontiers(&self) -> HashMap<BasicBlock, HashSet<BasicBlock>> {
+        let mut frontiers: HashMap<BasicBlock, HashSet<BasicBlock>> = HashMap::new();
+        for &bb in &self.order {
+            let preds = self.preds(bb);
+            if preds.len() < 2 {
+                continue;
+            }
+            let idom = self.idom(bb);
+            for &pred in preds {
+                let mut runner = Some(pred);
+                while runner.is_some() && runner != idom {
+                    let cur = runner.unwrap();
+                    frontiers.entry(cur).or_default().insert(bb);
+                    runner = self.idom(cur);
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+fn intersect(
+    idom: &HashMap<BasicBlock, BasicBlock>,
+    index: &HashMap<BasicBlock, usize>,
+    mut a: BasicBlock,
+    mut b: BasicBlock
+) -> BasicBlock {
+    while a != b {
+        while index[&a] > index[&b] {
+            a = idom[&a];
+        }
+        while index[&b] > index[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+fn reverse_postorder(func: &FunctionData, entry: BasicBlock) -> Vec<BasicBlock> {
+    let mut visited = HashSet::new();
+    let mut postorder = vec![];
+    // 显式栈: (块, 下一个要访问的后继下标)
+    let mut stack = vec![(entry, 0)];
+    visited.insert(entry);
+    while let Some((bb, next)) = stack.pop() {
+        let succs = cfg::successors(func, bb);
+        if next < succs.len() {
+            stack.push((bb, next + 1));
+            let succ = succs[next];
+            if visited.insert(succ) {
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(bb);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use koopa::ir::{ BasicBlock, BinaryOp, FunctionData, Value, ValueKind };
+
+use super::{ alias::AliasInfo, dom::DomTree, rewrite, FunctionPass };
+
+// 基于支配树的全局值编号
+// 沿支配树前序遍历, 表中保存支配当前块的纯运算(binary、getelemptr、getptr), 相同的运算直接使用已有的结果;
+// load按地址记录读到(或store写入)的值, 中间没有可能写同一地址的store或call时, 之后的load直接使用该值。
+// 内存的状态只传给唯一前驱是支配者的块, 其他块从空的状态开始
+#[derive(Default)]
+pub struct Gvn {
+    removed_exprs: usize,
+    removed_loads: usize,
+}
+
+// 运算的操作数, 整数常量按值比较(每次使用的常量都是不同的value)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operand {
+    Const(i32),
+    Value(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Expr {
+    Binary(BinaryOp, Operand, Operand),
+    GetElemPtr(Operand, Operand),
+    GetPtr(Operand, Operand),
+}
+
+impl FunctionPass for Gvn {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let dom = DomTree::new(func);
+        let mut numbering = Numbering {
+            exprs: HashMap::new(),
+            alias: AliasInfo::new(func),
+            removed_exprs: 0,
+            removed_loads: 0,
+        };
+        let entry = func.layout().entry_bb().unwrap();
+        numbering.visit(func, &dom, entry, HashMap::new());
+        self.removed_exprs += numbering.removed_exprs;
+        self.removed_loads += numbering.removed_loads;
+        numbering.removed_exprs + numbering.removed_loads > 0
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("removed expressions", self.removed_exprs),
+            ("removed loads", self.removed_loads),
+        ]
+    }
+}
+
+struct Numbering {
+    exprs: HashMap<Expr, Value>,
+    alias: AliasInfo,
+    removed_exprs: usize,
+    removed_loads: usize,
+}
+
+impl Numbering {
+    // memory: 地址 -> 该地址当前的值
+    fn visit(&mut self, func: &mut FunctionData, dom: &DomTree, bb: BasicBlock, mut memory: HashMap<Value, Value>) {
+        let saved = self.exprs.clone();
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            match func.dfg().value(inst).kind().clone() {
+                ValueKind::Binary(_) | ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => {
+                    let expr = expr_of(func, inst);
+                    if let Some(&value) = self.exprs.get(&expr) {
+                        rewrite::replace_all_uses(func, inst, value);
+                        rewrite::remove_inst(func, inst);
+                        self.removed_exprs += 1;
+                    } else {
+                        self.exprs.insert(expr, inst);
+                        if let Some(swapped) = swap_commutative(expr) {
+                            self.exprs.entry(swapped).or_insert(inst);
+                        }
+                    }
+                }
+                ValueKind::Load(load) => {
+                    if let Some(&value) = memory.get(&load.src()) {
+                        rewrite::replace_all_uses(func, inst, value);
+                        rewrite::remove_inst(func, inst);
+                        self.removed_loads += 1;
+                    } else {
+                        memory.insert(load.src(), inst);
+                    }
+                }
+                ValueKind::Store(store) => {
+                    memory.retain(|&addr, _| !self.alias.may_alias(func, addr, store.dest()));
+                    memory.insert(store.dest(), store.value());
+                }
+                // 被调用的函数可以修改全局变量和传给它的数组
+                ValueKind::Call(_) => {
+                    memory.retain(|&addr, _| !self.alias.visible_to_callee(func, addr));
+                }
+                _ => {}
+            }
+        }
+        for &child in dom.children(bb) {
+            let state = if dom.preds(child) == [bb] { memory.clone() } else { HashMap::new() };
+            self.visit(func, dom, child, state);
+        }
+        self.exprs = saved;
+    }
+}
+
+fn operand(func: &FunctionData, value: Value) -> Operand {
+    if !value.is_global() {
+        if let ValueKind::Integer(int) = func.dfg().value(value).kind() {
+            return Operand::Const(int.value());
+        }
+    }
+    Operand::Value(value)
+}
+
+fn expr_of(func: &FunctionData, inst: Value) -> Expr {
+    match func.dfg().value(inst).kind() {
+        ValueKind::Binary(binary) =>
+            Expr::Binary(binary.op(), operand(func, binary.lhs()), operand(func, binary.rhs())),
+        ValueKind::GetElemPtr(ptr) => Expr::GetElemPtr(operand(func, ptr.src()), operand(func, ptr.index())),
+        ValueKind::GetPtr(ptr) => Expr::GetPtr(operand(func, ptr.src()), operand(func, ptr.index())),
+        _ => unreachable!("not a pure expression"),
+    }
+}
+
+fn swap_commutative(expr: Expr) -> Option<Expr> {
+    match expr {
+        Expr::Binary(op, lhs, rhs) => {
+            let commutative = matches!(
+                op,
+                BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Eq | BinaryOp::NotEq
+            );
+            commutative.then_some(Expr::Binary(op, rhs, lhs))
+        }
+        _ => None,
+    }
+}
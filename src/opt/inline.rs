@@ -0,0 +1,324 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::builder::{ BasicBlockBuilder, LocalInstBuilder, ValueBuilder };
+use koopa::ir::{ BasicBlock, Function, FunctionData, Program, Type, Value, ValueKind };
+
+use super::{ dom::DomTree, rewrite, ModulePass };
+
+// 函数内联: 被调用函数的指令数不超过阈值, 或者只有一处调用且不超过阈值的SINGLE_CALL_FACTOR倍时,
+// 把函数体复制到调用处。调用所在的块在call处拆开, 后半部分成为合并块,
+// 被调用函数中的每个ret变成带返回值跳转到合并块, 返回值是合并块的参数。
+// 递归的函数(调用图中能回到自己)不内联; 按调用图的后序处理, 被调用者先完成内联再计算大小。
+// 被内联的函数本身保留, 其他编译单元可能还会调用它
+pub struct Inliner {
+    threshold: usize,
+    inlined: usize,
+}
+
+/// Default instruction count up to which a callee is inlined at every call
+/// site, overridden by `--inline-threshold=<n>`.
+pub const DEFAULT_THRESHOLD: usize = 30;
+
+// 只有一处调用的函数内联后不会重复代码, 允许更大的函数体
+const SINGLE_CALL_FACTOR: usize = 10;
+
+impl Inliner {
+    pub fn new(threshold: usize) -> Self {
+        Inliner { threshold, inlined: 0 }
+    }
+
+    fn should_inline(&self, program: &Program, callee: Function, recursive: &HashSet<Function>, calls: usize) -> bool {
+        let data = program.func(callee);
+        if data.layout().entry_bb().is_none() || recursive.contains(&callee) {
+            return false;
+        }
+        let size = size_of(data);
+        size <= self.threshold || (calls == 1 && size <= self.threshold * SINGLE_CALL_FACTOR)
+    }
+}
+
+impl ModulePass for Inliner {
+    fn run_on(&mut self, program: &mut Program) -> bool {
+        let graph = call_graph(program);
+        let recursive = recursive_funcs(&graph);
+        let mut call_counts: HashMap<Function, usize> = HashMap::new();
+        for sites in graph.values() {
+            for callee in sites {
+                *call_counts.entry(*callee).or_insert(0) += 1;
+            }
+        }
+
+        let mut inlined = 0;
+        for caller in post_order(program, &graph) {
+            let sites: Vec<(Value, Function)> = call_sites(program.func(caller));
+            for (call, callee) in sites {
+                if callee == caller || !self.should_inline(program, callee, &recursive, call_counts[&callee]) {
+                    continue;
+                }
+                let body = Body::new(program.func(callee));
+                inline_call(program.func_mut(caller), call, &body);
+                inlined += 1;
+            }
+        }
+        self.inlined += inlined;
+        inlined > 0
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![("inlined calls", self.inlined)]
+    }
+}
+
+// 函数的大小: 指令条数
+fn size_of(func: &FunctionData) -> usize {
+    func.layout().bbs().nodes().map(|node| node.insts().len()).sum()
+}
+
+// 函数中的call指令及其调用的函数
+fn call_sites(func: &FunctionData) -> Vec<(Value, Function)> {
+    let mut sites = vec![];
+    for (_, node) in func.layout().bbs() {
+        for &inst in node.insts().keys() {
+            if let ValueKind::Call(call) = func.dfg().value(inst).kind() {
+                sites.push((inst, call.callee()));
+            }
+        }
+    }
+    sites
+}
+
+// 调用图: 函数 -> 每处调用的函数(同一个函数调用多次时出现多次)
+fn call_graph(program: &Program) -> HashMap<Function, Vec<Function>> {
+    program
+        .func_layout()
+        .iter()
+        .map(|&func| (func, call_sites(program.func(func)).into_iter().map(|(_, callee)| callee).collect()))
+        .collect()
+}
+
+// 沿调用关系能回到自己的函数
+fn recursive_funcs(graph: &HashMap<Function, Vec<Function>>) -> HashSet<Function> {
+    let mut recursive = HashSet::new();
+    for &func in graph.keys() {
+        let mut visited = HashSet::new();
+        let mut work = graph[&func].clone();
+        while let Some(callee) = work.pop() {
+            if callee == func {
+                recursive.insert(func);
+                break;
+            }
+            if visited.insert(callee) {
+                work.extend(graph.get(&callee).into_iter().flatten());
+            }
+        }
+    }
+    recursive
+}
+
+// 调用图的后序: 被调用者排在调用者之前(递归的函数之间顺序任意), 只包含有函数体的函数
+fn post_order(program: &Program, graph: &HashMap<Function, Vec<Function>>) -> Vec<Function> {
+    fn visit(
+        func: Function,
+        graph: &HashMap<Function, Vec<Function>>,
+        visited: &mut HashSet<Function>,
+        order: &mut Vec<Function>
+    ) {
+        if !visited.insert(func) {
+            return;
+        }
+        for &callee in graph.get(&func).into_iter().flatten() {
+            visit(callee, graph, visited, order);
+        }
+        order.push(func);
+    }
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for &func in program.func_layout() {
+        visit(func, graph, &mut visited, &mut order);
+    }
+    order.retain(|func| program.func(*func).layout().entry_bb().is_some());
+    order
+}
+
+// 被调用函数的函数体的副本; 调用者与被调用者都在Program中, 不能同时借用
+struct Body {
+    name: String,
+    params: Vec<Value>,
+    // 按逆后序排列的可达块, 入口块在最前, 每个块的定义先于使用出现
+    blocks: Vec<BlockCopy>,
+    // 函数中的常量(整数、undef等)
+    consts: HashMap<Value, (ValueKind, Type)>,
+}
+
+struct BlockCopy {
+    bb: BasicBlock,
+    name: Option<String>,
+    params: Vec<(Value, Option<String>, Type)>,
+    insts: Vec<(Value, ValueKind, Type, Option<String>)>,
+}
+
+impl Body {
+    fn new(func: &FunctionData) -> Self {
+        let dom = DomTree::new(func);
+        let mut blocks = vec![];
+        let mut consts = HashMap::new();
+        for &bb in dom.reverse_postorder() {
+            let params = func
+                .dfg()
+                .bb(bb)
+                .params()
+                .iter()
+                .map(|&param| {
+                    let data = func.dfg().value(param);
+                    (param, data.name().clone(), data.ty().clone())
+                })
+                .collect();
+            let mut insts = vec![];
+            for &inst in func.layout().bbs().node(&bb).unwrap().insts().keys() {
+                let data = func.dfg().value(inst);
+                for operand in data.kind().value_uses() {
+                    collect_consts(func, operand, &mut consts);
+                }
+                insts.push((inst, data.kind().clone(), data.ty().clone(), data.name().clone()));
+            }
+            blocks.push(BlockCopy { bb, name: func.dfg().bb(bb).name().clone(), params, insts });
+        }
+        Body { name: func.name()[1..].to_owned(), params: func.params().to_vec(), blocks, consts }
+    }
+}
+
+fn collect_consts(func: &FunctionData, value: Value, consts: &mut HashMap<Value, (ValueKind, Type)>) {
+    if value.is_global() || consts.contains_key(&value) {
+        return;
+    }
+    let data = func.dfg().value(value);
+    match data.kind() {
+        ValueKind::Integer(_) | ValueKind::ZeroInit(_) | ValueKind::Undef(_) => {}
+        ValueKind::Aggregate(aggregate) => {
+            for &elem in aggregate.elems() {
+                collect_consts(func, elem, consts);
+            }
+        }
+        _ => return,
+    }
+    consts.insert(value, (data.kind().clone(), data.ty().clone()));
+}
+
+// 被调用函数中的值 -> 调用者中对应的值
+struct ValueMap<'a> {
+    body: &'a Body,
+    values: HashMap<Value, Value>,
+    blocks: HashMap<BasicBlock, BasicBlock>,
+}
+
+impl ValueMap<'_> {
+    fn value(&mut self, func: &mut FunctionData, value: Value) -> Value {
+        if value.is_global() {
+            return value;
+        }
+        if let Some(&mapped) = self.values.get(&value) {
+            return mapped;
+        }
+        // 和irgen一样, 常量的每次使用都复制一个新的value
+        let (kind, ty) = self.body.consts[&value].clone();
+        match kind {
+            ValueKind::Integer(int) => func.dfg_mut().new_value().integer(int.value()),
+            ValueKind::ZeroInit(_) => func.dfg_mut().new_value().zero_init(ty),
+            ValueKind::Undef(_) => func.dfg_mut().new_value().undef(ty),
+            ValueKind::Aggregate(aggregate) => {
+                let elems = aggregate.elems().iter().map(|&elem| self.value(func, elem)).collect();
+                func.dfg_mut().new_value().aggregate(elems)
+            }
+            _ => unreachable!("not a constant"),
+        }
+    }
+}
+
+/// Replaces the call instruction `call` of the function by a copy of the
+/// callee's body.
+fn inline_call(func: &mut FunctionData, call: Value, body: &Body) {
+    let bb = func.layout().parent_bb(call).unwrap();
+    let entry = func.layout().entry_bb().unwrap();
+    let ValueKind::Call(call_data) = func.dfg().value(call).kind().clone() else {
+        unreachable!("not a call");
+    };
+
+    // 在call处拆开所在的块, call之后的指令移到合并块
+    let ret_ty = func.dfg().value(call).ty().clone();
+    let merge_params = if ret_ty.is_unit() { vec![] } else { vec![(Some("%ret".to_owned()), ret_ty)] };
+    let merge_name = format!("%{}_merge", body.name);
+    let merge = func.dfg_mut().new_bb().basic_block_with_param_names(Some(merge_name), merge_params);
+    func.layout_mut().bbs_mut().cursor_mut(bb).insert_key_after(merge).unwrap();
+    let after: Vec<Value> = func
+        .layout()
+        .bbs()
+        .node(&bb)
+        .unwrap()
+        .insts()
+        .keys()
+        .skip_while(|inst| **inst != call)
+        .skip(1)
+        .copied()
+        .collect();
+    for inst in after {
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        func.layout_mut().bb_mut(merge).insts_mut().push_key_back(inst).unwrap();
+    }
+    if let Some(&ret) = func.dfg().bb(merge).params().first() {
+        rewrite::replace_all_uses(func, call, ret);
+    }
+    rewrite::remove_inst(func, call);
+
+    // 先创建所有的块与块参数, 跳转与参数的使用都可以直接对应
+    let mut map = ValueMap { body, values: HashMap::new(), blocks: HashMap::new() };
+    for (&param, &arg) in body.params.iter().zip(call_data.args()) {
+        map.values.insert(param, arg);
+    }
+    for block in &body.blocks {
+        let name = block.name.as_ref().map(|name| format!("%{}_{}", body.name, &name[1..]));
+        let params = block.params.iter().map(|(_, name, ty)| (name.clone(), ty.clone())).collect();
+        let new_bb = func.dfg_mut().new_bb().basic_block_with_param_names(name, params);
+        func.layout_mut().bbs_mut().cursor_mut(merge).insert_key_before(new_bb).unwrap();
+        map.blocks.insert(block.bb, new_bb);
+        let new_params = func.dfg().bb(new_bb).params().to_vec();
+        for ((param, _, _), new_param) in block.params.iter().zip(new_params) {
+            map.values.insert(*param, new_param);
+        }
+    }
+    let jump = func.dfg_mut().new_value().jump(map.blocks[&body.blocks[0].bb]);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+
+    for block in &body.blocks {
+        let new_bb = map.blocks[&block.bb];
+        for (inst, kind, ty, name) in &block.insts {
+            let mut kind = kind.clone();
+            let operands: Vec<Value> = kind.value_uses().collect();
+            let mapped: HashMap<Value, Value> =
+                operands.into_iter().map(|operand| (operand, map.value(func, operand))).collect();
+            rewrite::map_operands(&mut kind, |value| mapped[&value]);
+            match &mut kind {
+                ValueKind::Jump(jump) => *jump.target_mut() = map.blocks[&jump.target()],
+                ValueKind::Branch(branch) => {
+                    *branch.true_bb_mut() = map.blocks[&branch.true_bb()];
+                    *branch.false_bb_mut() = map.blocks[&branch.false_bb()];
+                }
+                _ => {}
+            }
+            let new_inst = match kind {
+                ValueKind::Return(ret) => {
+                    let args = ret.value().into_iter().collect();
+                    func.dfg_mut().new_value().jump_with_args(merge, args)
+                }
+                kind => rewrite::new_inst(func, kind, ty),
+            };
+            func.dfg_mut().set_value_name(new_inst, name.clone());
+            // 局部变量放到调用者的入口块, 栈空间在函数开始时分配
+            if matches!(func.dfg().value(new_inst).kind(), ValueKind::Alloc(_)) {
+                func.layout_mut().bb_mut(entry).insts_mut().push_key_front(new_inst).unwrap();
+            } else {
+                func.layout_mut().bb_mut(new_bb).insts_mut().push_key_back(new_inst).unwrap();
+            }
+            map.values.insert(*inst, new_inst);
+        }
+    }
+}
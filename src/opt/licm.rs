@@ -0,0 +1,164 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::{ BasicBlock, BinaryOp, Function, FunctionData, Program, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::alias::{ self, AliasInfo, Base };
+use super::dom::DomTree;
+use super::loops::{ self, Loop };
+use super::ModulePass;
+
+// 循环不变量外提: 为每个循环插入前置块(preheader), 把循环中操作数都在循环外定义的
+// binary、getelemptr、getptr移到前置块; 除法与取模可能出错, 只有除数是0和-1以外的常量、
+// 或每次进入循环都会执行时才外提; 读取全局变量的load在循环中没有可能写它的store或call、
+// 且每次进入循环都会执行(或读取的是整个全局变量, 不会越界)时同样外提。
+// 从内层循环开始处理, 外提到内层前置块的指令在处理外层循环时可以继续外提。
+// 需要知道哪些函数可能修改全局变量, 因此是模块级的pass
+#[derive(Default)]
+pub struct Licm {
+    hoisted: usize,
+    preheaders: usize,
+}
+
+impl ModulePass for Licm {
+    fn run_on(&mut self, program: &mut Program) -> bool {
+        let clobbering = alias::clobbering_funcs(program);
+        let mut changed = false;
+        for func in program.func_layout().to_vec() {
+            let func = program.func_mut(func);
+            if func.layout().entry_bb().is_some() {
+                changed |= self.run_on_func(func, &clobbering);
+            }
+        }
+        changed
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![("hoisted instructions", self.hoisted), ("inserted preheaders", self.preheaders)]
+    }
+}
+
+impl Licm {
+    fn run_on_func(&mut self, func: &mut FunctionData, clobbering: &HashSet<Function>) -> bool {
+        let entry = func.layout().entry_bb().unwrap();
+        let dom = DomTree::new(func);
+        let mut inserted = 0;
+        for lp in loops::find_loops(&dom) {
+            // 入口块不能有参数, 也就不能作为有前置块的循环头
+            if lp.header != entry && lp.preheader(func).is_none() {
+                loops::insert_preheader(func, &lp);
+                inserted += 1;
+            }
+        }
+        self.preheaders += inserted;
+
+        // 插入前置块后重新计算循环, 使外层循环包含内层循环的前置块
+        let dom = DomTree::new(func);
+        let alias = AliasInfo::new(func);
+        let mut hoisted = 0;
+        for lp in loops::find_loops(&dom) {
+            if lp.header != entry {
+                hoisted += hoist_invariants(func, &dom, &alias, clobbering, &lp);
+            }
+        }
+        self.hoisted += hoisted;
+        inserted + hoisted > 0
+    }
+}
+
+fn hoist_invariants(
+    func: &mut FunctionData,
+    dom: &DomTree,
+    alias: &AliasInfo,
+    clobbering: &HashSet<Function>,
+    lp: &Loop
+) -> usize {
+    let preheader = lp.preheader(func).unwrap();
+    // 基本块参数 -> 所在的块
+    let mut param_owner: HashMap<Value, BasicBlock> = HashMap::new();
+    for &bb in &lp.blocks {
+        for &param in func.dfg().bb(bb).params() {
+            param_owner.insert(param, bb);
+        }
+    }
+    let memory = LoopMemory::new(func, alias, clobbering, lp);
+    let exiting = lp.exiting_blocks(func);
+    // 循环中有ret时可能不经过出口离开循环, 没有出口时循环不会结束, 都不能保证块会被执行
+    let returns = lp.blocks.iter().any(|bb| {
+        cfg::terminator(func, *bb).is_some_and(|term| matches!(func.dfg().value(term).kind(), ValueKind::Return(_)))
+    });
+    let always_executed = |bb: BasicBlock| {
+        !returns && !exiting.is_empty() && exiting.iter().all(|exit| dom.dominates(bb, *exit))
+    };
+
+    let mut hoisted = 0;
+    // 按逆后序访问, 操作数先于使用它的指令被外提
+    let blocks: Vec<BasicBlock> = dom.reverse_postorder().iter().filter(|bb| lp.contains(**bb)).copied().collect();
+    for bb in blocks {
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            let invariant_operands = func.dfg().value(inst).kind().value_uses().all(|operand| {
+                operand.is_global()
+                    || match func.dfg().value(operand).kind() {
+                        ValueKind::BlockArgRef(_) => !param_owner.contains_key(&operand),
+                        _ => func.layout().parent_bb(operand).is_none_or(|def| !lp.contains(def)),
+                    }
+            });
+            if !invariant_operands {
+                continue;
+            }
+            let movable = match func.dfg().value(inst).kind() {
+                ValueKind::Binary(binary) if matches!(binary.op(), BinaryOp::Div | BinaryOp::Mod) => {
+                    let safe_divisor = match func.dfg().value(binary.rhs()).kind() {
+                        ValueKind::Integer(int) => int.value() != 0 && int.value() != -1,
+                        _ => false,
+                    };
+                    safe_divisor || always_executed(bb)
+                }
+                ValueKind::Binary(_) | ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => true,
+                ValueKind::Load(load) => {
+                    memory.is_invariant_global(func, load.src()) && (always_executed(bb) || load.src().is_global())
+                }
+                _ => false,
+            };
+            if movable {
+                func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+                let term = cfg::terminator(func, preheader).unwrap();
+                func.layout_mut().bb_mut(preheader).insts_mut().cursor_mut(term).insert_key_before(inst).unwrap();
+                hoisted += 1;
+            }
+        }
+    }
+    hoisted
+}
+
+// 循环中对内存的写: store的地址, 以及是否调用了可能修改全局变量的函数
+struct LoopMemory<'a> {
+    alias: &'a AliasInfo,
+    stores: Vec<Value>,
+    calls_clobber: bool,
+}
+
+impl<'a> LoopMemory<'a> {
+    fn new(func: &FunctionData, alias: &'a AliasInfo, clobbering: &HashSet<Function>, lp: &Loop) -> Self {
+        let mut stores = vec![];
+        let mut calls_clobber = false;
+        for bb in &lp.blocks {
+            for &inst in func.layout().bbs().node(bb).unwrap().insts().keys() {
+                match func.dfg().value(inst).kind() {
+                    ValueKind::Store(store) => stores.push(store.dest()),
+                    ValueKind::Call(call) => calls_clobber |= clobbering.contains(&call.callee()),
+                    _ => {}
+                }
+            }
+        }
+        LoopMemory { alias, stores, calls_clobber }
+    }
+
+    // 地址指向全局变量, 且在循环中不会被修改
+    fn is_invariant_global(&self, func: &FunctionData, addr: Value) -> bool {
+        matches!(alias::base_of(func, addr).0, Base::Global(_))
+            && !self.calls_clobber
+            && self.stores.iter().all(|store| !self.alias.may_alias(func, *store, addr))
+    }
+}
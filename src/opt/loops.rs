@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use koopa::ir::builder::{ BasicBlockBuilder, LocalInstBuilder };
+use koopa::ir::{ BasicBlock, FunctionData, Value };
+
+use crate::irgen::cfg;
+use super::{ dom::DomTree, rewrite };
+
+// 自然循环: 回边(尾 -> 头, 头支配尾)上能不经过头到达尾的所有块; 头相同的回边合并为一个循环
+pub struct Loop {
+    pub header: BasicBlock,
+    pub blocks: HashSet<BasicBlock>,
+    // 回边的起点: 循环中跳转到循环头的块
+    pub latches: Vec<BasicBlock>,
+}
+
+impl Loop {
+    pub fn contains(&self, bb: BasicBlock) -> bool {
+        self.blocks.contains(&bb)
+    }
+
+    // 循环中有后继在循环外的块
+    pub fn exiting_blocks(&self, func: &FunctionData) -> Vec<BasicBlock> {
+        let mut exiting: Vec<BasicBlock> = self
+            .blocks
+            .iter()
+            .filter(|bb| cfg::successors(func, **bb).iter().any(|succ| !self.contains(*succ)))
+            .copied()
+            .collect();
+        exiting.sort_by_key(|bb| func.layout().bbs().keys().position(|b| b == bb));
+        exiting
+    }
+
+    // 唯一的循环外前驱, 且它只跳转到循环头
+    pub fn preheader(&self, func: &FunctionData) -> Option<BasicBlock> {
+        let mut outside = self.outside_preds(func).into_iter();
+        let pred = outside.next()?;
+        let only_header = cfg::successors(func, pred).iter().all(|succ| *succ == self.header);
+        (outside.next().is_none() && only_header).then_some(pred)
+    }
+
+    fn outside_preds(&self, func: &FunctionData) -> Vec<BasicBlock> {
+        let mut preds = vec![];
+        for &user in func.dfg().bb(self.header).used_by() {
+            if let Some(bb) = func.layout().parent_bb(user) {
+                if !self.contains(bb) && !preds.contains(&bb) {
+                    preds.push(bb);
+                }
+            }
+        }
+        preds
+    }
+}
+
+/// Finds the natural loops of the function, inner loops before the loops
+/// containing them.
+pub fn find_loops(dom: &DomTree) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = vec![];
+    for &header in dom.reverse_postorder() {
+        let latches: Vec<BasicBlock> = dom
+            .preds(header)
+            .iter()
+            .filter(|pred| dom.dominates(header, **pred))
+            .copied()
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+        let mut blocks = HashSet::from([header]);
+        let mut work = latches.clone();
+        while let Some(bb) = work.pop() {
+            if blocks.insert(bb) {
+                work.extend(dom.preds(bb).iter().copied());
+            }
+        }
+        loops.push(Loop { header, blocks, latches });
+    }
+    // 内层循环的块是外层循环的真子集
+    loops.sort_by_key(|lp| lp.blocks.len());
+    loops
+}
+
+/// Makes sure the loop has a preheader and returns it. A new preheader
+/// takes over the parameters of the header from the edges entering the loop.
+pub fn insert_preheader(func: &mut FunctionData, lp: &Loop) -> BasicBlock {
+    if let Some(preheader) = lp.preheader(func) {
+        return preheader;
+    }
+    let header = lp.header;
+    let entering: Vec<Value> = func
+        .dfg()
+        .bb(header)
+        .used_by()
+        .iter()
+        .filter(|user| func.layout().parent_bb(**user).is_some_and(|bb| !lp.contains(bb)))
+        .copied()
+        .collect();
+
+    let name = func.dfg().bb(header).name().as_ref().map(|name| format!("{}_preheader", name));
+    let params: Vec<(Option<String>, _)> = func
+        .dfg()
+        .bb(header)
+        .params()
+        .iter()
+        .map(|param| {
+            let data = func.dfg().value(*param);
+            (data.name().clone(), data.ty().clone())
+        })
+        .collect();
+    let preheader = func.dfg_mut().new_bb().basic_block_with_param_names(name, params);
+    func.layout_mut().bbs_mut().cursor_mut(header).insert_key_before(preheader).unwrap();
+    for inst in entering {
+        rewrite::retarget(func, inst, header, preheader);
+    }
+    let args = func.dfg().bb(preheader).params().to_vec();
+    let jump = func.dfg_mut().new_value().jump_with_args(header, args);
+    func.layout_mut().bb_mut(preheader).insts_mut().push_key_back(jump).unwrap();
+    preheader
+}
@@ -0,0 +1,194 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::builder::ValueBuilder;
+use koopa::ir::{ BasicBlock, FunctionData, Type, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::{ dom::DomTree, rewrite, FunctionPass };
+
+// 把只通过load/store访问的int局部变量(包括参数与&&/||的临时变量)提升为SSA值
+// 汇合点的phi用基本块参数表示, 前驱通过jump/br的参数传入; 只在变量活跃的汇合点添加参数(pruned SSA)
+pub struct Mem2Reg;
+
+impl FunctionPass for Mem2Reg {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let allocs = promotable_allocs(func);
+        if allocs.is_empty() {
+            return false;
+        }
+        // 支配关系只对可达块有意义
+        cfg::remove_unreachable_blocks(func);
+        let allocs: Vec<Value> = allocs.into_iter().filter(|alloc| func.dfg().values().contains_key(alloc)).collect();
+        let dom = DomTree::new(func);
+        let frontiers = dom.frontiers();
+
+        // 每个变量在哪些块的开头需要参数
+        let mut phis: HashMap<BasicBlock, Vec<(Value, Value)>> = HashMap::new();
+        for &alloc in &allocs {
+            let blocks = phi_blocks(func, &dom, &frontiers, alloc);
+            let mut ordered: Vec<BasicBlock> = blocks.into_iter().collect();
+            // 按逆后序添加, 使结果与HashSet的遍历顺序无关
+            ordered.sort_by_key(|bb| dom.reverse_postorder().iter().position(|b| b == bb));
+            let name = func.dfg().value(alloc).name().as_ref().map(|name| format!("%{}", &name[1..]));
+            for bb in ordered {
+                let param = rewrite::add_block_params(func, bb, vec![(name.clone(), Type::get_i32())])[0];
+                phis.entry(bb).or_default().push((alloc, param));
+            }
+        }
+
+        let mut renamer = Renamer {
+            promoted: allocs.iter().copied().collect(),
+            phis,
+            current: HashMap::new(),
+            loads: HashMap::new(),
+            zero: func.dfg_mut().new_value().integer(0),
+        };
+        let entry = func.layout().entry_bb().unwrap();
+        renamer.rename(func, &dom, entry);
+        // 替换load会重新构造使用它的br, 要等所有跳转都传入了新参数的实参之后才能进行
+        for (load, value) in renamer.loads {
+            rewrite::replace_all_uses(func, load, value);
+            rewrite::remove_inst(func, load);
+        }
+        for alloc in allocs {
+            rewrite::remove_inst(func, alloc);
+        }
+        true
+    }
+}
+
+// 类型为int、且只作为load的地址与store的目标使用的alloc
+fn promotable_allocs(func: &FunctionData) -> Vec<Value> {
+    let mut allocs = vec![];
+    for (_, node) in func.layout().bbs() {
+        for &inst in node.insts().keys() {
+            let data = func.dfg().value(inst);
+            if !matches!(data.kind(), ValueKind::Alloc(_)) || data.ty() != &Type::get_pointer(Type::get_i32()) {
+                continue;
+            }
+            let promotable = data.used_by().iter().all(|user| {
+                match func.dfg().value(*user).kind() {
+                    ValueKind::Load(_) => true,
+                    ValueKind::Store(store) => store.dest() == inst && store.value() != inst,
+                    _ => false,
+                }
+            });
+            if promotable {
+                allocs.push(inst);
+            }
+        }
+    }
+    allocs
+}
+
+// 需要为alloc添加参数的块: 写入块的迭代支配边界中, 变量在入口处活跃的块
+fn phi_blocks(
+    func: &FunctionData,
+    dom: &DomTree,
+    frontiers: &HashMap<BasicBlock, HashSet<BasicBlock>>,
+    alloc: Value
+) -> HashSet<BasicBlock> {
+    let mut defs: HashSet<BasicBlock> = HashSet::new();
+    // 在块内第一次写入之前读取了变量的块
+    let mut upward_uses: HashSet<BasicBlock> = HashSet::new();
+    for &bb in dom.reverse_postorder() {
+        for &inst in func.layout().bbs().node(&bb).unwrap().insts().keys() {
+            match func.dfg().value(inst).kind() {
+                ValueKind::Load(load) if load.src() == alloc && !defs.contains(&bb) => {
+                    upward_uses.insert(bb);
+                }
+                ValueKind::Store(store) if store.dest() == alloc => {
+                    defs.insert(bb);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // 变量在入口处活跃的块: 从读取处沿前驱反向传播, 遇到写入的块停止
+    let mut live_in: HashSet<BasicBlock> = HashSet::new();
+    let mut work: Vec<BasicBlock> = upward_uses.into_iter().collect();
+    while let Some(bb) = work.pop() {
+        if !live_in.insert(bb) {
+            continue;
+        }
+        for &pred in dom.preds(bb) {
+            if !defs.contains(&pred) {
+                work.push(pred);
+            }
+        }
+    }
+
+    let mut blocks = HashSet::new();
+    let mut work: Vec<BasicBlock> = defs.iter().copied().collect();
+    while let Some(bb) = work.pop() {
+        for &df in frontiers.get(&bb).into_iter().flatten() {
+            if live_in.contains(&df) && blocks.insert(df) {
+                work.push(df);
+            }
+        }
+    }
+    blocks
+}
+
+struct Renamer {
+    promoted: HashSet<Value>,
+    // 块 -> 该块开头的(变量, 参数)
+    phis: HashMap<BasicBlock, Vec<(Value, Value)>>,
+    // 变量当前的值
+    current: HashMap<Value, Value>,
+    // 被删除的load -> 替换它的值
+    loads: HashMap<Value, Value>,
+    // 未初始化的变量取0
+    zero: Value,
+}
+
+impl Renamer {
+    // 沿支配树前序遍历, 进入块时保存变量的值, 离开时恢复
+    fn rename(&mut self, func: &mut FunctionData, dom: &DomTree, bb: BasicBlock) {
+        let saved = self.current.clone();
+        for &(alloc, param) in self.phis.get(&bb).into_iter().flatten() {
+            self.current.insert(alloc, param);
+        }
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            match func.dfg().value(inst).kind().clone() {
+                ValueKind::Load(load) if self.promoted.contains(&load.src()) => {
+                    let value = self.value_of(load.src());
+                    self.loads.insert(inst, value);
+                }
+                ValueKind::Store(store) if self.promoted.contains(&store.dest()) => {
+                    // 存入的值可能是之前被删除的load, 记录替换后的值
+                    let value = self.loads.get(&store.value()).copied().unwrap_or(store.value());
+                    self.current.insert(store.dest(), value);
+                    rewrite::remove_inst(func, inst);
+                }
+                _ => {}
+            }
+        }
+        // 把变量的当前值传给后继块的参数
+        if let Some(term) = cfg::terminator(func, bb) {
+            let mut succs = cfg::successors(func, bb);
+            succs.dedup();
+            for succ in succs {
+                let args: Vec<Value> = self.phis
+                    .get(&succ)
+                    .into_iter()
+                    .flatten()
+                    .map(|&(alloc, _)| self.value_of(alloc))
+                    .collect();
+                if !args.is_empty() {
+                    rewrite::add_jump_args(func, term, succ, &args);
+                }
+            }
+        }
+        for &child in dom.children(bb) {
+            self.rename(func, dom, child);
+        }
+        self.current = saved;
+    }
+
+    fn value_of(&self, alloc: Value) -> Value {
+        self.current.get(&alloc).copied().unwrap_or(self.zero)
+    }
+}
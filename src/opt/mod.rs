@@ -0,0 +1,222 @@
+use std::time::{ Duration, Instant };
+
+use koopa::back::KoopaGenerator;
+use koopa::ir::{ FunctionData, Program };
+
+mod alias;
+mod dce;
+mod dom;
+mod gvn;
+mod inline;
+mod licm;
+mod loops;
+mod mem2reg;
+mod rewrite;
+mod sccp;
+mod simplify_cfg;
+mod tail_recursion;
+mod unroll;
+mod verify;
+
+// 在irgen与asmgen之间对Koopa IR进行的优化
+// 函数级的pass逐个处理有函数体的函数, 模块级的pass处理整个Program;
+// pass的名字用于--passes与--print-after, 不应随意修改
+
+pub trait FunctionPass {
+    // 返回是否修改了函数
+    fn run_on(&mut self, func: &mut FunctionData) -> bool;
+
+    // 到目前为止的统计数据(名称, 数量), 用于--stats
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![]
+    }
+}
+
+pub trait ModulePass {
+    fn run_on(&mut self, program: &mut Program) -> bool;
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![]
+    }
+}
+
+pub enum Pass {
+    Function(Box<dyn FunctionPass>),
+    Module(Box<dyn ModulePass>),
+}
+
+type CreatePass = fn(&Options) -> Pass;
+
+// 所有可用的pass: 名字 -> 构造函数
+const PASSES: &[(&str, CreatePass)] = &[
+    ("dce", |_| Pass::Function(Box::new(dce::Dce::default()))),
+    ("gvn", |_| Pass::Function(Box::new(gvn::Gvn::default()))),
+    ("inline", |options| {
+        let threshold = options.inline_threshold.unwrap_or(inline::DEFAULT_THRESHOLD);
+        Pass::Module(Box::new(inline::Inliner::new(threshold)))
+    }),
+    ("licm", |_| Pass::Module(Box::new(licm::Licm::default()))),
+    ("mem2reg", |_| Pass::Function(Box::new(mem2reg::Mem2Reg))),
+    ("sccp", |_| Pass::Function(Box::new(sccp::Sccp))),
+    ("simplify-cfg", |_| Pass::Function(Box::new(simplify_cfg::SimplifyCfg))),
+    ("tail-recursion", |_| Pass::Module(Box::new(tail_recursion::TailRecursion::default()))),
+    ("unroll", |options| {
+        let factor = options.unroll_factor.unwrap_or(unroll::DEFAULT_FACTOR);
+        Pass::Function(Box::new(unroll::Unroll::new(factor)))
+    }),
+    ("verify", |_| Pass::Module(Box::new(verify::Verify))),
+];
+
+// 各优化级别默认运行的pass, 按顺序执行
+fn pipeline(level: u32) -> Vec<&'static str> {
+    match level {
+        0 => vec![],
+        1 => vec!["mem2reg", "tail-recursion", "sccp", "dce", "simplify-cfg"],
+        _ => vec![
+            "mem2reg", "tail-recursion", "inline", "sccp", "gvn", "licm",
+            "unroll", "sccp", "gvn", "dce", "simplify-cfg",
+        ],
+    }
+}
+
+/// Options of the optimizer, set by `-O<level>`, `--passes=`,
+/// `--time-passes`, `--stats`, `--inline-threshold=`, `--unroll-factor=` and
+/// `--print-after=`.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub level: u32,
+    // --passes=a,b,c: 代替优化级别对应的pass列表
+    pub passes: Option<Vec<String>>,
+    pub time_passes: bool,
+    pub stats: bool,
+    // --inline-threshold=n: 内联的函数大小上限(指令条数), 默认为inline::DEFAULT_THRESHOLD
+    pub inline_threshold: Option<usize>,
+    // --unroll-factor=n: 部分展开时每次迭代执行的循环体份数, 默认为unroll::DEFAULT_FACTOR
+    pub unroll_factor: Option<usize>,
+    // 这些pass运行之后把IR输出到标准错误
+    pub print_after: Vec<String>,
+}
+
+pub fn is_known_pass(name: &str) -> bool {
+    PASSES.iter().any(|(pass, _)| *pass == name)
+}
+
+pub struct PassManager {
+    passes: Vec<(&'static str, Pass)>,
+    print_after: Vec<String>,
+    // 每个pass累计的运行时间, 按第一次运行的顺序
+    timings: Vec<(&'static str, Duration)>,
+    time_passes: bool,
+    stats: bool,
+}
+
+impl PassManager {
+    pub fn new(options: &Options) -> Result<Self, String> {
+        let names: Vec<&str> = match &options.passes {
+            Some(passes) => passes.iter().map(|pass| pass.as_str()).collect(),
+            None => pipeline(options.level),
+        };
+        let mut passes = vec![];
+        for name in names {
+            let (name, create) = PASSES
+                .iter()
+                .find(|(pass, _)| *pass == name)
+                .ok_or_else(|| format!("unknown pass `{}`", name))?;
+            passes.push((*name, create(options)));
+        }
+        for name in &options.print_after {
+            if !is_known_pass(name) {
+                return Err(format!("unknown pass `{}` in --print-after", name));
+            }
+        }
+        Ok(PassManager {
+            passes,
+            print_after: options.print_after.clone(),
+            timings: vec![],
+            time_passes: options.time_passes,
+            stats: options.stats,
+        })
+    }
+
+    /// Runs all passes on the program in order. In debug builds the IR is
+    /// verified after every pass.
+    pub fn run(&mut self, program: &mut Program) {
+        for idx in 0..self.passes.len() {
+            let start = Instant::now();
+            let (name, pass) = &mut self.passes[idx];
+            let name = *name;
+            match pass {
+                Pass::Function(pass) => {
+                    let funcs = program.func_layout().to_vec();
+                    for func in funcs {
+                        let func = program.func_mut(func);
+                        // 函数声明没有基本块
+                        if func.layout().entry_bb().is_some() {
+                            pass.run_on(func);
+                        }
+                    }
+                }
+                Pass::Module(pass) => {
+                    pass.run_on(program);
+                }
+            }
+            self.record_time(name, start.elapsed());
+            if cfg!(debug_assertions) {
+                if let Err(err) = verify::verify(program) {
+                    panic!("invalid IR after pass `{}`: {}", name, err);
+                }
+            }
+            if self.print_after.iter().any(|pass| pass == name) {
+                eprintln!("; *** IR after {} ***", name);
+                eprint!("{}", dump(program));
+            }
+        }
+    }
+
+    fn record_time(&mut self, name: &'static str, time: Duration) {
+        match self.timings.iter_mut().find(|(pass, _)| *pass == name) {
+            Some((_, total)) => *total += time,
+            None => self.timings.push((name, time)),
+        }
+    }
+
+    // 各pass的累计运行时间, 未开启--time-passes时为None
+    pub fn timing_report(&self) -> Option<String> {
+        if !self.time_passes {
+            return None;
+        }
+        let total: Duration = self.timings.iter().map(|(_, time)| *time).sum();
+        let mut report = String::from("pass timing:\n");
+        for (name, time) in &self.timings {
+            let percent = if total.is_zero() { 0.0 } else { time.as_secs_f64() / total.as_secs_f64() * 100.0 };
+            report.push_str(&format!("  {:<16} {:>10.3} ms {:>6.1}%\n", name, time.as_secs_f64() * 1000.0, percent));
+        }
+        report.push_str(&format!("  {:<16} {:>10.3} ms\n", "total", total.as_secs_f64() * 1000.0));
+        Some(report)
+    }
+
+    // 各pass的统计数据, 未开启--stats时为None; 同一个pass出现多次时分别列出
+    pub fn stats_report(&self) -> Option<String> {
+        if !self.stats {
+            return None;
+        }
+        let mut report = String::from("pass statistics:\n");
+        for (name, pass) in &self.passes {
+            let statistics = match pass {
+                Pass::Function(pass) => pass.statistics(),
+                Pass::Module(pass) => pass.statistics(),
+            };
+            for (stat, count) in statistics {
+                report.push_str(&format!("  {:<16} {:<28} {:>6}\n", name, stat, count));
+            }
+        }
+        Some(report)
+    }
+}
+
+// 文本形式的Koopa IR
+pub fn dump(program: &Program) -> String {
+    let mut gen = KoopaGenerator::new(Vec::new());
+    gen.generate_on(program).unwrap();
+    String::from_utf8(gen.writer()).unwrap()
+}
@@ -0,0 +1,202 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::builder::{ BasicBlockBuilder, LocalInstBuilder };
+use koopa::ir::{ BasicBlock, FunctionData, Type, TypeKind, Value, ValueKind };
+
+// 修改Koopa IR的工具: Koopa中value的数据不能直接修改, 只能通过replace_value_with整体替换,
+// 由它维护used_by关系; 这里先修改ValueKind的副本, 再按其内容重新构造指令
+
+/// Replaces the instruction `inst` by an instruction of the given kind,
+/// keeping its handle (and therefore its uses) and its name.
+pub fn rebuild(func: &mut FunctionData, inst: Value, kind: ValueKind) {
+    // replace_value_with会清空被替换的值自己的used_by集合,
+    // 需要按拓扑序重新构造所有(间接)使用它的指令, 恢复各自的used_by
+    let users = transitive_users(func, inst);
+    build(func, inst, kind);
+    for user in users {
+        let kind = func.dfg().value(user).kind().clone();
+        build(func, user, kind);
+    }
+}
+
+// 直接或间接使用inst的指令, 每条指令排在它用到的其他指令之后
+// 基本块参数不是指令, 因此使用关系构成有向无环图
+fn transitive_users(func: &FunctionData, inst: Value) -> Vec<Value> {
+    let mut closure: HashSet<Value> = HashSet::new();
+    let mut work = vec![inst];
+    while let Some(value) = work.pop() {
+        for &user in func.dfg().value(value).used_by() {
+            if closure.insert(user) {
+                work.push(user);
+            }
+        }
+    }
+    // 每条指令还有多少个不同的操作数(inst本身或闭包中的指令)尚未排好
+    let mut pending: HashMap<Value, usize> = HashMap::new();
+    for &user in &closure {
+        let operands: HashSet<Value> = func.dfg().value(user).kind().value_uses().collect();
+        let count = operands.iter().filter(|operand| **operand == inst || closure.contains(operand)).count();
+        pending.insert(user, count);
+    }
+    let mut order = vec![];
+    let mut ready = vec![inst];
+    while let Some(value) = ready.pop() {
+        for &user in func.dfg().value(value).used_by() {
+            let count = pending.get_mut(&user).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                order.push(user);
+                ready.push(user);
+            }
+        }
+    }
+    order
+}
+
+fn build(func: &mut FunctionData, inst: Value, kind: ValueKind) {
+    let data = func.dfg().value(inst);
+    let name = data.name().clone();
+    let ty = data.ty().clone();
+    build_with(func.dfg_mut().replace_value_with(inst), kind, &ty);
+    func.dfg_mut().set_value_name(inst, name);
+}
+
+/// Creates a new instruction of the given kind, not yet placed in any basic
+/// block. `ty` is the type of the instruction, only needed for `alloc`.
+pub fn new_inst(func: &mut FunctionData, kind: ValueKind, ty: &Type) -> Value {
+    build_with(func.dfg_mut().new_value(), kind, ty)
+}
+
+// 按ValueKind的内容调用对应的构造方法, 替换已有的指令与创建新指令共用
+fn build_with<B: LocalInstBuilder>(builder: B, kind: ValueKind, ty: &Type) -> Value {
+    match kind {
+        ValueKind::Alloc(_) => {
+            let TypeKind::Pointer(base) = ty.kind() else {
+                unreachable!("alloc of non-pointer type");
+            };
+            builder.alloc(base.clone())
+        }
+        ValueKind::Load(load) => builder.load(load.src()),
+        ValueKind::Store(store) => builder.store(store.value(), store.dest()),
+        ValueKind::GetPtr(ptr) => builder.get_ptr(ptr.src(), ptr.index()),
+        ValueKind::GetElemPtr(ptr) => builder.get_elem_ptr(ptr.src(), ptr.index()),
+        ValueKind::Binary(binary) => builder.binary(binary.op(), binary.lhs(), binary.rhs()),
+        ValueKind::Branch(branch) =>
+            builder.branch_with_args(
+                branch.cond(),
+                branch.true_bb(),
+                branch.false_bb(),
+                branch.true_args().to_vec(),
+                branch.false_args().to_vec()
+            ),
+        ValueKind::Jump(jump) => builder.jump_with_args(jump.target(), jump.args().to_vec()),
+        ValueKind::Call(call) => builder.call(call.callee(), call.args().to_vec()),
+        ValueKind::Return(ret) => builder.ret(ret.value()),
+        _ => unreachable!("not an instruction"),
+    }
+}
+
+// 用f替换指令中的每个值操作数
+pub fn map_operands(kind: &mut ValueKind, f: impl Fn(Value) -> Value) {
+    let map = |value: &mut Value| *value = f(*value);
+    match kind {
+        ValueKind::Load(load) => map(load.src_mut()),
+        ValueKind::Store(store) => {
+            map(store.value_mut());
+            map(store.dest_mut());
+        }
+        ValueKind::GetPtr(ptr) => {
+            map(ptr.src_mut());
+            map(ptr.index_mut());
+        }
+        ValueKind::GetElemPtr(ptr) => {
+            map(ptr.src_mut());
+            map(ptr.index_mut());
+        }
+        ValueKind::Binary(binary) => {
+            map(binary.lhs_mut());
+            map(binary.rhs_mut());
+        }
+        ValueKind::Branch(branch) => {
+            map(branch.cond_mut());
+            branch.true_args_mut().iter_mut().for_each(map);
+            branch.false_args_mut().iter_mut().for_each(map);
+        }
+        ValueKind::Jump(jump) => jump.args_mut().iter_mut().for_each(map),
+        ValueKind::Call(call) => call.args_mut().iter_mut().for_each(map),
+        ValueKind::Return(ret) => ret.value_mut().iter_mut().for_each(map),
+        _ => {}
+    }
+}
+
+/// Replaces every use of `old` in the function by `new`.
+pub fn replace_all_uses(func: &mut FunctionData, old: Value, new: Value) {
+    let users: Vec<Value> = func.dfg().value(old).used_by().iter().copied().collect();
+    for user in users {
+        let mut kind = func.dfg().value(user).kind().clone();
+        map_operands(&mut kind, |value| if value == old { new } else { value });
+        rebuild(func, user, kind);
+    }
+}
+
+/// Appends parameters to the basic block and returns them.
+pub fn add_block_params(func: &mut FunctionData, bb: BasicBlock, params: Vec<(Option<String>, Type)>) -> Vec<Value> {
+    // BlockArgRef只能随基本块一起创建, 且其下标在创建后不能修改:
+    // 先创建一个参数更多的临时块, 取出下标正确的那部分参数, 再删除临时块(连同剩下的参数)
+    let count = func.dfg().bb(bb).params().len();
+    let mut all: Vec<(Option<String>, Type)> = vec![(None, Type::get_i32()); count];
+    all.extend(params);
+    let tmp = func.dfg_mut().new_bb().basic_block_with_param_names(None, all);
+    let added = func.dfg_mut().bb_mut(tmp).params_mut().split_off(count);
+    func.dfg_mut().remove_bb(tmp);
+    func.dfg_mut().bb_mut(bb).params_mut().extend(&added);
+    added
+}
+
+/// Appends arguments to the edges from the terminator `inst` to `target`.
+pub fn add_jump_args(func: &mut FunctionData, inst: Value, target: BasicBlock, args: &[Value]) {
+    let mut kind = func.dfg().value(inst).kind().clone();
+    match &mut kind {
+        ValueKind::Jump(jump) => jump.args_mut().extend(args),
+        ValueKind::Branch(branch) => {
+            if branch.true_bb() == target {
+                branch.true_args_mut().extend(args);
+            }
+            if branch.false_bb() == target {
+                branch.false_args_mut().extend(args);
+            }
+        }
+        _ => unreachable!("not a terminator"),
+    }
+    rebuild(func, inst, kind);
+}
+
+/// Makes the terminator `inst` jump to `new` instead of `old`, keeping
+/// the arguments passed.
+pub fn retarget(func: &mut FunctionData, inst: Value, old: BasicBlock, new: BasicBlock) {
+    let mut kind = func.dfg().value(inst).kind().clone();
+    match &mut kind {
+        ValueKind::Jump(jump) => {
+            *jump.target_mut() = new;
+        }
+        ValueKind::Branch(branch) => {
+            if branch.true_bb() == old {
+                *branch.true_bb_mut() = new;
+            }
+            if branch.false_bb() == old {
+                *branch.false_bb_mut() = new;
+            }
+        }
+        _ => unreachable!("not a terminator"),
+    }
+    rebuild(func, inst, kind);
+}
+
+/// Removes the instruction from its basic block and from the function.
+/// The instruction must not be used any more.
+pub fn remove_inst(func: &mut FunctionData, inst: Value) {
+    if let Some(bb) = func.layout().parent_bb(inst) {
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+    }
+    func.dfg_mut().remove_value(inst);
+}
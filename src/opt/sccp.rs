@@ -0,0 +1,220 @@
+use std::collections::{ HashMap, HashSet };
+
+use koopa::ir::builder::{ LocalInstBuilder, ValueBuilder };
+use koopa::ir::{ BasicBlock, BinaryOp, FunctionData, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::{ rewrite, FunctionPass };
+
+// 稀疏条件常量传播(Wegman & Zadeck)
+// 同时沿SSA使用关系传播常量、沿控制流边传播可达性: 只有可达的边传入的参数参与合并,
+// 条件为常量的分支只有一条边可达。结束后把常量值替换为整数, 常量条件的br改为jump, 并删除不可达的块
+pub struct Sccp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+    // 尚未确定(例如只从不可达的边传入)
+    Undef,
+    Const(i32),
+    // 运行时才能确定
+    Overdefined,
+}
+
+impl Lattice {
+    fn meet(self, other: Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Undef, other) | (other, Lattice::Undef) => other,
+            (Lattice::Const(a), Lattice::Const(b)) if a == b => self,
+            _ => Lattice::Overdefined,
+        }
+    }
+}
+
+impl FunctionPass for Sccp {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let mut solver = Solver::default();
+        solver.solve(func);
+        solver.rewrite(func)
+    }
+}
+
+#[derive(Default)]
+struct Solver {
+    values: HashMap<Value, Lattice>,
+    executable: HashSet<BasicBlock>,
+    edges: HashSet<(BasicBlock, BasicBlock)>,
+    // 值改变了的指令/参数, 需要重新计算其使用者
+    ssa_work: Vec<Value>,
+    // 新的可达的边
+    flow_work: Vec<(BasicBlock, BasicBlock)>,
+}
+
+impl Solver {
+    fn solve(&mut self, func: &FunctionData) {
+        let entry = func.layout().entry_bb().unwrap();
+        self.executable.insert(entry);
+        self.visit_block(func, entry);
+        loop {
+            if let Some((from, to)) = self.flow_work.pop() {
+                if self.executable.insert(to) {
+                    self.visit_block(func, to);
+                } else {
+                    // 块已经可达, 只需要合并这条边传入的参数
+                    self.visit_inst(func, cfg::terminator(func, from).unwrap());
+                }
+            } else if let Some(value) = self.ssa_work.pop() {
+                for &user in func.dfg().value(value).used_by() {
+                    let reachable = func.layout().parent_bb(user).is_some_and(|bb| self.executable.contains(&bb));
+                    if reachable {
+                        self.visit_inst(func, user);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit_block(&mut self, func: &FunctionData, bb: BasicBlock) {
+        for &inst in func.layout().bbs().node(&bb).unwrap().insts().keys() {
+            self.visit_inst(func, inst);
+        }
+    }
+
+    fn visit_inst(&mut self, func: &FunctionData, inst: Value) {
+        let bb = func.layout().parent_bb(inst).unwrap();
+        match func.dfg().value(inst).kind() {
+            ValueKind::Binary(binary) => {
+                let lhs = self.lattice(func, binary.lhs());
+                let rhs = self.lattice(func, binary.rhs());
+                let result = match (lhs, rhs) {
+                    (Lattice::Const(lhs), Lattice::Const(rhs)) =>
+                        fold_binary(binary.op(), lhs, rhs).map_or(Lattice::Overdefined, Lattice::Const),
+                    (Lattice::Overdefined, _) | (_, Lattice::Overdefined) => Lattice::Overdefined,
+                    _ => Lattice::Undef,
+                };
+                self.update(inst, result);
+            }
+            ValueKind::Jump(jump) => self.mark_edge(func, bb, jump.target(), jump.args()),
+            ValueKind::Branch(branch) => {
+                let cond = self.lattice(func, branch.cond());
+                if matches!(cond, Lattice::Overdefined) || cond == Lattice::Const(0) {
+                    self.mark_edge(func, bb, branch.false_bb(), branch.false_args());
+                }
+                if matches!(cond, Lattice::Overdefined) || matches!(cond, Lattice::Const(c) if c != 0) {
+                    self.mark_edge(func, bb, branch.true_bb(), branch.true_args());
+                }
+            }
+            ValueKind::Return(_) | ValueKind::Store(_) => {}
+            // load、call以及地址的值在编译期无法确定
+            _ => self.update(inst, Lattice::Overdefined),
+        }
+    }
+
+    // 标记可达的边, 并把参数合并到目标块的参数中
+    fn mark_edge(&mut self, func: &FunctionData, from: BasicBlock, to: BasicBlock, args: &[Value]) {
+        if self.edges.insert((from, to)) {
+            self.flow_work.push((from, to));
+        }
+        let params = func.dfg().bb(to).params();
+        for (&arg, &param) in args.iter().zip(params) {
+            let value = self.lattice(func, arg).meet(self.lattice(func, param));
+            self.update(param, value);
+        }
+    }
+
+    fn update(&mut self, value: Value, lattice: Lattice) {
+        let old = self.values.get(&value).copied().unwrap_or(Lattice::Undef);
+        if old != lattice {
+            self.values.insert(value, lattice);
+            self.ssa_work.push(value);
+        }
+    }
+
+    fn lattice(&self, func: &FunctionData, value: Value) -> Lattice {
+        if value.is_global() {
+            return Lattice::Overdefined;
+        }
+        match func.dfg().value(value).kind() {
+            ValueKind::Integer(int) => Lattice::Const(int.value()),
+            ValueKind::FuncArgRef(_) => Lattice::Overdefined,
+            _ => self.values.get(&value).copied().unwrap_or(Lattice::Undef),
+        }
+    }
+
+    fn rewrite(&self, func: &mut FunctionData) -> bool {
+        let mut changed = false;
+        let blocks: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+        for bb in blocks {
+            if !self.executable.contains(&bb) {
+                continue;
+            }
+            // 值为常量的参数: 使用处替换为常量, 参数本身留给其他pass删除
+            let params = func.dfg().bb(bb).params().to_vec();
+            for param in params {
+                if let Some(Lattice::Const(c)) = self.values.get(&param) {
+                    if !func.dfg().value(param).used_by().is_empty() {
+                        let int = func.dfg_mut().new_value().integer(*c);
+                        rewrite::replace_all_uses(func, param, int);
+                        changed = true;
+                    }
+                }
+            }
+            let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+            for inst in insts {
+                let kind = func.dfg().value(inst).kind().clone();
+                match kind {
+                    ValueKind::Binary(_) => {
+                        if let Some(Lattice::Const(c)) = self.values.get(&inst) {
+                            let int = func.dfg_mut().new_value().integer(*c);
+                            rewrite::replace_all_uses(func, inst, int);
+                            rewrite::remove_inst(func, inst);
+                            changed = true;
+                        }
+                    }
+                    ValueKind::Branch(branch) => {
+                        let (target, args) = match self.lattice(func, branch.cond()) {
+                            Lattice::Const(0) => (branch.false_bb(), branch.false_args()),
+                            Lattice::Const(_) => (branch.true_bb(), branch.true_args()),
+                            _ => continue,
+                        };
+                        let name = func.dfg().value(inst).name().clone();
+                        func.dfg_mut().replace_value_with(inst).jump_with_args(target, args.to_vec());
+                        func.dfg_mut().set_value_name(inst, name);
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        changed |= cfg::remove_unreachable_blocks(func) > 0;
+        changed
+    }
+}
+
+// 与RISC-V的运算结果一致: 溢出时回绕, INT_MIN / -1 为 INT_MIN;
+// 除数为0时运行时的结果与目标平台有关, 不在编译期计算
+fn fold_binary(op: BinaryOp, lhs: i32, rhs: i32) -> Option<i32> {
+    let res = match op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div if rhs == 0 => return None,
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod if rhs == 0 => return None,
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => ((lhs as u32).wrapping_shr(rhs as u32)) as i32,
+        BinaryOp::Sar => lhs.wrapping_shr(rhs as u32),
+    };
+    Some(res)
+}
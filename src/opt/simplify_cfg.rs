@@ -0,0 +1,93 @@
+use koopa::ir::{ BasicBlock, FunctionData, ValueKind };
+
+use crate::irgen::cfg;
+use super::{ rewrite, FunctionPass };
+
+// 化简控制流图:
+// 1. 只有一条无参数jump的基本块, 把跳到它的分支直接指向jump的目标
+// 2. 以无参数jump结尾、且目标块只有这一个前驱时, 把目标块合并到当前块
+pub struct SimplifyCfg;
+
+impl FunctionPass for SimplifyCfg {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let mut changed = false;
+        loop {
+            let step = forward_empty_blocks(func) | merge_blocks(func);
+            if !step {
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+}
+
+// 块中只有 jump %target (无参数)时返回target
+fn empty_jump_target(func: &FunctionData, bb: BasicBlock) -> Option<BasicBlock> {
+    let insts = func.layout().bbs().node(&bb)?.insts();
+    if insts.len() != 1 || !func.dfg().bb(bb).params().is_empty() {
+        return None;
+    }
+    match func.dfg().value(*insts.front_key()?).kind() {
+        ValueKind::Jump(jump) if jump.args().is_empty() && jump.target() != bb => Some(jump.target()),
+        _ => None,
+    }
+}
+
+fn forward_empty_blocks(func: &mut FunctionData) -> bool {
+    let entry = func.layout().entry_bb().unwrap();
+    let blocks: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let mut changed = false;
+    for bb in blocks {
+        if bb == entry {
+            continue;
+        }
+        let Some(target) = empty_jump_target(func, bb) else {
+            continue;
+        };
+        let users: Vec<_> = func.dfg().bb(bb).used_by().iter().copied().collect();
+        for user in users {
+            rewrite::retarget(func, user, bb, target);
+        }
+        remove_block(func, bb);
+        changed = true;
+    }
+    changed
+}
+
+fn merge_blocks(func: &mut FunctionData) -> bool {
+    let entry = func.layout().entry_bb().unwrap();
+    let blocks: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let mut changed = false;
+    for bb in blocks {
+        // 之前的合并可能已经删除了这个块
+        let Some(jump) = cfg::terminator(func, bb) else {
+            continue;
+        };
+        let ValueKind::Jump(data) = func.dfg().value(jump).kind() else {
+            continue;
+        };
+        let succ = data.target();
+        if succ == bb || succ == entry || !data.args().is_empty() || func.dfg().bb(succ).used_by().len() != 1 {
+            continue;
+        }
+        rewrite::remove_inst(func, jump);
+        let insts: Vec<_> = func.layout().bbs().node(&succ).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            func.layout_mut().bb_mut(succ).insts_mut().remove(&inst);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+        }
+        remove_block(func, succ);
+        changed = true;
+    }
+    changed
+}
+
+// 删除已经没有前驱的基本块及其中的指令
+fn remove_block(func: &mut FunctionData, bb: BasicBlock) {
+    let (_, node) = func.layout_mut().bbs_mut().remove(&bb).unwrap();
+    for inst in node.insts().keys() {
+        func.dfg_mut().remove_value(*inst);
+    }
+    func.dfg_mut().remove_bb(bb);
+}
@@ -0,0 +1,199 @@
+use koopa::ir::builder::{ BasicBlockBuilder, LocalInstBuilder, ValueBuilder };
+use koopa::ir::{ BasicBlock, BinaryOp, Function, FunctionData, Program, Type, Value, ValueKind };
+
+use super::alias::{ self, Base };
+use super::{ rewrite, ModulePass };
+
+// 尾递归消除: 函数中调用自己、且调用的结果直接返回的call(尾调用)改为跳转回函数开头。
+// 入口块中除alloc外的指令移到新的循环头%tailrecurse, 函数参数改为它的基本块参数,
+// 入口块带着函数参数跳转到循环头, 尾调用处带着新的实参跳转到循环头。
+// 对 `return x + f(...)`(或 `*`)的形式引入累加器: 累加器也是循环头的参数, 初值为运算的单位元,
+// 尾调用处把x累加进去, 其他的ret返回累加器与原返回值的运算结果。
+// 实参中有局部数组的地址时不做变换, 跳转后局部数组会被覆盖
+#[derive(Default)]
+pub struct TailRecursion {
+    eliminated: usize,
+    accumulators: usize,
+}
+
+// 尾调用: 块末尾的call、可选的累加运算(运算, binary指令)与ret
+struct TailCall {
+    bb: BasicBlock,
+    call: Value,
+    accumulate: Option<(BinaryOp, Value)>,
+    ret: Value,
+}
+
+impl ModulePass for TailRecursion {
+    fn run_on(&mut self, program: &mut Program) -> bool {
+        let mut changed = false;
+        for func in program.func_layout().to_vec() {
+            let data = program.func_mut(func);
+            if data.layout().entry_bb().is_some() {
+                changed |= self.run_on_func(data, func);
+            }
+        }
+        changed
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![("eliminated tail calls", self.eliminated), ("introduced accumulators", self.accumulators)]
+    }
+}
+
+impl TailRecursion {
+    fn run_on_func(&mut self, func: &mut FunctionData, this: Function) -> bool {
+        let mut calls = find_tail_calls(func, this);
+        // 只用一个累加器, 运算不同的尾调用不做变换
+        let op = calls.iter().find_map(|call| call.accumulate.map(|(op, _)| op));
+        calls.retain(|call| call.accumulate.is_none_or(|(other, _)| Some(other) == op));
+        if calls.is_empty() {
+            return false;
+        }
+
+        let header = make_loop_header(func, op);
+        let params = func.dfg().bb(header).params().to_vec();
+        let acc = op.map(|_| *params.last().unwrap());
+        // 非尾调用处的ret返回 累加器 op 原返回值
+        if let (Some(op), Some(acc)) = (op, acc) {
+            let rets: Vec<Value> = func
+                .layout()
+                .bbs()
+                .nodes()
+                .filter_map(|node| node.insts().back_key().copied())
+                .filter(|inst| matches!(func.dfg().value(*inst).kind(), ValueKind::Return(_)))
+                .filter(|inst| calls.iter().all(|call| call.ret != *inst))
+                .collect();
+            for ret in rets {
+                let mut kind = func.dfg().value(ret).kind().clone();
+                let ValueKind::Return(data) = &mut kind else { unreachable!() };
+                let result = func.dfg_mut().new_value().binary(op, acc, data.value().unwrap());
+                *data.value_mut() = Some(result);
+                let bb = func.layout().parent_bb(ret).unwrap();
+                func.layout_mut().bb_mut(bb).insts_mut().cursor_mut(ret).insert_key_before(result).unwrap();
+                rewrite::rebuild(func, ret, kind);
+            }
+            self.accumulators += 1;
+        }
+
+        for call in &calls {
+            let ValueKind::Call(data) = func.dfg().value(call.call).kind() else { unreachable!() };
+            let mut args = data.args().to_vec();
+            rewrite::remove_inst(func, call.ret);
+            if let Some((op, binary)) = call.accumulate {
+                // 函数参数已经换成循环头的参数, 另一个操作数要从指令中重新读取
+                let ValueKind::Binary(data) = func.dfg().value(binary).kind() else { unreachable!() };
+                let x = if data.rhs() == call.call { data.lhs() } else { data.rhs() };
+                rewrite::remove_inst(func, binary);
+                let next = func.dfg_mut().new_value().binary(op, acc.unwrap(), x);
+                func.layout_mut().bb_mut(call.bb).insts_mut().push_key_back(next).unwrap();
+                args.push(next);
+            } else if let Some(acc) = acc {
+                args.push(acc);
+            }
+            rewrite::remove_inst(func, call.call);
+            let jump = func.dfg_mut().new_value().jump_with_args(header, args);
+            func.layout_mut().bb_mut(call.bb).insts_mut().push_key_back(jump).unwrap();
+        }
+        self.eliminated += calls.len();
+        true
+    }
+}
+
+fn find_tail_calls(func: &FunctionData, this: Function) -> Vec<TailCall> {
+    let mut calls = vec![];
+    for (&bb, node) in func.layout().bbs() {
+        let mut insts: Vec<Value> = node.insts().keys().copied().collect();
+        insts.reverse();
+        insts.truncate(3);
+        let Some(ValueKind::Return(ret)) = insts.first().map(|inst| func.dfg().value(*inst).kind()) else {
+            continue;
+        };
+        let is_self_call = |inst: Option<&Value>| {
+            inst.filter(|inst| {
+                matches!(func.dfg().value(**inst).kind(), ValueKind::Call(call) if call.callee() == this)
+            }).copied()
+        };
+        let tail_call = match ret.value() {
+            // f(...); ret
+            None => is_self_call(insts.get(1)).map(|call| (call, None)),
+            // ret f(...)
+            Some(value) if is_self_call(insts.get(1)) == Some(value) => Some((value, None)),
+            // ret x op f(...)
+            Some(value) if insts.get(1) == Some(&value) => {
+                let ValueKind::Binary(binary) = func.dfg().value(value).kind() else {
+                    continue;
+                };
+                let Some(call) = is_self_call(insts.get(2)) else {
+                    continue;
+                };
+                let accumulative = matches!(binary.op(), BinaryOp::Add | BinaryOp::Mul)
+                    && (binary.lhs() == call) != (binary.rhs() == call)
+                    && func.dfg().value(call).used_by().len() == 1
+                    && func.dfg().value(value).used_by().len() == 1;
+                accumulative.then_some((call, Some((binary.op(), value))))
+            }
+            _ => None,
+        };
+        let Some((call, accumulate)) = tail_call else {
+            continue;
+        };
+        let ValueKind::Call(data) = func.dfg().value(call).kind() else { unreachable!() };
+        let passes_local = data
+            .args()
+            .iter()
+            .any(|arg| !arg.is_global() && matches!(alias::base_of(func, *arg).0, Base::Local(_)));
+        if !passes_local {
+            calls.push(TailCall { bb, call, accumulate, ret: insts[0] });
+        }
+    }
+    calls
+}
+
+// 创建循环头, 把入口块中alloc之外的指令移过去, 函数参数的使用改为循环头的参数;
+// 有累加器时它是最后一个参数
+fn make_loop_header(func: &mut FunctionData, op: Option<BinaryOp>) -> BasicBlock {
+    let entry = func.layout().entry_bb().unwrap();
+    let func_params = func.params().to_vec();
+    let mut params: Vec<(Option<String>, Type)> = func_params
+        .iter()
+        .map(|param| {
+            let data = func.dfg().value(*param);
+            (data.name().as_ref().map(|name| format!("%{}", &name[1..])), data.ty().clone())
+        })
+        .collect();
+    if op.is_some() {
+        params.push((Some("%acc".to_owned()), Type::get_i32()));
+    }
+    let header = func.dfg_mut().new_bb().basic_block_with_param_names(Some("%tailrecurse".to_owned()), params);
+    func.layout_mut().bbs_mut().cursor_mut(entry).insert_key_after(header).unwrap();
+
+    let moved: Vec<Value> = func
+        .layout()
+        .bbs()
+        .node(&entry)
+        .unwrap()
+        .insts()
+        .keys()
+        .filter(|inst| !matches!(func.dfg().value(**inst).kind(), ValueKind::Alloc(_)))
+        .copied()
+        .collect();
+    for inst in moved {
+        func.layout_mut().bb_mut(entry).insts_mut().remove(&inst);
+        func.layout_mut().bb_mut(header).insts_mut().push_key_back(inst).unwrap();
+    }
+    let header_params = func.dfg().bb(header).params().to_vec();
+    for (&param, &new_param) in func_params.iter().zip(&header_params) {
+        rewrite::replace_all_uses(func, param, new_param);
+    }
+
+    // 累加器的初值: 加法为0, 乘法为1
+    let mut args = func_params;
+    if let Some(op) = op {
+        let identity = if op == BinaryOp::Add { 0 } else { 1 };
+        args.push(func.dfg_mut().new_value().integer(identity));
+    }
+    let jump = func.dfg_mut().new_value().jump_with_args(header, args);
+    func.layout_mut().bb_mut(entry).insts_mut().push_key_back(jump).unwrap();
+    header
+}
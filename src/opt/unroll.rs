@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use koopa::ir::builder::{ BasicBlockBuilder, LocalInstBuilder, ValueBuilder };
+use koopa::ir::{ BasicBlock, BinaryOp, FunctionData, Type, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::dom::DomTree;
+use super::loops::{ self, Loop };
+use super::{ rewrite, FunctionPass };
+
+// 循环展开: 处理只在循环头判断是否退出、条件为 归纳变量 比较 循环不变量 的最内层计数循环,
+// 归纳变量是循环头的参数, 经过唯一的回边时加上一个常量(步长)。
+// 初值与边界都是常量、迭代次数不超过FULL_UNROLL_TRIPS时完全展开: 循环体复制迭代次数份依次执行,
+// 最后回到原来的循环头, 此时条件不成立而退出, 原来的循环由之后的sccp删除;
+// 否则按展开因子部分展开: 新的循环每次执行factor份循环体, 进入前检查剩余的迭代是否足够,
+// 不够时转到原来的循环, 由它执行剩下的迭代。
+// 检查不通过时循环头会再执行一次, 因此循环头中不能有store与call
+pub struct Unroll {
+    factor: usize,
+    full: usize,
+    partial: usize,
+}
+
+/// Default number of loop body copies per iteration of a partially unrolled
+/// loop, overridden by `--unroll-factor=<n>`.
+pub const DEFAULT_FACTOR: usize = 4;
+
+// 完全展开的最大迭代次数
+const FULL_UNROLL_TRIPS: i64 = 16;
+// 展开后循环中指令条数的上限
+const SIZE_LIMIT: usize = 200;
+
+impl Unroll {
+    pub fn new(factor: usize) -> Self {
+        Unroll { factor, full: 0, partial: 0 }
+    }
+}
+
+impl FunctionPass for Unroll {
+    fn run_on(&mut self, func: &mut FunctionData) -> bool {
+        let entry = func.layout().entry_bb().unwrap();
+        let dom = DomTree::new(func);
+        let all = loops::find_loops(&dom);
+        // 最内层循环之间没有公共的块, 展开一个不影响其他循环
+        let headers: Vec<BasicBlock> = all
+            .iter()
+            .filter(|lp| lp.header != entry)
+            .filter(|lp| all.iter().all(|other| other.header == lp.header || !lp.contains(other.header)))
+            .map(|lp| lp.header)
+            .collect();
+
+        let mut changed = false;
+        for header in headers {
+            let mut dom = DomTree::new(func);
+            let mut lp = loops::find_loops(&dom).into_iter().find(|lp| lp.header == header).unwrap();
+            if lp.preheader(func).is_none() {
+                loops::insert_preheader(func, &lp);
+                dom = DomTree::new(func);
+                lp = loops::find_loops(&dom).into_iter().find(|lp| lp.header == header).unwrap();
+                changed = true;
+            }
+            let Some(counted) = CountedLoop::analyze(func, &lp) else {
+                continue;
+            };
+            let order: Vec<BasicBlock> = dom.reverse_postorder().iter().filter(|bb| lp.contains(**bb)).copied().collect();
+            match counted.trip_count(func) {
+                Some(0) => {}
+                Some(trips) if (trips as usize) * counted.size <= SIZE_LIMIT => {
+                    fully_unroll(func, &lp, &counted, &order, trips as usize);
+                    self.full += 1;
+                    changed = true;
+                }
+                _ if self.factor >= 2 && self.factor * counted.size <= SIZE_LIMIT => {
+                    let Some(distance) = counted.distance(self.factor) else {
+                        continue;
+                    };
+                    partially_unroll(func, &lp, &counted, &order, self.factor, distance);
+                    self.partial += 1;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+
+    fn statistics(&self) -> Vec<(&'static str, usize)> {
+        vec![("fully unrolled loops", self.full), ("partially unrolled loops", self.partial)]
+    }
+}
+
+// 计数循环: 每次迭代开始时 iv op bound 成立则执行循环体, 经过回边时iv加上step
+struct CountedLoop {
+    preheader: BasicBlock,
+    latch: BasicBlock,
+    cond: Value,
+    // 比较规范化为iv在左边, 只有lt、le、gt、ge
+    op: BinaryOp,
+    iv: Value,
+    bound: Value,
+    init: Value,
+    step: i32,
+    // 循环中的指令条数
+    size: usize,
+}
+
+impl CountedLoop {
+    fn analyze(func: &FunctionData, lp: &Loop) -> Option<Self> {
+        let header = lp.header;
+        let preheader = lp.preheader(func)?;
+        let [latch] = lp.latches[..] else {
+            return None;
+        };
+        if lp.exiting_blocks(func) != [header] {
+            return None;
+        }
+        let mut header_insts = func.layout().bbs().node(&header).unwrap().insts().keys();
+        if header_insts.any(|inst| matches!(func.dfg().value(*inst).kind(), ValueKind::Store(_) | ValueKind::Call(_))) {
+            return None;
+        }
+        let ValueKind::Branch(branch) = func.dfg().value(cfg::terminator(func, header)?).kind() else {
+            return None;
+        };
+        if !lp.contains(branch.true_bb()) || lp.contains(branch.false_bb()) {
+            return None;
+        }
+        let ValueKind::Jump(back) = func.dfg().value(cfg::terminator(func, latch)?).kind() else {
+            return None;
+        };
+
+        // 条件: 循环头参数与循环不变量的比较
+        let cond = branch.cond();
+        if cond.is_global() {
+            return None;
+        }
+        let ValueKind::Binary(binary) = func.dfg().value(cond).kind() else {
+            return None;
+        };
+        let params = func.dfg().bb(header).params();
+        let (op, iv, bound) = if params.contains(&binary.lhs()) && is_invariant(func, lp, binary.rhs()) {
+            (binary.op(), binary.lhs(), binary.rhs())
+        } else if params.contains(&binary.rhs()) && is_invariant(func, lp, binary.lhs()) {
+            let swapped = match binary.op() {
+                BinaryOp::Lt => BinaryOp::Gt,
+                BinaryOp::Gt => BinaryOp::Lt,
+                BinaryOp::Le => BinaryOp::Ge,
+                BinaryOp::Ge => BinaryOp::Le,
+                op => op,
+            };
+            (swapped, binary.rhs(), binary.lhs())
+        } else {
+            return None;
+        };
+        let index = params.iter().position(|param| *param == iv).unwrap();
+
+        // 回边传入的 iv + 常量 或 iv - 常量
+        let next = back.args()[index];
+        let ValueKind::Binary(update) = func.dfg().value(next).kind() else {
+            return None;
+        };
+        let step = match (update.op(), const_of(func, update.lhs()), const_of(func, update.rhs())) {
+            (BinaryOp::Add, None, Some(step)) if update.lhs() == iv => step,
+            (BinaryOp::Add, Some(step), None) if update.rhs() == iv => step,
+            (BinaryOp::Sub, None, Some(step)) if update.lhs() == iv => step.checked_neg()?,
+            _ => return None,
+        };
+        let increasing = match op {
+            BinaryOp::Lt | BinaryOp::Le => true,
+            BinaryOp::Gt | BinaryOp::Ge => false,
+            _ => return None,
+        };
+        if step == 0 || (step > 0) != increasing {
+            return None;
+        }
+
+        let ValueKind::Jump(enter) = func.dfg().value(cfg::terminator(func, preheader)?).kind() else {
+            return None;
+        };
+        let init = enter.args()[index];
+        let size = lp.blocks.iter().map(|bb| func.layout().bbs().node(bb).unwrap().insts().len()).sum();
+        Some(CountedLoop { preheader, latch, cond, op, iv, bound, init, step, size })
+    }
+
+    // 初值与边界都是常量时模拟得到迭代次数, 超过FULL_UNROLL_TRIPS或iv溢出时为None
+    fn trip_count(&self, func: &FunctionData) -> Option<i64> {
+        let (mut iv, bound) = (const_of(func, self.init)? as i64, const_of(func, self.bound)? as i64);
+        let mut trips = 0;
+        while compare(self.op, iv, bound) {
+            trips += 1;
+            iv += self.step as i64;
+            if trips > FULL_UNROLL_TRIPS || i32::try_from(iv).is_err() {
+                return None;
+            }
+        }
+        Some(trips)
+    }
+
+    // 进入展开的循环时iv与边界至少要相差的距离: 之后还要执行factor-1次, 即(factor-1)*|step|
+    fn distance(&self, factor: usize) -> Option<i32> {
+        i32::try_from((factor as i64 - 1) * (self.step as i64).abs()).ok()
+    }
+}
+
+fn compare(op: BinaryOp, lhs: i64, rhs: i64) -> bool {
+    match op {
+        BinaryOp::Lt => lhs < rhs,
+        BinaryOp::Le => lhs <= rhs,
+        BinaryOp::Gt => lhs > rhs,
+        BinaryOp::Ge => lhs >= rhs,
+        _ => unreachable!("not a comparison"),
+    }
+}
+
+fn const_of(func: &FunctionData, value: Value) -> Option<i32> {
+    if value.is_global() {
+        return None;
+    }
+    match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => None,
+    }
+}
+
+fn is_invariant(func: &FunctionData, lp: &Loop, value: Value) -> bool {
+    if value.is_global() {
+        return true;
+    }
+    match func.dfg().value(value).kind() {
+        ValueKind::BlockArgRef(_) => lp.blocks.iter().all(|bb| !func.dfg().bb(*bb).params().contains(&value)),
+        _ => func.layout().parent_bb(value).is_none_or(|def| !lp.contains(def)),
+    }
+}
+
+// 整数常量在每次使用时复制一个新的value
+fn reuse(func: &mut FunctionData, value: Value) -> Value {
+    match const_of(func, value) {
+        Some(int) => func.dfg_mut().new_value().integer(int),
+        None => value,
+    }
+}
+
+// 复制出的一份循环: 原来的块 -> 复制的块, 原来的值 -> 复制的值
+struct LoopCopy {
+    blocks: HashMap<BasicBlock, BasicBlock>,
+    values: HashMap<Value, Value>,
+}
+
+// 复制循环中的所有块(按逆后序, 定义先于使用), 放在原来的循环头之前;
+// 循环中的跳转指向复制出的块, 回边指向复制出的循环头
+fn clone_loop(func: &mut FunctionData, lp: &Loop, order: &[BasicBlock], copy: usize) -> LoopCopy {
+    let mut blocks = HashMap::new();
+    let mut values = HashMap::new();
+    for &bb in order {
+        let data = func.dfg().bb(bb);
+        let name = data.name().as_ref().map(|name| format!("{}_unroll{}", name, copy));
+        let old_params = data.params().to_vec();
+        let params: Vec<(Option<String>, Type)> = old_params
+            .iter()
+            .map(|param| {
+                let data = func.dfg().value(*param);
+                (data.name().clone(), data.ty().clone())
+            })
+            .collect();
+        let new_bb = func.dfg_mut().new_bb().basic_block_with_param_names(name, params);
+        func.layout_mut().bbs_mut().cursor_mut(lp.header).insert_key_before(new_bb).unwrap();
+        values.extend(old_params.into_iter().zip(func.dfg().bb(new_bb).params().to_vec()));
+        blocks.insert(bb, new_bb);
+    }
+    for &bb in order {
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            let data = func.dfg().value(inst);
+            let ty = data.ty().clone();
+            let mut kind = data.kind().clone();
+            let operands: Vec<Value> = kind.value_uses().collect();
+            let mut mapped = HashMap::new();
+            for operand in operands {
+                let new = match values.get(&operand) {
+                    Some(&value) => value,
+                    None => reuse(func, operand),
+                };
+                mapped.insert(operand, new);
+            }
+            rewrite::map_operands(&mut kind, |value| mapped[&value]);
+            match &mut kind {
+                ValueKind::Jump(jump) => {
+                    if let Some(&target) = blocks.get(&jump.target()) {
+                        *jump.target_mut() = target;
+                    }
+                }
+                ValueKind::Branch(branch) => {
+                    if let Some(&target) = blocks.get(&branch.true_bb()) {
+                        *branch.true_bb_mut() = target;
+                    }
+                    if let Some(&target) = blocks.get(&branch.false_bb()) {
+                        *branch.false_bb_mut() = target;
+                    }
+                }
+                _ => {}
+            }
+            let new_inst = rewrite::new_inst(func, kind, &ty);
+            func.layout_mut().bb_mut(blocks[&bb]).insts_mut().push_key_back(new_inst).unwrap();
+            values.insert(inst, new_inst);
+        }
+    }
+    LoopCopy { blocks, values }
+}
+
+fn replace_terminator(func: &mut FunctionData, bb: BasicBlock, new: Value) {
+    let term = cfg::terminator(func, bb).unwrap();
+    rewrite::remove_inst(func, term);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(new).unwrap();
+}
+
+// 复制的循环头不再判断条件, 直接进入循环体
+fn enter_body(func: &mut FunctionData, header: BasicBlock) {
+    let ValueKind::Branch(branch) = func.dfg().value(cfg::terminator(func, header).unwrap()).kind().clone() else {
+        unreachable!("loop header without a branch");
+    };
+    let jump = func.dfg_mut().new_value().jump_with_args(branch.true_bb(), branch.true_args().to_vec());
+    replace_terminator(func, header, jump);
+}
+
+// 依次连接各份复制: 第k份的回边跳转到第k+1份的循环头, 最后一份跳转到last
+fn chain_copies(func: &mut FunctionData, lp: &Loop, counted: &CountedLoop, copies: &[LoopCopy], last: BasicBlock) {
+    for (k, copy) in copies.iter().enumerate() {
+        let next = copies.get(k + 1).map_or(last, |next| next.blocks[&lp.header]);
+        let back = cfg::terminator(func, copy.blocks[&counted.latch]).unwrap();
+        rewrite::retarget(func, back, copy.blocks[&lp.header], next);
+    }
+    let enter = cfg::terminator(func, counted.preheader).unwrap();
+    rewrite::retarget(func, enter, lp.header, copies[0].blocks[&lp.header]);
+}
+
+fn fully_unroll(func: &mut FunctionData, lp: &Loop, counted: &CountedLoop, order: &[BasicBlock], trips: usize) {
+    let copies: Vec<LoopCopy> = (1..=trips).map(|copy| clone_loop(func, lp, order, copy)).collect();
+    for copy in &copies {
+        enter_body(func, copy.blocks[&lp.header]);
+    }
+    chain_copies(func, lp, counted, &copies, lp.header);
+}
+
+fn partially_unroll(
+    func: &mut FunctionData,
+    lp: &Loop,
+    counted: &CountedLoop,
+    order: &[BasicBlock],
+    factor: usize,
+    distance: i32
+) {
+    let copies: Vec<LoopCopy> = (1..=factor).map(|copy| clone_loop(func, lp, order, copy)).collect();
+    for copy in &copies[1..] {
+        enter_body(func, copy.blocks[&lp.header]);
+    }
+    chain_copies(func, lp, counted, &copies, copies[0].blocks[&lp.header]);
+
+    // 第一份的循环头: 原来的条件成立且剩余的迭代足够时执行展开的循环体, 否则转到原来的循环
+    // 距离用减法计算, 溢出时结果为负, 同样转到原来的循环
+    let first = &copies[0];
+    let header = first.blocks[&lp.header];
+    let iv = first.values[&counted.iv];
+    let bound = reuse(func, counted.bound);
+    let increasing = matches!(counted.op, BinaryOp::Lt | BinaryOp::Le);
+    let (lhs, rhs) = if increasing { (bound, iv) } else { (iv, bound) };
+    let distance = func.dfg_mut().new_value().integer(distance);
+    let remaining = func.dfg_mut().new_value().binary(BinaryOp::Sub, lhs, rhs);
+    let op = if matches!(counted.op, BinaryOp::Lt | BinaryOp::Gt) { BinaryOp::Gt } else { BinaryOp::Ge };
+    let enough = func.dfg_mut().new_value().binary(op, remaining, distance);
+    let guard = func.dfg_mut().new_value().binary(BinaryOp::And, first.values[&counted.cond], enough);
+    let term = cfg::terminator(func, header).unwrap();
+    for inst in [remaining, enough, guard] {
+        func.layout_mut().bb_mut(header).insts_mut().cursor_mut(term).insert_key_before(inst).unwrap();
+    }
+    let ValueKind::Branch(branch) = func.dfg().value(term).kind().clone() else {
+        unreachable!("loop header without a branch");
+    };
+    let params = func.dfg().bb(header).params().to_vec();
+    let br = func.dfg_mut().new_value().branch_with_args(
+        guard,
+        branch.true_bb(),
+        lp.header,
+        branch.true_args().to_vec(),
+        params
+    );
+    replace_terminator(func, header, br);
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use koopa::ir::{ BasicBlock, FunctionData, Program, TypeKind, Value, ValueKind };
+
+use crate::irgen::cfg;
+use super::{ dom::DomTree, ModulePass };
+
+// Koopa IR的结构检查, 调试构建中每个pass之后运行, 尽早发现pass产生的错误IR
+// 也可以作为名为verify的pass显式运行, 在发布构建中同样进行检查
+pub struct Verify;
+
+impl ModulePass for Verify {
+    fn run_on(&mut self, program: &mut Program) -> bool {
+        if let Err(err) = verify(program) {
+            panic!("invalid IR: {}", err);
+        }
+        false
+    }
+}
+
+/// Checks the structural invariants of every function in the program,
+/// returning a description of the first violation.
+pub fn verify(program: &Program) -> Result<(), String> {
+    for &func in program.func_layout() {
+        let data = program.func(func);
+        if data.layout().entry_bb().is_some() {
+            verify_func(data).map_err(|err| format!("in function {}: {}", data.name(), err))?;
+        }
+    }
+    Ok(())
+}
+
+fn verify_func(func: &FunctionData) -> Result<(), String> {
+    let entry = func.layout().entry_bb().unwrap();
+    if !func.dfg().bb(entry).params().is_empty() {
+        return Err("entry block has parameters".to_string());
+    }
+    // 每条指令在所在块中的位置, 用于检查同一块内定义先于使用
+    let mut position: HashMap<Value, usize> = HashMap::new();
+    for (&bb, node) in func.layout().bbs() {
+        // asmgen用块名生成标签
+        if func.dfg().bb(bb).name().is_none() {
+            return Err("basic block without name".to_string());
+        }
+        if node.insts().is_empty() {
+            return Err(format!("empty basic block {}", bb_name(func, bb)));
+        }
+        for (idx, &inst) in node.insts().keys().enumerate() {
+            position.insert(inst, idx);
+            let is_last = idx + 1 == node.insts().len();
+            if cfg::is_terminator(func.dfg().value(inst).kind()) != is_last {
+                return Err(format!("basic block {} is not ended by exactly one terminator", bb_name(func, bb)));
+            }
+        }
+    }
+    let dom = DomTree::new(func);
+    for (&bb, node) in func.layout().bbs() {
+        for &inst in node.insts().keys() {
+            verify_inst(func, &dom, bb, inst, &position)
+                .map_err(|err| format!("in basic block {}: {}", bb_name(func, bb), err))?;
+        }
+    }
+    Ok(())
+}
+
+fn verify_inst(
+    func: &FunctionData,
+    dom: &DomTree,
+    bb: BasicBlock,
+    inst: Value,
+    position: &HashMap<Value, usize>
+) -> Result<(), String> {
+    let data = func.dfg().value(inst);
+    for operand in data.kind().value_uses() {
+        if operand.is_global() {
+            continue;
+        }
+        let Some(operand_data) = func.dfg().values().get(&operand) else {
+            return Err("use of a removed value".to_string());
+        };
+        if !operand_data.used_by().contains(&inst) {
+            return Err("use missing from the used_by set of the operand".to_string());
+        }
+        match operand_data.kind() {
+            ValueKind::FuncArgRef(_) => {
+                if !func.params().contains(&operand) {
+                    return Err("argument of another function".to_string());
+                }
+            }
+            ValueKind::BlockArgRef(_) => {
+                let owner = func.layout().bbs().keys().find(|bb| func.dfg().bb(**bb).params().contains(&operand));
+                let Some(&owner) = owner else {
+                    return Err("parameter of a basic block not in the function".to_string());
+                };
+                if !dominates(dom, owner, bb) {
+                    return Err("use of a basic block parameter not dominated by its block".to_string());
+                }
+            }
+            kind if kind.is_const() => {}
+            _ => {
+                let Some(def_bb) = func.layout().parent_bb(operand) else {
+                    return Err("use of an instruction not in the layout".to_string());
+                };
+                if def_bb == bb && position[&operand] >= position[&inst] {
+                    return Err("instruction used before its definition".to_string());
+                }
+                if !dominates(dom, def_bb, bb) {
+                    return Err("use of an instruction not dominated by its definition".to_string());
+                }
+            }
+        }
+    }
+    for target in data.kind().bb_uses() {
+        if func.layout().bbs().node(&target).is_none() {
+            return Err("jump to a basic block not in the layout".to_string());
+        }
+        if !func.dfg().bb(target).used_by().contains(&inst) {
+            return Err("jump missing from the used_by set of the target".to_string());
+        }
+    }
+    match data.kind() {
+        ValueKind::Jump(jump) => verify_args(func, jump.target(), jump.args())?,
+        ValueKind::Branch(branch) => {
+            verify_args(func, branch.true_bb(), branch.true_args())?;
+            verify_args(func, branch.false_bb(), branch.false_args())?;
+        }
+        ValueKind::Return(ret) => {
+            let TypeKind::Function(_, ret_ty) = func.ty().kind() else {
+                unreachable!();
+            };
+            let ty = ret.value().map(|value| func.dfg().value(value).ty().clone());
+            if ty.as_ref().map_or(!ret_ty.is_unit(), |ty| ty != ret_ty) {
+                return Err("return value does not match the return type".to_string());
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn verify_args(func: &FunctionData, target: BasicBlock, args: &[Value]) -> Result<(), String> {
+    let params = func.dfg().bb(target).params();
+    if params.len() != args.len() {
+        return Err(format!(
+            "{} arguments passed to basic block {} with {} parameters",
+            args.len(),
+            bb_name(func, target),
+            params.len()
+        ));
+    }
+    Ok(())
+}
+
+// 不可达块中的使用不受支配关系约束
+fn dominates(dom: &DomTree, def_bb: BasicBlock, use_bb: BasicBlock) -> bool {
+    !dom.is_reachable(use_bb) || dom.dominates(def_bb, use_bb)
+}
+
+fn bb_name(func: &FunctionData, bb: BasicBlock) -> String {
+    func.dfg().bb(bb).name().clone().unwrap_or_else(|| "<unnamed>".to_string())
+}
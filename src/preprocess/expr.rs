@@ -0,0 +1,174 @@
+use super::macros::Token;
+
+// #if 条件表达式求值, 语义同C预处理器: 展开后剩余的标识符视为0, 运算在i64上进行
+
+#[derive(Debug, PartialEq)]
+enum ExprToken {
+    Num(i64),
+    Ident,
+    Op(&'static str),
+}
+
+// 多字符运算符排在前面, 保证最长匹配
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "*", "/", "%", "!", "~", "?", ":", "(", ")", "=",
+];
+
+// 二元运算符按优先级从低到高排列
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+pub fn eval(tokens: &[Token]) -> Option<i64> {
+    let mut expr_tokens = vec![];
+    for tok in tokens {
+        match tok {
+            Token::Space(_) => {}
+            Token::Ident(_) => expr_tokens.push(ExprToken::Ident),
+            Token::Number(num) => expr_tokens.push(ExprToken::Num(parse_number(num)?)),
+            Token::Punct(p) => expr_tokens.push(ExprToken::Op(OPERATORS.iter().find(|op| *op == p)?)),
+        }
+    }
+    // 符号在预处理记号中是单字符的, 相邻的符号能组成运算符时合并
+    // 注意空白已被丢弃, 因此 "< =" 也会被视为 "<="
+    let mut merged: Vec<ExprToken> = vec![];
+    for tok in expr_tokens {
+        if let (Some(ExprToken::Op(prev)), ExprToken::Op(cur)) = (merged.last(), &tok) {
+            let combined = format!("{}{}", prev, cur);
+            if let Some(op) = OPERATORS.iter().find(|op| **op == combined) {
+                *merged.last_mut().unwrap() = ExprToken::Op(op);
+                continue;
+            }
+        }
+        merged.push(tok);
+    }
+    let mut parser = Parser { tokens: merged, pos: 0 };
+    let val = parser.conditional()?;
+    if parser.pos == parser.tokens.len() {
+        Some(val)
+    } else {
+        None
+    }
+}
+
+struct Parser {
+    tokens: Vec<ExprToken>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.tokens.get(self.pos), Some(ExprToken::Op(cur)) if *cur == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn conditional(&mut self) -> Option<i64> {
+        let cond = self.binary(0)?;
+        if self.eat("?") {
+            let then = self.conditional()?;
+            if !self.eat(":") {
+                return None;
+            }
+            let other = self.conditional()?;
+            Some(if cond != 0 { then } else { other })
+        } else {
+            Some(cond)
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Option<i64> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for op in BINARY_LEVELS[level] {
+                if self.eat(op) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = apply(op, lhs, rhs)?;
+                    continue 'outer;
+                }
+            }
+            return Some(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        if self.eat("!") {
+            Some((self.unary()? == 0) as i64)
+        } else if self.eat("-") {
+            Some(self.unary()?.wrapping_neg())
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("~") {
+            Some(!self.unary()?)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Option<i64> {
+        if self.eat("(") {
+            let val = self.conditional()?;
+            return if self.eat(")") { Some(val) } else { None };
+        }
+        let val = match self.tokens.get(self.pos)? {
+            ExprToken::Num(num) => *num,
+            ExprToken::Ident => 0,
+            ExprToken::Op(_) => {
+                return None;
+            }
+        };
+        self.pos += 1;
+        Some(val)
+    }
+}
+
+fn apply(op: &str, lhs: i64, rhs: i64) -> Option<i64> {
+    Some(match op {
+        "||" => (lhs != 0 || rhs != 0) as i64,
+        "&&" => (lhs != 0 && rhs != 0) as i64,
+        "|" => lhs | rhs,
+        "^" => lhs ^ rhs,
+        "&" => lhs & rhs,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "<" => (lhs < rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" => lhs.checked_div(rhs)?,
+        "%" => lhs.checked_rem(rhs)?,
+        _ => unreachable!(),
+    })
+}
+
+fn parse_number(num: &str) -> Option<i64> {
+    let digits = num.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()
+    } else {
+        digits.parse().ok()
+    }
+}
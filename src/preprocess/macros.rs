@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use super::ErrorKind;
+
+// 宏展开按预处理记号进行, 空白也作为记号保留下来, 使展开结果尽量保持原样
+
+#[derive(Debug, Clone)]
+pub enum Token {
+    Ident(String),
+    // pp-number, 例如 0x1F, 不会被拆成数字和标识符
+    Number(String),
+    Punct(String),
+    Space(String),
+}
+
+impl Token {
+    pub fn text(&self) -> &str {
+        match self {
+            Token::Ident(s) | Token::Number(s) | Token::Punct(s) | Token::Space(s) => s,
+        }
+    }
+
+    pub fn is_space(&self) -> bool {
+        matches!(self, Token::Space(_))
+    }
+
+    pub fn is_ident(&self, name: &str) -> bool {
+        matches!(self, Token::Ident(s) if s == name)
+    }
+
+    pub fn is_punct(&self, punct: &str) -> bool {
+        matches!(self, Token::Punct(s) if s == punct)
+    }
+}
+
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let take_while = |mut i: usize, f: &dyn Fn(char) -> bool| {
+            while i < chars.len() && f(chars[i]) {
+                i += 1;
+            }
+            i
+        };
+        let token = if c.is_whitespace() {
+            i = take_while(i, &|c| c.is_whitespace());
+            Token::Space(chars[start..i].iter().collect())
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            i = take_while(i, &|c| c.is_ascii_alphanumeric() || c == '_');
+            Token::Ident(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit() {
+            i = take_while(i, &|c| c.is_ascii_alphanumeric() || c == '_');
+            Token::Number(chars[start..i].iter().collect())
+        } else {
+            // 只有##需要作为整体识别, 其余符号按单字符处理即可
+            i += if c == '#' && chars.get(i + 1) == Some(&'#') { 2 } else { 1 };
+            Token::Punct(chars[start..i].iter().collect())
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    // None表示对象式宏, Some表示函数式宏的形参列表
+    params: Option<Vec<String>>,
+    body: Vec<Token>,
+}
+
+impl Macro {
+    // 解析 #define 之后的部分, 例如 `N 10` 或 `MAX(a, b) ((a) > (b) ? (a) : (b))`
+    pub fn parse(def: &str) -> Option<(String, Macro)> {
+        let tokens = tokenize(def.trim());
+        let name = match tokens.first() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => {
+                return None;
+            }
+        };
+        let mut rest = &tokens[1..];
+        // 宏名与'('之间没有空白时才是函数式宏
+        let params = if rest.first().is_some_and(|t| t.is_punct("(")) {
+            let close = rest.iter().position(|t| t.is_punct(")"))?;
+            let mut params = vec![];
+            let mut expect_name = true;
+            for tok in rest[1..close].iter().filter(|t| !t.is_space()) {
+                match tok {
+                    Token::Ident(p) if expect_name => params.push(p.clone()),
+                    Token::Punct(p) if p == "," && !expect_name => {}
+                    _ => {
+                        return None;
+                    }
+                }
+                expect_name = !expect_name;
+            }
+            if expect_name && !params.is_empty() {
+                return None;
+            }
+            rest = &rest[close + 1..];
+            Some(params)
+        } else {
+            None
+        };
+        let body = trim_spaces(rest).to_vec();
+        Some((name, Macro { params, body }))
+    }
+}
+
+fn trim_spaces(tokens: &[Token]) -> &[Token] {
+    let start = tokens
+        .iter()
+        .position(|t| !t.is_space())
+        .unwrap_or(tokens.len());
+    let end = tokens
+        .iter()
+        .rposition(|t| !t.is_space())
+        .map_or(start, |p| p + 1);
+    &tokens[start..end]
+}
+
+// 展开一组记号; disabled记录正在展开的宏, 防止宏递归引用自身时无限展开
+pub fn expand(
+    macros: &HashMap<String, Macro>,
+    tokens: &[Token],
+    line: usize,
+    disabled: &mut Vec<String>
+) -> Result<Vec<Token>, ErrorKind> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let tok = &tokens[i];
+        i += 1;
+        let name = match tok {
+            Token::Ident(name) if !disabled.contains(name) => name,
+            _ => {
+                out.push(tok.clone());
+                continue;
+            }
+        };
+        if name == "__LINE__" {
+            out.push(Token::Number(line.to_string()));
+            continue;
+        }
+        let mac = match macros.get(name) {
+            Some(mac) => mac,
+            None => {
+                out.push(tok.clone());
+                continue;
+            }
+        };
+        let body = match &mac.params {
+            None => mac.body.clone(),
+            Some(params) => {
+                // 函数式宏的名字后面没有跟'(', 不展开
+                let mut j = i;
+                while j < tokens.len() && tokens[j].is_space() {
+                    j += 1;
+                }
+                if !tokens.get(j).is_some_and(|t| t.is_punct("(")) {
+                    out.push(tok.clone());
+                    continue;
+                }
+                let (args, next) = collect_args(tokens, j + 1).ok_or_else(||
+                    ErrorKind::UnterminatedMacroCall(name.clone())
+                )?;
+                i = next;
+                let args_ok =
+                    args.len() == params.len() ||
+                    (params.is_empty() && args.len() == 1 && trim_spaces(&args[0]).is_empty());
+                if !args_ok {
+                    return Err(ErrorKind::MacroArgCount(name.clone()));
+                }
+                substitute(macros, params, &args, &mac.body, line, disabled)?
+            }
+        };
+        disabled.push(name.clone());
+        let expanded = expand(macros, &body, line, disabled);
+        disabled.pop();
+        out.extend(expanded?);
+    }
+    Ok(out)
+}
+
+// 从'('之后开始收集实参, 按最外层的逗号切分; 返回实参列表以及')'之后的位置
+fn collect_args(tokens: &[Token], start: usize) -> Option<(Vec<Vec<Token>>, usize)> {
+    let mut args = vec![vec![]];
+    let mut depth = 0;
+    for (i, tok) in tokens.iter().enumerate().skip(start) {
+        if tok.is_punct("(") {
+            depth += 1;
+        } else if tok.is_punct(")") {
+            if depth == 0 {
+                return Some((args, i + 1));
+            }
+            depth -= 1;
+        } else if tok.is_punct(",") && depth == 0 {
+            args.push(vec![]);
+            continue;
+        }
+        args.last_mut().unwrap().push(tok.clone());
+    }
+    None
+}
+
+// 用实参替换宏体中的形参; 与##相邻的形参使用未展开的实参, 其余使用完全展开后的实参
+fn substitute(
+    macros: &HashMap<String, Macro>,
+    params: &[String],
+    args: &[Vec<Token>],
+    body: &[Token],
+    line: usize,
+    disabled: &mut Vec<String>
+) -> Result<Vec<Token>, ErrorKind> {
+    let param_index = |tok: &Token| {
+        match tok {
+            Token::Ident(name) => params.iter().position(|p| p == name),
+            _ => None,
+        }
+    };
+    // idx之后的第一个非空白记号
+    let next_non_space = |idx: usize| body[idx + 1..].iter().find(|t| !t.is_space());
+    let mut out: Vec<Token> = vec![];
+    let mut paste_next = false;
+    for (idx, tok) in body.iter().enumerate() {
+        if tok.is_punct("##") {
+            while out.last().is_some_and(|t| t.is_space()) {
+                out.pop();
+            }
+            paste_next = true;
+            continue;
+        }
+        if paste_next && tok.is_space() {
+            continue;
+        }
+        let replacement: Vec<Token> = match param_index(tok) {
+            Some(p) => {
+                let arg = trim_spaces(&args[p]);
+                let pasted =
+                    paste_next || next_non_space(idx).is_some_and(|t| t.is_punct("##"));
+                if pasted {
+                    arg.to_vec()
+                } else {
+                    expand(macros, arg, line, disabled)?
+                }
+            }
+            None => vec![tok.clone()],
+        };
+        if paste_next {
+            // 把左侧最后一个记号与右侧第一个记号拼接后重新切分
+            let left = out.pop().map(|t| t.text().to_string()).unwrap_or_default();
+            let mut rest = replacement.into_iter();
+            let right = rest
+                .next()
+                .map(|t| t.text().to_string())
+                .unwrap_or_default();
+            out.extend(tokenize(&(left + &right)));
+            out.extend(rest);
+            paste_next = false;
+        } else {
+            out.extend(replacement);
+        }
+    }
+    Ok(out)
+}
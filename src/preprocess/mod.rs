@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{ Path, PathBuf };
+
+use macros::{ tokenize, Macro, Token };
+
+mod expr;
+mod macros;
+
+// 预处理阶段: 在交给CompUnitParser之前处理 #include / #define / #if 等指令
+// 输出一份展开后的源码, 以及输出的每一行对应的原始文件与行号(LineMap), 使诊断信息能指回原始源码
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    IncludeNotFound(String),
+    IncludeTooDeep,
+    InvalidDirective(String),
+    InvalidMacroDef,
+    MacroArgCount(String),
+    UnterminatedMacroCall(String),
+    InvalidCondition,
+    UnmatchedEndif,
+    UnmatchedElse,
+    UnterminatedIf,
+    UserError(String),
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: error: ", self.file, self.line)?;
+        match &self.kind {
+            ErrorKind::IncludeNotFound(name) => write!(f, "cannot find include file \"{}\"", name),
+            ErrorKind::IncludeTooDeep => write!(f, "#include nested too deeply"),
+            ErrorKind::InvalidDirective(name) => write!(f, "invalid directive #{}", name),
+            ErrorKind::InvalidMacroDef => write!(f, "invalid macro definition"),
+            ErrorKind::MacroArgCount(name) =>
+                write!(f, "wrong number of arguments for macro `{}`", name),
+            ErrorKind::UnterminatedMacroCall(name) =>
+                write!(f, "unterminated argument list invoking macro `{}`", name),
+            ErrorKind::InvalidCondition => write!(f, "invalid #if expression"),
+            ErrorKind::UnmatchedEndif => write!(f, "#endif without #if"),
+            ErrorKind::UnmatchedElse => write!(f, "#else/#elif without #if"),
+            ErrorKind::UnterminatedIf => write!(f, "unterminated conditional directive"),
+            ErrorKind::UserError(msg) => write!(f, "#error {}", msg),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Result of preprocessing: the expanded text fed to the parser, and the
+/// origin of each of its lines.
+pub struct Source {
+    pub text: String,
+    pub line_map: LineMap,
+    // 生效的#pragma指令(去掉了"#pragma"), 由使用者解释
+    pub pragmas: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    // 输出的第i行 => (files中的下标, 原始行号,从1开始)
+    lines: Vec<(usize, usize)>,
+}
+
+/// A position in the original (not preprocessed) source.
+#[derive(Debug, Clone)]
+pub struct SourceLoc {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+impl LineMap {
+    fn push(&mut self, file: usize, line: usize) {
+        self.lines.push((file, line));
+    }
+
+    // 把预处理后文本中的字节偏移量映射回原始文件的位置
+    pub fn locate(&self, text: &str, offset: usize) -> SourceLoc {
+        let offset = offset.min(text.len());
+        let before = &text[..offset];
+        let out_line = before.matches('\n').count();
+        let col = offset - before.rfind('\n').map_or(0, |pos| pos + 1) + 1;
+        match self.lines.get(out_line).or(self.lines.last()) {
+            Some(&(file, line)) =>
+                SourceLoc {
+                    file: self.files[file].clone(),
+                    line,
+                    col,
+                },
+            None =>
+                SourceLoc {
+                    file: self.files.first().cloned().unwrap_or_default(),
+                    line: 1,
+                    col,
+                },
+        }
+    }
+}
+
+// 条件编译的状态, 每层#if对应一个
+struct Cond {
+    // 外层是否处于生效状态
+    parent_active: bool,
+    // 本层当前分支是否生效
+    active: bool,
+    // 本层是否已经有分支生效过(之后的#elif/#else都不再生效)
+    taken: bool,
+    seen_else: bool,
+    // #if所在的行, 用于报告未闭合的条件编译
+    line: usize,
+}
+
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    out: String,
+    line_map: LineMap,
+    pragmas: Vec<String>,
+    depth: usize,
+}
+
+/// Preprocesses the file at `path`, expanding includes, macros and
+/// conditional sections.
+pub fn preprocess(path: &str) -> Result<Source> {
+    let text = read_to_string(path).map_err(|_| Error {
+        kind: ErrorKind::IncludeNotFound(path.to_string()),
+        file: path.to_string(),
+        line: 0,
+    })?;
+    let mut pp = Preprocessor::new();
+    pp.process_file(Path::new(path), &text)?;
+    Ok(Source {
+        text: pp.out,
+        line_map: pp.line_map,
+        pragmas: pp.pragmas,
+    })
+}
+
+impl Preprocessor {
+    fn new() -> Self {
+        let mut pp = Preprocessor {
+            macros: HashMap::new(),
+            out: String::new(),
+            line_map: LineMap::default(),
+            pragmas: vec![],
+            depth: 0,
+        };
+        // SysY运行时头文件中的计时宏, 测试程序直接使用而不include
+        pp.define_builtin("starttime() _sysy_starttime(__LINE__)");
+        pp.define_builtin("stoptime() _sysy_stoptime(__LINE__)");
+        pp
+    }
+
+    fn define_builtin(&mut self, def: &str) {
+        let (name, mac) = Macro::parse(def).expect("invalid builtin macro");
+        self.macros.insert(name, mac);
+    }
+
+    fn process_file(&mut self, path: &Path, text: &str) -> Result<()> {
+        let file_id = self.line_map.files.len();
+        let file_name = path.to_string_lossy().to_string();
+        self.line_map.files.push(file_name.clone());
+        let err = |kind, line| Error { kind, file: file_name.clone(), line };
+
+        let text = strip_comments(text);
+        let physical: Vec<&str> = text.split('\n').collect();
+        let mut conds: Vec<Cond> = vec![];
+        let mut i = 0;
+        while i < physical.len() {
+            // 逻辑行从第start行开始
+            let start = i;
+            let mut logical = logical_line(&physical, &mut i);
+            let line_no = start + 1;
+            let active = conds.last().is_none_or(|c| c.active);
+
+            let trimmed = logical.trim_start();
+            if let Some(directive) = trimmed.strip_prefix('#') {
+                let directive = directive.trim_start();
+                let name_len = directive
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(directive.len());
+                let (name, rest) = directive.split_at(name_len);
+                let rest = rest.trim();
+                match name {
+                    "if" | "ifdef" | "ifndef" => {
+                        let cond = if !active {
+                            false
+                        } else {
+                            match name {
+                                "if" => self.eval_condition(rest, line_no).map_err(|k| err(k, line_no))?,
+                                "ifdef" => self.macros.contains_key(rest),
+                                _ => !self.macros.contains_key(rest),
+                            }
+                        };
+                        conds.push(Cond {
+                            parent_active: active,
+                            active: cond,
+                            taken: cond,
+                            seen_else: false,
+                            line: line_no,
+                        });
+                    }
+                    "elif" => {
+                        let parent_active = match conds.last() {
+                            Some(c) if !c.seen_else => c.parent_active && !c.taken,
+                            _ => {
+                                return Err(err(ErrorKind::UnmatchedElse, line_no));
+                            }
+                        };
+                        let cond =
+                            parent_active &&
+                            self.eval_condition(rest, line_no).map_err(|k| err(k, line_no))?;
+                        let top = conds.last_mut().unwrap();
+                        top.active = cond;
+                        top.taken |= cond;
+                    }
+                    "else" => {
+                        match conds.last_mut() {
+                            Some(c) if !c.seen_else => {
+                                c.active = c.parent_active && !c.taken;
+                                c.taken = true;
+                                c.seen_else = true;
+                            }
+                            _ => {
+                                return Err(err(ErrorKind::UnmatchedElse, line_no));
+                            }
+                        }
+                    }
+                    "endif" => {
+                        if conds.pop().is_none() {
+                            return Err(err(ErrorKind::UnmatchedEndif, line_no));
+                        }
+                    }
+                    _ if !active => {}
+                    "define" => {
+                        let (name, mac) = Macro::parse(rest).ok_or_else(||
+                            err(ErrorKind::InvalidMacroDef, line_no)
+                        )?;
+                        self.macros.insert(name, mac);
+                    }
+                    "undef" => {
+                        self.macros.remove(rest);
+                    }
+                    "include" => {
+                        self.emit_line("", file_id, line_no);
+                        self.include(path, rest, line_no)?;
+                        // include的内容已经输出, 本行不再占位
+                        self.skip_continuation(file_id, start, i);
+                        continue;
+                    }
+                    "error" => {
+                        return Err(err(ErrorKind::UserError(rest.to_string()), line_no));
+                    }
+                    "pragma" => {
+                        self.pragmas.push(rest.to_string());
+                    }
+                    // 空指令不影响输出
+                    "" => {}
+                    _ => {
+                        return Err(err(ErrorKind::InvalidDirective(name.to_string()), line_no));
+                    }
+                }
+                self.emit_line("", file_id, line_no);
+            } else if active {
+                // 函数式宏调用的实参可以跨越多行: 实参不完整时拼接下一行(不能是指令)后重新展开
+                let mut expanded = self.expand_line(&logical, line_no);
+                while
+                    matches!(expanded, Err(ErrorKind::UnterminatedMacroCall(_))) &&
+                    i < physical.len() &&
+                    !physical[i].trim_start().starts_with('#')
+                {
+                    logical.push(' ');
+                    logical.push_str(&logical_line(&physical, &mut i));
+                    expanded = self.expand_line(&logical, line_no);
+                }
+                let expanded = expanded.map_err(|k| err(k, line_no))?;
+                self.emit_line(&expanded, file_id, line_no);
+            } else {
+                self.emit_line("", file_id, line_no);
+            }
+            self.skip_continuation(file_id, start, i);
+        }
+        if let Some(cond) = conds.last() {
+            return Err(err(ErrorKind::UnterminatedIf, cond.line));
+        }
+        Ok(())
+    }
+
+    // 续行(以及跨行的宏调用)被拼接到了逻辑行中, 为保持行数一致输出空行
+    fn skip_continuation(&mut self, file_id: usize, start: usize, end: usize) {
+        for line in start + 1..end {
+            self.emit_line("", file_id, line + 1);
+        }
+    }
+
+    fn emit_line(&mut self, line: &str, file_id: usize, line_no: usize) {
+        self.out.push_str(line);
+        self.out.push('\n');
+        self.line_map.push(file_id, line_no);
+    }
+
+    fn include(&mut self, cur_path: &Path, arg: &str, line_no: usize) -> Result<()> {
+        let err = |kind| Error {
+            kind,
+            file: cur_path.to_string_lossy().to_string(),
+            line: line_no,
+        };
+        let name = arg
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .ok_or_else(|| err(ErrorKind::InvalidDirective(format!("include {}", arg))))?;
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(err(ErrorKind::IncludeTooDeep));
+        }
+        // 相对于当前文件所在目录查找
+        let path: PathBuf = cur_path.parent().unwrap_or(Path::new("")).join(name);
+        let text = read_to_string(&path).map_err(|_|
+            err(ErrorKind::IncludeNotFound(name.to_string()))
+        )?;
+        self.depth += 1;
+        let res = self.process_file(&path, &text);
+        self.depth -= 1;
+        res
+    }
+
+    fn expand_line(&self, line: &str, line_no: usize) -> std::result::Result<String, ErrorKind> {
+        let tokens = tokenize(line);
+        let expanded = macros::expand(&self.macros, &tokens, line_no, &mut vec![])?;
+        Ok(
+            expanded
+                .iter()
+                .map(|t| t.text())
+                .collect()
+        )
+    }
+
+    fn eval_condition(&self, cond: &str, line_no: usize) -> std::result::Result<bool, ErrorKind> {
+        // 先处理defined, 避免其参数被当作宏展开
+        let tokens = tokenize(cond);
+        let mut resolved = vec![];
+        let mut iter = tokens.iter().filter(|t| !t.is_space()).peekable();
+        while let Some(tok) = iter.next() {
+            if tok.is_ident("defined") {
+                let paren = iter.peek().is_some_and(|t| t.is_punct("("));
+                if paren {
+                    iter.next();
+                }
+                let name = match iter.next() {
+                    Some(Token::Ident(name)) => name.clone(),
+                    _ => {
+                        return Err(ErrorKind::InvalidCondition);
+                    }
+                };
+                if paren && !iter.next().is_some_and(|t| t.is_punct(")")) {
+                    return Err(ErrorKind::InvalidCondition);
+                }
+                let defined = self.macros.contains_key(&name) as i32;
+                resolved.push(Token::Number(defined.to_string()));
+            } else {
+                resolved.push(tok.clone());
+            }
+        }
+        let expanded = macros::expand(&self.macros, &resolved, line_no, &mut vec![])?;
+        expr::eval(&expanded).map(|v| v != 0).ok_or(ErrorKind::InvalidCondition)
+    }
+}
+
+// 从第i行开始读取一个逻辑行: 拼接以'\'结尾的续行, i移动到下一个逻辑行
+fn logical_line(physical: &[&str], i: &mut usize) -> String {
+    let mut logical = String::new();
+    loop {
+        let line = physical[*i].strip_suffix('\r').unwrap_or(physical[*i]);
+        *i += 1;
+        match line.strip_suffix('\\') {
+            Some(prefix) if *i < physical.len() => logical.push_str(prefix),
+            _ => {
+                logical.push_str(line);
+                return logical;
+            }
+        }
+    }
+}
+
+// 去掉注释, 但保留其中的换行, 使行号保持不变
+// 字符串与字符字面量中的//和/*不是注释, 原样保留; 未闭合的字面量到行尾为止
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('"' | '\'', _) => {
+                out.push(c);
+                while let Some(next) = chars.next_if(|&next| next != '\n') {
+                    out.push(next);
+                    if next == c {
+                        break;
+                    }
+                    // 转义序列中的引号与反斜杠不结束字面量
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next_if(|&escaped| escaped != '\n') {
+                            out.push(escaped);
+                        }
+                    }
+                }
+            }
+            ('/', Some('/')) => {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,44 @@
+use crate::warnings;
+
+// 常量下标越界: 局部、全局与常量数组, 多维数组的每一维, 以及常量表达式下标;
+// 数组参数的第一维长度未知, 不做检查
+
+const SRC: &str = r#"int g[4];
+const int c[3] = {1, 2, 3};
+int f(int p[][3]) {
+    p[100][1] = 1;
+    p[0][3] = 2;
+    return p[-1][0];
+}
+int main() {
+    int a[10];
+    int m[2][3];
+    a[10] = 1;
+    a[-1] = 2;
+    a[9] = 3;
+    m[1][3] = 4;
+    m[2][0] = 5;
+    g[4] = c[3];
+    putint(a[1 + 9] + c[2]);
+    return f(m) + m[1][2];
+}
+"#;
+
+#[test]
+fn constant_out_of_bounds_indices() {
+    let stderr = warnings(SRC, &[]);
+    let expected = [
+        "test.c:5:5: warning: index 3 is out of bounds for dimension 2 of `p` (size 3) [-Warray-bounds]",
+        "test.c:11:5: warning: index 10 is out of bounds for dimension 1 of `a` (size 10) [-Warray-bounds]",
+        "test.c:12:5: warning: index -1 is out of bounds for dimension 1 of `a` (size 10) [-Warray-bounds]",
+        "test.c:14:5: warning: index 3 is out of bounds for dimension 2 of `m` (size 3) [-Warray-bounds]",
+        "test.c:15:5: warning: index 2 is out of bounds for dimension 1 of `m` (size 2) [-Warray-bounds]",
+        "test.c:16:5: warning: index 4 is out of bounds for dimension 1 of `g` (size 4) [-Warray-bounds]",
+        "test.c:16:12: warning: index 3 is out of bounds for dimension 1 of `c` (size 3) [-Warray-bounds]",
+        "test.c:17:12: warning: index 10 is out of bounds for dimension 1 of `a` (size 10) [-Warray-bounds]",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":0,"warnings":8,"lints":{"array-bounds":8}}"#), "{}", stderr);
+}
@@ -0,0 +1,60 @@
+use crate::{ check, errors };
+
+// 赋值表达式: 右结合, 值为写入的值; 先计算左值的地址(包括下标), 再计算右边的值;
+// 赋值不能出现在常量表达式中
+
+#[test]
+fn assignment_expressions() {
+    let src = r#"
+int g;
+
+int next() {
+    g = g + 1;
+    return g;
+}
+
+int main() {
+    int a, b, c, n = 0, i = 0, arr[4] = {};
+    a = b = c = 5;
+    putint(a + b * 10 + c * 100);
+    putch(32);
+    putint((i = 2) * 10 + i);
+    putch(32);
+    i = 0;
+    arr[i] = (i = 2);
+    arr[i = 3] = i + 1;
+    arr[next()] = next() * 10;
+    putint(arr[0] * 1000 + arr[1] * 100 + arr[2] * 10 + arr[3]);
+    putch(32);
+    while ((c = getch()) != 10) {
+        if (c != 32) n = n * 10 + c - 48;
+    }
+    putint(n);
+    putch(32);
+    putint(getint());
+    return a = 7;
+}
+"#;
+    let output = check(src, "1 2 3\n45", &["--passes=mem2reg,verify,sccp,dce,verify"]);
+    assert_eq!((output.stdout.as_str(), output.exit_code), ("555 22 4004 123 45", 7));
+}
+
+#[test]
+fn assignment_is_not_constant() {
+    let src = r#"
+int g;
+int h = (g = 1);
+
+int main() {
+    int x;
+    const int c = (x = 2);
+    int a[(x = 3)];
+    return 0;
+}
+"#;
+    let stderr = errors(src, &[]);
+    for loc in ["3:10", "7:20", "8:11"] {
+        let expected = format!("test.c:{}: error: expression is not a compile-time constant", loc);
+        assert!(stderr.contains(&expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::check;
+
+// 死代码删除: 只有被有副作用的指令用到的值保留, 调用与store即使结果没有被使用也要保留
+
+#[test]
+fn side_effects_are_kept() {
+    let src = r#"
+int g = 0;
+int count() {
+    g = g + 1;
+    return g;
+}
+int main() {
+    int unused = count() * 2 + 5;
+    int a[3] = {1, 2, 3};
+    a[1] = 9;
+    int i = 0, dead = 0, live = 0;
+    while (i < 4) {
+        dead = dead + i * 3;
+        live = live + a[i % 3];
+        count();
+        i = i + 1;
+    }
+    putint(live);
+    return g;
+}
+"#;
+    let output = check(src, "", &["--passes=mem2reg,dce,verify", "--passes=mem2reg,sccp,dce,simplify-cfg,verify"]);
+    assert_eq!((output.stdout.as_str(), output.exit_code), ("14", 5));
+}
+
+#[test]
+fn dead_loop_params() {
+    let src = r#"
+int main() {
+    int n = getint(), i = 0, a = 1, b = 2, c = 0;
+    while (i < n) {
+        int t = a;
+        a = b;
+        b = t + b;
+        c = c + 1;
+        i = i + 1;
+    }
+    putint(c);
+    return 0;
+}
+"#;
+    assert_eq!(check(src, "6", &["--passes=mem2reg,dce,verify"]).stdout, "6");
+}
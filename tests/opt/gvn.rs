@@ -0,0 +1,55 @@
+use crate::check;
+
+// 全局值编号: 不可交换的运算不能合并, 可能写同一地址的store与修改全局变量的调用之后要重新load
+
+#[test]
+fn non_commutative_operations() {
+    let src = r#"
+int main() {
+    int a = getint(), b = getint();
+    int x = a - b, y = b - a, z = a - b;
+    int p = a / b, q = b / a, r = a % b, s = b % a;
+    int l = a < b, g = b < a;
+    putint(x + y * 10 + z * 100 + p * 1000 + q * 10000 + r + s + l * 7 + g * 11);
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,gvn,verify", "--passes=mem2reg,gvn,dce,verify"];
+    assert_eq!(check(src, "9 4", &passes).stdout, "2471");
+}
+
+#[test]
+fn loads_after_aliasing_writes() {
+    let src = r#"
+int g[4];
+int n = 1;
+
+void bump() {
+    n = n + 1;
+}
+
+int f(int a[], int b[], int i) {
+    int s = a[i];
+    b[0] = s + 10;
+    s = s + a[i];
+    bump();
+    s = s + n;
+    g[i] = 5;
+    s = s + g[1];
+    if (s > 0) s = s + a[i] + g[i]; else s = s - a[i];
+    return s;
+}
+
+int main() {
+    int x[4] = {1, 2, 3, 4};
+    putint(f(x, x, 0));
+    putch(32);
+    putint(f(x, g, 1));
+    putch(32);
+    putint(f(g, g, 0));
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,gvn,verify", "--passes=mem2reg,gvn,licm,gvn,dce,verify"];
+    assert_eq!(check(src, "", &passes).stdout, "30 19 53");
+}
@@ -0,0 +1,161 @@
+use crate::errors;
+
+// 降级到HIR时的语义错误: 报告在出错的位置, 之后用合法的HIR代替出错的部分继续检查,
+// 因此一个文件中的多个错误都能被报告, 并且编译器不会崩溃
+
+// 每个例子中只有一个错误, 检查其信息与位置(以及附带的说明)
+fn check_error(src: &str, expected: &[&str]) {
+    let stderr = errors(src, &["-Wno-unused", "-Wno-dead-store"]);
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":1,"#), "{}", stderr);
+}
+
+#[test]
+fn assignment_targets() {
+    // 对数组整体(包括多维数组的一行)赋值曾经在生成Koopa IR时崩溃
+    check_error("int main() {\n    int a[3];\n    a = 1;\n    return 0;\n}\n", &[
+        "test.c:3:5: error: cannot assign to an array of type int[3]",
+    ]);
+    check_error("int main() {\n    int a[3][2];\n    a[1] = 1;\n    return a[1][0];\n}\n", &[
+        "test.c:3:5: error: cannot assign to an array of type int[2]",
+    ]);
+    check_error("int f(int p[]) {\n    p = 0;\n    return 0;\n}\nint main() { return 0; }\n", &[
+        "test.c:2:5: error: cannot assign to an array of type int[]",
+    ]);
+    check_error("const int k = 3;\nint main() {\n    k = 2;\n    return k;\n}\n", &[
+        "test.c:3:5: error: cannot assign to constant `k`",
+    ]);
+    check_error("const int c[2] = {1, 2};\nint main() {\n    c[1] = 2;\n    return 0;\n}\n", &[
+        "test.c:3:5: error: cannot assign to constant `c`",
+    ]);
+}
+
+#[test]
+fn call_arguments() {
+    let funcs = "int f(int x, int y) { return x + y; }\nint g(int a[][2]) { return a[0][1]; }\n";
+    let cases: [(&str, &[&str]); 5] = [
+        // 以数组作为int参数、以int作为数组参数曾经在生成Koopa IR时崩溃
+        ("int a[3];\n    return f(a, 1);", &[
+            "test.c:5:12: error: argument 1 of `f` has type int[], expected int",
+            "test.c:1:5: note: `f` declared here",
+        ]),
+        ("return g(3);", &["test.c:4:12: error: argument 1 of `g` has type int, expected int[][2]"]),
+        ("int a[3];\n    return g(a);", &["test.c:5:12: error: argument 1 of `g` has type int[], expected int[][2]"]),
+        ("return f(3);", &[
+            "test.c:4:12: error: too few arguments to function `f` (expected 2, found 1)",
+            "test.c:1:5: note: `f` declared here",
+        ]),
+        ("return f(1, 2, 3) + getint(1);", &[
+            "test.c:4:12: error: too many arguments to function `f` (expected 2, found 3)",
+            "test.c:4:25: error: too many arguments to function `getint` (expected 0, found 1)",
+        ]),
+    ];
+    for (body, expected) in cases {
+        let src = format!("{}int main() {{\n    {}\n}}\n", funcs, body);
+        let stderr = errors(&src, &["-Wno-unused"]);
+        for expected in expected {
+            assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+        }
+    }
+    // 多维数组的一行可以作为一维数组参数
+    check_error(
+        "int h(int a[]) { return a[0]; }\nint main() {\n    int b[2][3];\n    b[1][0] = 7;\n    return h(b[1]) + h(b);\n}\n",
+        &["test.c:5:22: error: argument 1 of `h` has type int[][3], expected int[]"]
+    );
+}
+
+#[test]
+fn values_and_returns() {
+    check_error("void v() {}\nint main() {\n    return v() + 1;\n}\n", &[
+        "test.c:3:12: error: result of void function `v` is used as a value",
+    ]);
+    check_error("void r() {\n    return 1;\n}\nint main() { return 0; }\n", &[
+        "test.c:2:5: error: void function `r` should not return a value",
+    ]);
+    check_error("int s() {\n    return;\n}\nint main() { return s(); }\n", &[
+        "test.c:2:5: error: non-void function `s` should return a value",
+    ]);
+    check_error("int main() {\n    int n = 1;\n    return n[0];\n}\n", &[
+        "test.c:3:12: error: subscripted value `n` is not an array",
+    ]);
+    check_error("int main() {\n    int a[2][2];\n    return a[0][1][1];\n}\n", &[
+        "test.c:3:12: error: subscripted value `a` is not an array",
+    ]);
+    // 数组只能作为函数的实参, 不能作为int值使用
+    let cases = [
+        ("int x = a;", "test.c:3:13: error: expected a value of type int, found int[]"),
+        ("if (a) return 1;", "test.c:3:9: error: expected a value of type int, found int[]"),
+        ("return -a;", "test.c:3:13: error: expected a value of type int, found int[]"),
+        ("return a[0] > a;", "test.c:3:19: error: expected a value of type int, found int[]"),
+        ("return a[1] || a;", "test.c:3:20: error: expected a value of type int, found int[]"),
+        ("return b[a][0];", "test.c:3:14: error: expected a value of type int, found int[]"),
+        ("return b[1];", "test.c:3:12: error: expected a value of type int, found int[]"),
+    ];
+    for (stmt, expected) in cases {
+        let src = format!("int main() {{\n    int a[3], b[2][3];\n    {}\n    return 0;\n}}\n", stmt);
+        check_error(&src, &[expected]);
+    }
+}
+
+#[test]
+fn names_and_declarations() {
+    check_error("int main() {\n    return undefined + 1;\n}\n", &["test.c:2:12: error: use of undeclared identifier `undefined`"]);
+    check_error("int main() {\n    missing = 1;\n    return 0;\n}\n", &["test.c:2:5: error: use of undeclared identifier `missing`"]);
+    check_error("int main() {\n    return h(1);\n}\n", &["test.c:2:12: error: call to undeclared function `h`"]);
+    check_error("int main() {\n    int x = 1;\n    int x = 2;\n    return x;\n}\n", &[
+        "test.c:3:9: error: redefinition of `x`",
+        "test.c:2:9: note: previous declaration of `x` is here",
+    ]);
+    check_error("int g = 1;\nint g = 2;\nint main() { return g; }\n", &[
+        "test.c:2:5: error: redefinition of `g`",
+        "test.c:1:5: note: previous declaration of `g` is here",
+    ]);
+    check_error("extern int a[3];\nint a[4];\nint main() { return a[0]; }\n", &[
+        "test.c:2:5: error: conflicting types for `a`",
+        "test.c:1:12: note: previous declaration of `a` is here",
+    ]);
+    check_error("int f(int a);\nint f(int a, int b) { return a; }\nint main() { return 0; }\n", &[
+        "test.c:2:5: error: conflicting types for `f`",
+    ]);
+    check_error("int f() { return 0; }\nint f() { return 1; }\nint main() { return f(); }\n", &[
+        "test.c:2:5: error: redefinition of `f`",
+        "test.c:1:5: note: previous declaration of `f` is here",
+    ]);
+    check_error("int getint() { return 1; }\nint main() { return getint(); }\n", &["test.c:1:5: error: redefinition of `getint`"]);
+    check_error("extern const int c = 1;\nint main() { return c; }\n", &["test.c:1:18: error: const declaration cannot be `extern`"]);
+    check_error("int main() {\n    if (1) break;\n    return 0;\n}\n", &["test.c:2:12: error: `break` statement not in a loop"]);
+    check_error("int main() {\n    continue;\n}\n", &["test.c:2:5: error: `continue` statement not in a loop"]);
+}
+
+#[test]
+fn errors_are_recovered() {
+    let src = r#"const int k = 3;
+void v() {}
+int main() {
+    int a[3];
+    a = v();
+    k = undefined;
+    while (a[k] > a) a[0] = f(a[1], 2);
+    break;
+    return a;
+}
+"#;
+    let stderr = errors(src, &["-Wno-unused", "-Wno-dead-store"]);
+    let expected = [
+        "test.c:5:5: error: cannot assign to an array of type int[3]",
+        "test.c:5:9: error: result of void function `v` is used as a value",
+        "test.c:6:5: error: cannot assign to constant `k`",
+        "test.c:6:9: error: use of undeclared identifier `undefined`",
+        "test.c:7:29: error: call to undeclared function `f`",
+        "test.c:7:19: error: expected a value of type int, found int[]",
+        "test.c:8:5: error: `break` statement not in a loop",
+        "test.c:9:12: error: expected a value of type int, found int[]",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    // 用于恢复的HIR不会引起更多的错误
+    assert!(stderr.contains(r#"{"errors":8,"#), "{}", stderr);
+}
@@ -0,0 +1,93 @@
+use crate::{ check, errors };
+
+// 初始化列表按SysY的规则对齐: 全局、局部、static局部与常量数组共用同一套规则;
+// 形状有误的初始化列表与非法的数组长度给出指向出错元素的错误
+
+#[test]
+fn brace_elision() {
+    let src = r#"
+int gb[4][2] = {1, 2, {3}, {5}, 7, 8};
+int gc[2][3][4] = {1, 2, 3, 4, {5}, {6}, {7, 8}};
+
+void show(int a[][3][4]) {
+    int i = 0;
+    while (i < 24) {
+        putint(a[i / 12][i / 4 % 3][i % 4]);
+        i = i + 1;
+    }
+    putch(10);
+}
+
+void show2(int a[][2]) {
+    int i = 0;
+    while (i < 8) {
+        putint(a[i / 2][i % 2]);
+        i = i + 1;
+    }
+    putch(10);
+}
+
+int main() {
+    int x = getint();
+    int lb[4][2] = {x, 2, {3}, {5}, 7, x + 7};
+    int lc[2][3][4] = {1, 2, 3, 4, {5}, {6}, {7, x * 8}};
+    static int sc[2][3][4] = {{1, 2, 3, 4, {5}, {6}}, {7}};
+    const int cb[4][2] = {{1}, 2, 3, {4, 5}};
+    show2(gb);
+    show2(lb);
+    show2(cb);
+    show(gc);
+    show(lc);
+    show(sc);
+    return cb[1][1] + cb[2][0];
+}
+"#;
+    let output = check(src, "1", &[]);
+    let expected = [
+        "12305078",
+        "12305078",
+        "10234500",
+        "123450006000780000000000",
+        "123450006000780000000000",
+        "123450006000700000000000",
+    ];
+    assert_eq!(output.stdout, expected.join("\n") + "\n");
+    assert_eq!(output.exit_code, 7);
+}
+
+#[test]
+fn initializer_errors() {
+    let src = r#"
+int a[2] = {1, 2, 3};
+int m[2][2] = {1, {2}};
+int n = {1};
+int o[2] = 1;
+int p[2][2] = {{{1}}};
+const int q[2] = {1, 2, {3}};
+int d[0];
+int e[3][1 / 0];
+int f[2 - 3];
+
+int main() {
+    int l[2][2] = {1, 2, 3, {4}};
+    return a[0] + m[0][0] + n + o[0] + p[0][0] + q[0] + d[0] + e[0][0] + f[0] + l[0][0];
+}
+"#;
+    let stderr = errors(src, &[]);
+    let expected = [
+        "2:19: error: excess elements in array initializer",
+        "3:19: error: braced initializer is not aligned to a sub-array boundary",
+        "4:9: error: braces around scalar initializer",
+        "5:12: error: array must be initialized with a brace-enclosed initializer list",
+        "6:17: error: braces around scalar initializer",
+        "7:25: error: excess elements in array initializer",
+        "8:7: error: size of array dimension must be positive, found 0",
+        "9:10: error: division by zero in constant expression",
+        "10:7: error: size of array dimension must be positive, found -1",
+        "13:29: error: braced initializer is not aligned to a sub-array boundary",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(&format!("test.c:{}", expected)), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains("{\"errors\":10,"), "{}", stderr);
+}
@@ -0,0 +1,58 @@
+use crate::check;
+
+// 函数内联: 多个ret汇合到合并块、void函数、局部数组在循环中的每次调用重新初始化、嵌套调用与递归
+
+const PROGRAM: &str = r#"
+int g = 0;
+
+int clamp(int x, int lo, int hi) {
+    if (x < lo) return lo;
+    if (x > hi) return hi;
+    return x;
+}
+
+void record(int x) {
+    if (x == 0) return;
+    g = g * 3 + x;
+}
+
+int sum3(int a, int b, int c) {
+    int buf[3] = {a, b};
+    buf[2] = c;
+    return buf[0] + buf[1] + buf[2];
+}
+
+int twice(int x) {
+    return sum3(clamp(x, 0, 10), clamp(x, 0, 10), 0);
+}
+
+int fact(int n) {
+    if (n <= 1) return 1;
+    return n * fact(n - 1);
+}
+
+int main() {
+    int i = -3;
+    while (i < 15) {
+        record(twice(i));
+        i = i + 4;
+    }
+    putint(g);
+    putch(32);
+    putint(fact(clamp(getint(), 0, 10)));
+    return sum3(1, 2, 3);
+}
+"#;
+
+#[test]
+fn inlined_calls() {
+    let passes = [
+        "--passes=inline,verify",
+        "--passes=mem2reg,inline,verify,sccp,dce,simplify-cfg,verify",
+        "-O2 --inline-threshold=0",
+        "-O2 --inline-threshold=1000",
+    ];
+    let output = check(PROGRAM, "5", &passes);
+    assert_eq!((output.stdout.as_str(), output.exit_code), ("218 120", 6));
+    check(PROGRAM, "-4", &passes);
+}
@@ -0,0 +1,60 @@
+use crate::check;
+
+// 循环不变量外提: 被条件保护的除法不能提前到前置块执行, 不会执行的循环体同样如此
+
+#[test]
+fn guarded_division() {
+    let src = r#"
+int main() {
+    int i = 0, j = getint(), s = 0, d = getint();
+    while (i < j) {
+        if (d != 0) s = s + 100 / d + 7 % d;
+        i = i + 1;
+    }
+    putint(s);
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,licm,verify", "--passes=mem2reg,gvn,licm,dce,verify"];
+    assert_eq!(check(src, "5 0", &passes).stdout, "0");
+    assert_eq!(check(src, "5 3", &passes).stdout, "170");
+}
+
+#[test]
+fn division_in_zero_trip_loop() {
+    let src = r#"
+int main() {
+    int n = getint(), d = getint(), i = 0, s = 0;
+    while (i < n) {
+        s = s + 10 / d;
+        i = i + 1;
+    }
+    while (1) {
+        if (d != 0) s = s + 1000 / d;
+        if (s >= 0) break;
+    }
+    putint(s);
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,licm,verify"];
+    assert_eq!(check(src, "0 0", &passes).stdout, "0");
+    assert_eq!(check(src, "3 5", &passes).stdout, "206");
+}
+
+#[test]
+fn constant_divisor() {
+    let src = r#"
+int g[4] = {10, 20, 30, 40};
+int main() {
+    int i = 0, s = 0, k = getint();
+    while (i < 8) {
+        if (i > 2) s = s + k / 3 + k % 7 + g[k % 4];
+        i = i + 1;
+    }
+    putint(s);
+    return 0;
+}
+"#;
+    assert_eq!(check(src, "10", &["--passes=mem2reg,licm,verify"]).stdout, "180");
+}
@@ -0,0 +1,63 @@
+use crate::{ compile_files, errors, warnings };
+
+// 检查级别: -Wno-name关闭, -Werror与-Werror=name升级为错误, 后面的选项覆盖前面的;
+// #pragma lint只影响所在的文件; 编译结束时输出一行JSON格式的统计
+
+const SRC: &str = r#"int main() {
+    int a;
+    int b = 1;
+    return a;
+    b = 2;
+}
+"#;
+
+#[test]
+fn command_line_levels() {
+    let stderr = warnings(SRC, &[]);
+    assert!(stderr.contains("test.c:3:9: warning: value stored to `b` is never read [-Wdead-store]"), "{}", stderr);
+    assert!(stderr.contains("test.c:4:12: warning: `a` may be used uninitialized [-Wuninit]"), "{}", stderr);
+    assert!(stderr.contains("test.c:5:5: warning: unreachable code [-Wunreachable]"), "{}", stderr);
+    assert!(
+        stderr.ends_with("{\"errors\":0,\"warnings\":3,\"lints\":{\"dead-store\":1,\"uninit\":1,\"unreachable\":1}}\n"),
+        "{}",
+        stderr
+    );
+
+    let stderr = warnings(SRC, &["-Wno-uninit", "-Wno-unreachable"]);
+    assert!(!stderr.contains("[-Wuninit]") && !stderr.contains("[-Wunreachable]"), "{}", stderr);
+    assert!(stderr.contains(r#"{"errors":0,"warnings":1,"lints":{"dead-store":1}}"#), "{}", stderr);
+    // 没有任何诊断时不输出统计
+    assert_eq!(warnings(SRC, &["-Wno-uninit", "-Wno-unreachable", "-Wno-dead-store"]), "");
+
+    let stderr = errors(SRC, &["-Werror=uninit"]);
+    assert!(stderr.contains("test.c:4:12: error: `a` may be used uninitialized [-Wuninit]"), "{}", stderr);
+    assert!(stderr.contains("test.c:3:9: warning: value stored to `b`"), "{}", stderr);
+    assert!(stderr.contains(r#"{"errors":1,"warnings":2,"#), "{}", stderr);
+
+    let stderr = errors(SRC, &["-Werror", "-Wno-uninit", "-Wno-unreachable"]);
+    assert!(stderr.contains("test.c:3:9: error: value stored to `b` is never read [-Wdead-store]"), "{}", stderr);
+    assert!(stderr.contains(r#"{"errors":1,"warnings":0,"lints":{"dead-store":1}}"#), "{}", stderr);
+    assert!(warnings(SRC, &["-Werror=uninit", "-Wuninit"]).contains("warning: `a` may be used uninitialized"));
+
+    assert!(errors(SRC, &["-Wfoo"]).contains("error: unknown lint `foo`"));
+    assert!(errors(SRC, &["-Werror=bar"]).contains("error: unknown lint `bar`"));
+}
+
+#[test]
+fn pragma_lint() {
+    let quiet = format!("#pragma lint -Wno-uninit -Werror=unreachable\n{}", SRC);
+    let result = compile_files(&[("quiet.c", &quiet), ("loud.c", SRC)], &["-koopa", "-c", "quiet.c", "loud.c"]);
+    assert!(!result.success);
+    assert!(result.stderr.contains("quiet.c:6:5: error: unreachable code [-Wunreachable]"), "{}", result.stderr);
+    assert!(!result.stderr.contains("quiet.c:5:12"), "{}", result.stderr);
+    assert!(result.stderr.contains("loud.c:4:12: warning: `a` may be used uninitialized"), "{}", result.stderr);
+    assert!(result.stderr.contains("loud.c:5:5: warning: unreachable code"), "{}", result.stderr);
+    assert!(result.stderr.contains(r#"{"errors":1,"warnings":4,"#), "{}", result.stderr);
+
+    // #pragma在命令行选项之后生效
+    let flags = ["-Werror=dead-store", "-Wno-uninit", "-Wno-unreachable"];
+    assert!(errors(SRC, &flags).contains("error: value stored to `b`"));
+    assert_eq!(warnings(&format!("#pragma lint -Wno-dead-store\n{}", SRC), &flags), "");
+    let src = format!("#pragma lint -Wbogus\n{}", SRC);
+    assert!(errors(&src, &[]).contains("test.c: error: unknown lint `bogus` in #pragma lint"));
+}
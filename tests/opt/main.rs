@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+mod array_bounds;
+mod assign;
+mod dce;
+mod gvn;
+mod hir;
+mod init;
+mod inline;
+mod licm;
+mod lint_levels;
+mod missing_return;
+mod mem2reg;
+mod pipeline;
+mod preprocess;
+mod riscv;
+mod sccp;
+mod separate;
+mod shadowing;
+mod simplify_cfg;
+mod stack_report;
+mod static_locals;
+mod tail_call;
+mod tail_recursion;
+mod traps;
+mod uninit;
+mod unreachable;
+mod unroll;
+mod unused;
+
+// 端到端测试: 同一段SysY程序分别以-O0和开启优化的选项编译为RISC-V汇编,
+// 在解释器中运行并比较输出与返回值。-O2常常会把问题掩盖掉(例如常量传播后条件不再成立),
+// 因此每个测试还会单独运行被测的pass。前端的测试同样基于这里的编译函数, 检查诊断信息与生成的文件
+
+/// Result of one compiler invocation. The temporary directory holding
+/// the sources and outputs is removed on drop.
+pub struct Compilation {
+    pub success: bool,
+    pub stderr: String,
+    dir: PathBuf,
+}
+
+impl Compilation {
+    // 读取编译器在临时目录中生成的文件
+    pub fn read(&self, name: &str) -> String {
+        std::fs::read_to_string(self.dir.join(name))
+            .unwrap_or_else(|err| panic!("cannot read `{}`: {}\n{}", name, err, self.stderr))
+    }
+}
+
+impl Drop for Compilation {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Writes `files` (name and content) into a fresh temporary directory and
+/// runs the compiler there with `args`, which refer to the files by name.
+pub fn compile_files(files: &[(&str, &str)], args: &[&str]) -> Compilation {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "sysy-test-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, content) in files {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+    let result = Command::new(env!("CARGO_BIN_EXE_sysy-compiler"))
+        .args(args)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    Compilation {
+        success: result.status.success(),
+        stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+        dir,
+    }
+}
+
+// 把src作为test.c编译为test.S, flags在文件名之前
+fn compile_test(src: &str, flags: &[&str]) -> Compilation {
+    let args: Vec<&str> = ["-riscv"]
+        .iter()
+        .chain(flags)
+        .chain(&["test.c", "-o", "test.S"])
+        .copied()
+        .collect();
+    compile_files(&[("test.c", src)], &args)
+}
+
+// 编译src, 返回生成的汇编
+pub fn compile(src: &str, flags: &[&str]) -> String {
+    let result = compile_test(src, flags);
+    assert!(result.success, "compiling with {:?} failed:\n{}", flags, result.stderr);
+    result.read("test.S")
+}
+
+// 汇编中是否导出了name, 函数与变量分别使用.global与.globl
+pub fn exported(asm: &str, name: &str) -> bool {
+    [".global", ".globl"]
+        .iter()
+        .any(|directive| asm.contains(&format!("{} {}\n", directive, name)))
+}
+
+/// Compiles `src` with `flags`, expecting success, and returns the
+/// diagnostics printed on stderr.
+pub fn warnings(src: &str, flags: &[&str]) -> String {
+    let result = compile_test(src, flags);
+    assert!(result.success, "compiling with {:?} failed:\n{}", flags, result.stderr);
+    result.stderr.clone()
+}
+
+/// Compiles `src` with `flags`, expecting it to be rejected with
+/// diagnostics (not a crash), and returns what was printed on stderr.
+pub fn errors(src: &str, flags: &[&str]) -> String {
+    let result = compile_test(src, flags);
+    assert!(!result.success, "compiling with {:?} unexpectedly succeeded:\n{}", flags, result.stderr);
+    assert!(!result.stderr.contains("panicked"), "compiling with {:?} crashed:\n{}", flags, result.stderr);
+    result.stderr.clone()
+}
+
+/// Runs `src` compiled at -O0, -O1, -O2 and with each of `flag_sets`
+/// (whitespace-separated compiler flags), asserting that every build
+/// produces the same output and exit code. Returns the -O0 output.
+pub fn check(src: &str, input: &str, flag_sets: &[&str]) -> riscv::Output {
+    let expected = riscv::run(&compile(src, &["-O0"]), input).unwrap_or_else(|err| panic!("-O0 failed: {}", err));
+    for flags in ["-O1", "-O2"].iter().chain(flag_sets) {
+        let flags: Vec<&str> = flags.split_whitespace().collect();
+        let actual = riscv::run(&compile(src, &flags), input);
+        assert_eq!(actual.as_ref(), Ok(&expected), "output with {:?} differs from -O0", flags);
+    }
+    expected
+}
@@ -0,0 +1,53 @@
+use crate::check;
+
+// 提升为SSA值: 汇合点的参数、break产生的循环出口汇合、参数与短路求值的临时变量
+
+#[test]
+fn loop_with_break() {
+    let src = r#"
+int main() {
+    int i = 0, s = 0, n = getint();
+    while (i < n) {
+        i = i + 1;
+        if (i % 3 == 0) continue;
+        if (s > 40) break;
+        s = s + i;
+    }
+    putint(s);
+    putch(32);
+    putint(i);
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,verify", "--passes=mem2reg,dce,simplify-cfg,verify"];
+    assert_eq!(check(src, "100", &passes).stdout, "48 13");
+    assert_eq!(check(src, "0", &passes).stdout, "0 0");
+}
+
+#[test]
+fn branches_and_short_circuit() {
+    let src = r#"
+int f(int a, int b) {
+    int x;
+    if (a > b) x = a - b; else x = b - a;
+    int y = a;
+    while (y > 0 && (y % 5 != 0 || x == 0)) {
+        a = a + y;
+        y = y - 1;
+    }
+    if (x || y) b = b * 2;
+    return a + b + x + y;
+}
+
+int main() {
+    putint(f(7, 3));
+    putch(32);
+    putint(f(3, 3));
+    putch(32);
+    putint(f(0, 9));
+    return 0;
+}
+"#;
+    let output = check(src, "", &["--passes=mem2reg,verify", "--passes=mem2reg,sccp,dce,verify"]);
+    assert_eq!(output.stdout, "35 12 27");
+}
@@ -0,0 +1,59 @@
+use crate::{ check, errors, warnings };
+
+// int函数存在不返回的路径时给出警告并补上ret 0; --missing-return=error时作为错误, main函数除外
+
+const SRC: &str = r#"
+int partial(int x) {
+    if (x > 0) return x * 2;
+}
+
+int nested(int x) {
+    while (x < 10) {
+        if (x == 5) return 50;
+        x = x + 1;
+    }
+}
+
+int complete(int x) {
+    if (x > 0) {
+        return 1;
+    } else {
+        return 2;
+    }
+}
+
+void nothing() {}
+
+int main() {
+    nothing();
+    putint(partial(3) + partial(-3) + complete(1));
+    putch(32);
+    putint(nested(getint()));
+}
+"#;
+
+#[test]
+fn implicit_return_zero() {
+    assert_eq!(check(SRC, "2", &[]).stdout, "7 50");
+    assert_eq!(check(SRC, "7", &[]).stdout, "7 0");
+}
+
+#[test]
+fn missing_return_diagnostics() {
+    let stderr = warnings(SRC, &[]);
+    let expected = [
+        "test.c:2:5: warning: control reaches end of non-void function `partial` without returning a value [-Wimplicit-return]",
+        "test.c:6:5: warning: control reaches end of non-void function `nested` without returning a value [-Wimplicit-return]",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(!stderr.contains("`complete`") && !stderr.contains("`main`"), "{}", stderr);
+
+    for flag in ["--missing-return=error", "-Werror=implicit-return"] {
+        let stderr = errors(SRC, &[flag]);
+        assert!(stderr.contains("test.c:2:5: error: control reaches end of non-void function `partial`"), "{}", stderr);
+        assert!(stderr.contains("\"errors\":2,"), "{}", stderr);
+    }
+    assert_eq!(warnings(SRC, &["--missing-return=error", "--missing-return=warn"]), stderr);
+}
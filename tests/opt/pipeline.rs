@@ -0,0 +1,56 @@
+use crate::check;
+
+// 各优化级别与--passes指定的pass序列, 每个pass之后都检查IR
+
+const PROGRAM: &str = r#"
+int n = 6;
+int a[10] = {5, 3, 8, 1, 9, 2};
+
+void sort(int arr[], int len) {
+    int i = 0;
+    while (i < len) {
+        int j = i + 1;
+        while (j < len) {
+            if (arr[j] < arr[i]) {
+                int t = arr[i];
+                arr[i] = arr[j];
+                arr[j] = t;
+            }
+            j = j + 1;
+        }
+        i = i + 1;
+    }
+}
+
+int fib(int x) {
+    if (x < 2) return x;
+    return fib(x - 1) + fib(x - 2);
+}
+
+int main() {
+    int k = getint();
+    a[n] = k;
+    n = n + 1;
+    sort(a, n);
+    putarray(n, a);
+    putint(fib(k));
+    putch(10);
+    return a[0] + k;
+}
+"#;
+
+#[test]
+fn optimization_levels() {
+    let output = check(PROGRAM, "7", &[
+        "--passes=verify",
+        "--passes=mem2reg,verify,sccp,verify,dce,verify,simplify-cfg,verify",
+        "-O2 --stats --time-passes",
+    ]);
+    assert_eq!(output.stdout, "7: 1 2 3 5 7 8 9\n13\n");
+    assert_eq!(output.exit_code, 8);
+}
+
+#[test]
+fn repeated_passes() {
+    check(PROGRAM, "12", &["--passes=mem2reg,sccp,gvn,licm,sccp,gvn,dce,simplify-cfg,dce,simplify-cfg,verify"]);
+}
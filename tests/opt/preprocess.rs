@@ -0,0 +1,79 @@
+use crate::{ compile_files, errors, riscv, warnings };
+
+// 预处理: #include、对象式与函数式宏(包括实参跨越多行的调用与##)、条件编译、__LINE__,
+// 以及诊断信息中的位置仍然指向原始文件的行
+
+const HEADER: &str = r#"#ifndef DEFS_H
+#define DEFS_H
+#define N 5
+#define SQUARE(x) ((x) * (x))
+#define ADD3(a, b, c) ((a) + (b) + (c))
+static int unused_in_header;
+#endif
+"#;
+
+const MAIN: &str = r#"#include "defs.h"
+#include "defs.h"
+#define CAT(a, b) a ## b
+#if N > 3 && defined(SQUARE)
+int big = 1;
+#elif N > 1
+int big = 2;
+#else
+int big = 3;
+#endif
+#ifdef UNDEFINED
+int missing(;
+#endif
+int main() {
+    int CAT(val, ue) = ADD3(SQUARE(N),
+                            N, /* ) */
+                            1);
+    starttime();
+    putint(value);
+    putch(32);
+    putint(__LINE__);
+    putch(32);
+    putint(big);
+    stoptime();
+    int unused_in_main;
+    return 0;
+}
+"#;
+
+#[test]
+fn includes_macros_and_conditions() {
+    let result = compile_files(&[("defs.h", HEADER), ("main.c", MAIN)], &["-riscv", "main.c", "-o", "main.S"]);
+    assert!(result.success, "{}", result.stderr);
+    let output = riscv::run(&result.read("main.S"), "").unwrap();
+    assert_eq!(output.stdout, "31 21 1");
+    // 头文件中的位置指向头文件, 跨行的宏调用之后的行号不变
+    assert!(result.stderr.contains("defs.h:6:12: warning: unused variable `unused_in_header`"), "{}", result.stderr);
+    assert!(result.stderr.contains("main.c:25:9: warning: unused variable `unused_in_main`"), "{}", result.stderr);
+}
+
+#[test]
+fn comment_markers_in_literals() {
+    let src = "int main() {\n#error \"a // b\" '/*' \"say \\\"/*\\\"\" end\n    return 0;\n}\n";
+    let stderr = errors(src, &[]);
+    assert!(stderr.contains(r#"test.c:2: error: #error "a // b" '/*' "say \"/*\"" end"#), "{}", stderr);
+    let src = "int main() { /* \"unterminated\n*/ return 0; } // \"\nint unused; /* ' */\n";
+    assert!(warnings(src, &[]).contains("test.c:3:5: warning: unused variable `unused`"));
+}
+
+#[test]
+fn preprocessor_errors() {
+    let cases = [
+        ("#if 1\nint main() { return 0; }\n", "test.c:1: error: unterminated conditional directive"),
+        ("#endif\n", "test.c:1: error: #endif without #if"),
+        ("\n#include \"missing.h\"\n", "test.c:2: error: cannot find include file \"missing.h\""),
+        ("#define F(a, b) a\nint x = F(1);\n", "test.c:2: error: wrong number of arguments for macro `F`"),
+        ("#define F(a) a\nint x = F(1,\n;\n", "test.c:2: error: unterminated argument list invoking macro `F`"),
+        ("#if 1 +\n#endif\n", "test.c:1: error: invalid #if expression"),
+        ("#foo\n", "test.c:1: error: invalid directive #foo"),
+    ];
+    for (src, expected) in cases {
+        let stderr = errors(src, &[]);
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+// 运行编译器生成的RISC-V汇编的解释器, 只支持asmgen会生成的指令与SysY的库函数。
+// 除零和INT_MIN / -1在SysY中是未定义行为, 这里作为错误报告(硬件上不会出错),
+// 这样把除法提前到判断之外执行的优化能被测试发现; 读取未写过的内存与栈溢出同样是错误
+
+#[derive(Debug, PartialEq)]
+pub struct Output {
+    pub stdout: String,
+    pub exit_code: i32,
+}
+
+const DATA_BASE: u32 = 0x1000_0000;
+const STACK_TOP: u32 = 0x7ff0_0000;
+// 栈的大小, 足够测试程序使用, 又能发现本应是尾调用的深层调用
+const STACK_SIZE: u32 = 64 * 1024;
+// 从main返回时跳转到的地址
+const EXIT: i32 = -1;
+const MAX_STEPS: usize = 50_000_000;
+
+struct Inst {
+    op: String,
+    args: Vec<String>,
+}
+
+struct Machine<'a> {
+    regs: HashMap<String, i32>,
+    mem: HashMap<u32, i32>,
+    // 全局变量所在区域的结束地址
+    data_end: u32,
+    // 尚未读取的输入, getint按空白分隔读取整数, getch逐个读取字符
+    input: &'a str,
+    stdout: String,
+}
+
+pub fn run(asm: &str, input: &str) -> Result<Output, String> {
+    let mut insts = vec![];
+    let mut labels = HashMap::new();
+    let mut data_labels = HashMap::new();
+    let mut mem = HashMap::new();
+    let mut data_addr = DATA_BASE;
+    let mut in_data = false;
+    for line in asm.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(label) = line.strip_suffix(':') {
+            if in_data {
+                data_labels.insert(label.to_owned(), data_addr);
+            } else {
+                labels.insert(label.to_owned(), insts.len());
+            }
+            continue;
+        }
+        let (op, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args: Vec<String> = rest.split(',').map(|arg| arg.trim().to_owned()).filter(|arg| !arg.is_empty()).collect();
+        match op {
+            ".data" => in_data = true,
+            ".text" => in_data = false,
+            ".word" => {
+                mem.insert(data_addr, parse_imm(&args[0])?);
+                data_addr += 4;
+            }
+            ".zero" => {
+                let size: u32 = args[0].parse().map_err(|_| format!("invalid .zero `{}`", line))?;
+                for offset in (0..size).step_by(4) {
+                    mem.insert(data_addr + offset, 0);
+                }
+                data_addr += size;
+            }
+            _ if op.starts_with('.') => {}
+            _ => insts.push(Inst { op: op.to_owned(), args }),
+        }
+    }
+
+    let mut machine = Machine {
+        regs: HashMap::new(),
+        mem,
+        data_end: data_addr,
+        input,
+        stdout: String::new(),
+    };
+    machine.write("sp", STACK_TOP as i32);
+    machine.write("ra", EXIT);
+    let label = |name: &str| labels.get(name).copied().ok_or_else(|| format!("undefined label `{}`", name));
+    let mut pc = label("main")?;
+    for _ in 0..MAX_STEPS {
+        if pc == EXIT as usize {
+            let exit_code = machine.read("a0")? & 0xff;
+            return Ok(Output { stdout: machine.stdout, exit_code });
+        }
+        let Inst { op, args } = insts.get(pc).ok_or_else(|| format!("pc {} out of range", pc))?;
+        pc += 1;
+        let arg = |i: usize| args[i].as_str();
+        match op.as_str() {
+            "li" => machine.write(arg(0), parse_imm(arg(1))?),
+            "la" => {
+                let addr = data_labels.get(arg(1)).ok_or_else(|| format!("undefined symbol `{}`", arg(1)))?;
+                machine.write(arg(0), *addr as i32);
+            }
+            "mv" => {
+                let value = machine.read(arg(1))?;
+                machine.write(arg(0), value);
+            }
+            "lw" => {
+                let addr = machine.address(arg(1))?;
+                let value = *machine.mem.get(&addr).ok_or_else(|| format!("load from uninitialized {:#x}", addr))?;
+                machine.write(arg(0), value);
+            }
+            "sw" => {
+                let addr = machine.address(arg(1))?;
+                let value = machine.read(arg(0))?;
+                machine.mem.insert(addr, value);
+            }
+            "addi" | "xori" | "andi" | "ori" | "slti" => {
+                let (lhs, rhs) = (machine.read(arg(1))?, parse_imm(arg(2))?);
+                machine.write(arg(0), compute(&op[..op.len() - 1], lhs, rhs)?);
+            }
+            "add" | "sub" | "mul" | "div" | "rem" | "and" | "or" | "xor" | "slt" | "sgt" => {
+                let (lhs, rhs) = (machine.read(arg(1))?, machine.read(arg(2))?);
+                machine.write(arg(0), compute(op, lhs, rhs)?);
+            }
+            "seqz" | "snez" => {
+                let value = machine.read(arg(1))?;
+                machine.write(arg(0), ((value == 0) == (op == "seqz")) as i32);
+            }
+            "neg" => {
+                let value = machine.read(arg(1))?;
+                machine.write(arg(0), value.wrapping_neg());
+            }
+            "j" => pc = label(arg(0))?,
+            "beqz" | "bnez" => {
+                if (machine.read(arg(0))? == 0) == (op == "beqz") {
+                    pc = label(arg(1))?;
+                }
+            }
+            "call" => {
+                if !machine.library_call(arg(0))? {
+                    machine.write("ra", pc as i32);
+                    pc = label(arg(0))?;
+                }
+            }
+            "tail" => {
+                if machine.library_call(arg(0))? {
+                    pc = machine.read("ra")? as usize;
+                } else {
+                    pc = label(arg(0))?;
+                }
+            }
+            "ret" => pc = machine.read("ra")? as usize,
+            // --hardened生成的陷阱函数
+            "unimp" => return Err("trap".to_owned()),
+            _ => return Err(format!("unsupported instruction `{}`", op)),
+        }
+    }
+    Err("step limit exceeded".to_owned())
+}
+
+fn parse_imm(imm: &str) -> Result<i32, String> {
+    imm.parse::<i64>().map(|imm| imm as i32).map_err(|_| format!("invalid immediate `{}`", imm))
+}
+
+fn compute(op: &str, lhs: i32, rhs: i32) -> Result<i32, String> {
+    Ok(match op {
+        "add" => lhs.wrapping_add(rhs),
+        "sub" => lhs.wrapping_sub(rhs),
+        "mul" => lhs.wrapping_mul(rhs),
+        "div" | "rem" if rhs == 0 => return Err("division by zero".to_owned()),
+        "div" | "rem" if lhs == i32::MIN && rhs == -1 => return Err("division overflow".to_owned()),
+        "div" => lhs / rhs,
+        "rem" => lhs % rhs,
+        "and" => lhs & rhs,
+        "or" => lhs | rhs,
+        "xor" => lhs ^ rhs,
+        "slt" => (lhs < rhs) as i32,
+        "sgt" => (lhs > rhs) as i32,
+        _ => return Err(format!("unsupported operation `{}`", op)),
+    })
+}
+
+impl Machine<'_> {
+    fn read(&self, reg: &str) -> Result<i32, String> {
+        match reg {
+            "zero" | "x0" => Ok(0),
+            _ => self.regs.get(reg).copied().ok_or_else(|| format!("read of unset register `{}`", reg)),
+        }
+    }
+
+    fn write(&mut self, reg: &str, value: i32) {
+        if reg != "zero" && reg != "x0" {
+            self.regs.insert(reg.to_owned(), value);
+        }
+    }
+
+    // offset(reg)形式的地址, 只允许按字对齐地访问全局变量与栈
+    fn address(&self, operand: &str) -> Result<u32, String> {
+        let (offset, reg) = operand
+            .strip_suffix(')')
+            .and_then(|operand| operand.split_once('('))
+            .ok_or_else(|| format!("invalid address `{}`", operand))?;
+        let addr = self.read(reg)?.wrapping_add(parse_imm(offset)?) as u32;
+        if !addr.is_multiple_of(4) {
+            return Err(format!("unaligned access to {:#x}", addr));
+        }
+        if (STACK_TOP - STACK_SIZE..STACK_TOP).contains(&addr) || (DATA_BASE..self.data_end).contains(&addr) {
+            Ok(addr)
+        } else if addr < STACK_TOP && addr > self.data_end {
+            Err(format!("stack overflow at {:#x}", addr))
+        } else {
+            Err(format!("access to invalid address {:#x}", addr))
+        }
+    }
+
+    fn next_int(&mut self) -> Result<i32, String> {
+        let input = self.input.trim_start();
+        let (token, rest) = input.split_at(input.find(char::is_whitespace).unwrap_or(input.len()));
+        if token.is_empty() {
+            return Err("input exhausted".to_owned());
+        }
+        self.input = rest;
+        token.parse().map_err(|_| format!("invalid input `{}`", token))
+    }
+
+    // 输入结束时返回-1(EOF)
+    fn next_char(&mut self) -> i32 {
+        let mut chars = self.input.chars();
+        let c = chars.next().map_or(-1, |c| c as i32);
+        self.input = chars.as_str();
+        c
+    }
+
+    // SysY的库函数, 返回name是否是库函数
+    fn library_call(&mut self, name: &str) -> Result<bool, String> {
+        match name {
+            "getint" => {
+                let value = self.next_int()?;
+                self.write("a0", value);
+            }
+            "getch" => {
+                let c = self.next_char();
+                self.write("a0", c);
+            }
+            "getarray" => {
+                let (len, ptr) = (self.next_int()?, self.read("a0")? as u32);
+                for i in 0..len as u32 {
+                    let value = self.next_int()?;
+                    self.mem.insert(ptr + 4 * i, value);
+                }
+                self.write("a0", len);
+            }
+            "putint" => self.stdout += &self.read("a0")?.to_string(),
+            "putch" => self.stdout.push(self.read("a0")? as u8 as char),
+            "putarray" => {
+                let (len, ptr) = (self.read("a0")?, self.read("a1")? as u32);
+                self.stdout += &format!("{}:", len);
+                for i in 0..len as u32 {
+                    let value = self.mem.get(&(ptr + 4 * i)).copied().unwrap_or(0);
+                    self.stdout += &format!(" {}", value);
+                }
+                self.stdout.push('\n');
+            }
+            "starttime" | "stoptime" | "_sysy_starttime" | "_sysy_stoptime" => {}
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
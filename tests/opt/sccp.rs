@@ -0,0 +1,47 @@
+use crate::check;
+
+// 常量传播: 只从可达的边合并参数, 常量条件的分支折叠, 不可达分支中的除零不能被折叠出错
+
+#[test]
+fn constants_through_branches_and_loops() {
+    let src = r#"
+int main() {
+    int a = 3, b = 0, d = 0;
+    if (a > 2) b = a * 7; else b = getint();
+    if (b != 21) d = 100 / d;
+    int i = 0, k = 5;
+    while (i < 10) {
+        if (k != 5) k = k + 1;
+        i = i + 1;
+    }
+    int big = 2147483647;
+    putint(b + k + i);
+    putch(32);
+    putint(big + 1);
+    putch(32);
+    putint(-7 / 2 + -7 % 2);
+    return k;
+}
+"#;
+    let output = check(src, "", &["--passes=mem2reg,sccp,verify", "--passes=mem2reg,sccp,dce,simplify-cfg,verify"]);
+    assert_eq!(output.stdout, "36 -2147483648 -4");
+    assert_eq!(output.exit_code, 5);
+}
+
+#[test]
+fn runtime_values_stay_overdefined() {
+    let src = r#"
+int main() {
+    int x = getint(), s = 1, i = 0;
+    while (i < x) {
+        s = s * 2;
+        i = i + 1;
+    }
+    if (s == 1) putint(0); else putint(s);
+    return 0;
+}
+"#;
+    let passes = ["--passes=mem2reg,sccp,verify"];
+    assert_eq!(check(src, "0", &passes).stdout, "0");
+    assert_eq!(check(src, "4", &passes).stdout, "16");
+}
@@ -0,0 +1,97 @@
+use crate::{ compile_files, exported, riscv };
+
+// 分别编译: 多个文件通过extern变量与函数声明互相引用, static符号只在本文件可见,
+// 文件之间的重复定义与类型冲突是错误
+
+const MAIN: &str = r#"
+extern int counter;
+extern int table[4];
+int add(int a, int b);
+int twice(int x);
+
+static int helper() {
+    return 1000;
+}
+
+int main() {
+    counter = add(counter, 5);
+    table[1] = twice(counter);
+    putint(counter + table[1] + table[3]);
+    putch(32);
+    putint(helper());
+    return 0;
+}
+
+int twice(int x) {
+    return x * 2;
+}
+"#;
+
+const LIB: &str = r#"
+int counter = 2;
+int table[4] = {0, 0, 0, 40};
+
+static int helper() {
+    return 1;
+}
+
+int add(int a, int b) {
+    extern int table[4];
+    table[0] = a;
+    return a + b + helper();
+}
+"#;
+
+#[test]
+fn combined_program() {
+    let files = [("main.c", MAIN), ("lib.c", LIB)];
+    let result = compile_files(&files, &["-riscv", "main.c", "lib.c", "-o", "out.S"]);
+    assert!(result.success, "{}", result.stderr);
+    assert_eq!(riscv::run(&result.read("out.S"), "").unwrap().stdout, "64 1000");
+    let result = compile_files(&files, &["-riscv", "-O2", "lib.c", "main.c"]);
+    assert!(result.success, "{}", result.stderr);
+    assert_eq!(riscv::run(&result.read("a.S"), "").unwrap().stdout, "64 1000");
+}
+
+#[test]
+fn separate_outputs() {
+    let files = [("main.c", MAIN), ("lib.c", LIB)];
+    let result = compile_files(&files, &["-riscv", "-c", "main.c", "lib.c"]);
+    assert!(result.success, "{}", result.stderr);
+    // 其他文件中定义的变量不分配空间, 也不导出
+    let main = result.read("main.S");
+    assert!(!main.contains("counter:") && !exported(&main, "counter"), "{}", main);
+    assert!(exported(&main, "main") && exported(&main, "twice"), "{}", main);
+    let lib = result.read("lib.S");
+    assert!(lib.contains("counter:") && exported(&lib, "counter") && exported(&lib, "add"), "{}", lib);
+    assert!(!exported(&lib, "helper"), "{}", lib);
+
+    let result = compile_files(&files, &["-koopa", "-c", "main.c", "lib.c"]);
+    assert!(result.success, "{}", result.stderr);
+    assert!(result.read("main.koopa").contains("decl @add(i32, i32): i32"));
+    assert!(result.read("lib.koopa").contains("fun @add("));
+}
+
+#[test]
+fn conflicting_symbols() {
+    let cases = [
+        ("int f() { return 1; }", "int f() { return 2; }", "error: multiple definition of `f` (in a.c and b.c)"),
+        ("int g = 1;", "int g = 2;", "error: multiple definition of `g` (in a.c and b.c)"),
+        ("int f(int x);", "int f() { return 2; }", "error: conflicting types for `f` (in a.c and b.c)"),
+        ("extern int g[3];", "int g[4];", "error: conflicting types for `g` (in a.c and b.c)"),
+    ];
+    for (a, b, expected) in cases {
+        let a = format!("{}\nint main() {{ return 0; }}\n", a);
+        let result = compile_files(&[("a.c", &a), ("b.c", b)], &["-riscv", "-c", "a.c", "b.c"]);
+        assert!(!result.success && result.stderr.contains(expected), "expected `{}` in:\n{}", expected, result.stderr);
+    }
+}
+
+#[test]
+fn output_options() {
+    let files = [("main.c", MAIN), ("lib.c", LIB)];
+    let result = compile_files(&files, &["-riscv", "-c", "main.c", "lib.c", "-o", "out.S"]);
+    assert!(!result.success && result.stderr.contains("cannot specify -o with multiple output files"));
+    let result = compile_files(&files, &["-koopa", "main.c", "lib.c"]);
+    assert!(!result.success && result.stderr.contains("use -c"));
+}
@@ -0,0 +1,66 @@
+use crate::{ check, errors, warnings };
+
+// 遮蔽外层局部变量、全局变量与函数的声明给出警告并指出被遮蔽的声明;
+// 全局变量与函数共用一个命名空间, 同名是错误; 被变量遮蔽的函数不能再调用
+
+const SRC: &str = r#"int g = 10;
+int f(int x) { return x + 1; }
+int h(int g) {
+    int x = g;
+    {
+        int x = 2;
+        int f = x * 100;
+        g = g + f;
+    }
+    return x + g;
+}
+int main() {
+    return h(f(3));
+}
+"#;
+
+#[test]
+fn shadowing_warnings() {
+    let stderr = warnings(SRC, &["-Wno-unused"]);
+    let expected = [
+        "test.c:3:11: warning: declaration of `g` shadows a global declaration [-Wshadowing]\n",
+        "test.c:1:5: note: shadowed declaration is here\n",
+        "test.c:6:13: warning: declaration of `x` shadows a previous local declaration [-Wshadowing]\n",
+        "test.c:4:9: note: shadowed declaration is here\n",
+        "test.c:7:13: warning: declaration of `f` shadows a function [-Wshadowing]\n",
+        "test.c:2:5: note: shadowed declaration is here\n",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":0,"warnings":3,"lints":{"shadowing":3}}"#), "{}", stderr);
+    // 内层的声明只在所在的块中生效
+    assert_eq!(check(SRC, "", &[]).exit_code, 208);
+}
+
+#[test]
+fn name_conflicts() {
+    let cases: [(&str, &[&str]); 6] = [
+        ("int f;\nint f() { return 0; }\n", &[
+            "test.c:2:5: error: `f` redeclared as a different kind of symbol",
+            "test.c:1:5: note: previous declaration of `f` is here",
+        ]),
+        ("int f() { return 0; }\nint f[2];\n", &["test.c:2:5: error: `f` redeclared as a different kind of symbol"]),
+        ("void f();\nextern int f;\n", &["test.c:2:12: error: `f` redeclared as a different kind of symbol"]),
+        ("int g = 1;\nint f() { int g = 2; return g(); }\n", &[
+            "test.c:2:29: error: called object `g` is not a function",
+            "test.c:2:15: note: `g` declared here",
+        ]),
+        ("int g[2];\nint f() { return g(1); }\n", &["test.c:2:18: error: called object `g` is not a function"]),
+        ("int f() { return 0; }\nint h() { int f = 1; return f() + f; }\n", &[
+            "test.c:2:29: error: called object `f` is not a function",
+        ]),
+    ];
+    for (src, expected) in cases {
+        let src = format!("{}int main() {{ return 0; }}\n", src);
+        let stderr = errors(&src, &["-Wno-unused"]);
+        for expected in expected {
+            assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+        }
+    }
+}
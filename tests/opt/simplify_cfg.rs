@@ -0,0 +1,40 @@
+use crate::check;
+
+// 空块与可合并的块: 空的if分支、两个分支跳到同一个块、break/continue产生的跳转链
+
+#[test]
+fn empty_branches() {
+    let src = r#"
+int main() {
+    int x = getint(), s = 0;
+    if (x > 0) {} else {}
+    if (x > 1) { if (x > 2) {} } else { if (x < -1) {} else {} }
+    if (x == 3) s = s + 1;
+    if (x != 3) {} else s = s + 2;
+    putint(s);
+    return 0;
+}
+"#;
+    assert_eq!(check(src, "3", &["--passes=simplify-cfg,verify", "--passes=mem2reg,simplify-cfg,verify"]).stdout, "3");
+    check(src, "-5", &["--passes=simplify-cfg,verify", "--passes=mem2reg,simplify-cfg,verify"]);
+}
+
+#[test]
+fn break_and_continue() {
+    let src = r#"
+int main() {
+    int i = 0, s = 0;
+    while (i < 20) {
+        i = i + 1;
+        if (i % 3 == 0) continue;
+        if (i > 15) break;
+        while (1) { if (s > 1000) {} break; }
+        s = s + i;
+    }
+    putint(s);
+    return i;
+}
+"#;
+    let output = check(src, "", &["--passes=simplify-cfg,verify", "--passes=mem2reg,sccp,simplify-cfg,verify"]);
+    assert_eq!((output.stdout.as_str(), output.exit_code), ("75", 16));
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use crate::compile_files;
+
+// 调用图与栈使用报告: 按所有编译单元链接后的程序计算, 最坏栈使用沿非递归的调用路径累加栈帧,
+// 递归调用环单独列出; 调用图以Graphviz格式输出, 递归调用的边标为红色
+
+const MAIN: &str = r#"
+int leaf(int x);
+int mid(int x) { return leaf(x) + leaf(x + 1); }
+int even(int n);
+int odd(int n) { if (n == 0) return 0; return even(n - 1); }
+int even(int n) { if (n == 0) return 1; return odd(n - 1); }
+int fact(int n) { if (n <= 1) return 1; return n * fact(n - 1); }
+int main() {
+    int big[100];
+    big[0] = mid(1);
+    putint(big[0] + even(4) + fact(5));
+    return 0;
+}
+"#;
+
+const LIB: &str = "int leaf(int x) { int a[10]; a[0] = x; return a[0]; }\n";
+
+struct Row {
+    frame: usize,
+    worst: usize,
+    path: String,
+}
+
+// 解析报告中的函数表: 函数名 -> 栈帧大小、最坏栈使用与对应的路径
+fn rows(report: &str) -> HashMap<String, Row> {
+    report
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let row = Row {
+                frame: fields[1].parse().unwrap(),
+                worst: fields[2].parse().unwrap(),
+                path: fields[3..].join(" "),
+            };
+            (fields[0].to_owned(), row)
+        })
+        .collect()
+}
+
+#[test]
+fn stack_report_and_call_graph() {
+    let files = [("main.c", MAIN), ("lib.c", LIB)];
+    let args = ["-riscv", "main.c", "lib.c", "--stack-report=report.txt", "--call-graph=graph.dot"];
+    let result = compile_files(&files, &args);
+    assert!(result.success, "{}", result.stderr);
+
+    let report = result.read("report.txt");
+    assert!(report.starts_with("function"), "{}", report);
+    let rows = rows(&report);
+    assert_eq!(rows.len(), 6, "{}", report);
+    // 局部数组占用栈帧
+    assert!(rows["leaf"].frame >= 40 && rows["main"].frame >= 400, "{}", report);
+    assert_eq!((rows["leaf"].worst, rows["leaf"].path.as_str()), (rows["leaf"].frame, "leaf"));
+    assert_eq!((rows["mid"].worst, rows["mid"].path.as_str()), (rows["mid"].frame + rows["leaf"].frame, "mid -> leaf"));
+    assert_eq!(rows["main"].worst, rows["main"].frame + rows["mid"].worst, "{}", report);
+    assert_eq!(rows["main"].path, "main -> mid -> leaf");
+    for name in ["even", "odd", "fact"] {
+        assert_eq!(rows[name].worst, rows[name].frame, "{}", report);
+        assert_eq!(rows[name].path, format!("{} (recursive)", name));
+    }
+    let cycles = report.split_once("recursive cycles (stack usage multiplies with recursion depth):\n").unwrap().1;
+    assert_eq!(cycles, "  even -> odd -> even\n  fact -> fact\n");
+
+    let dot = result.read("graph.dot");
+    assert!(dot.starts_with("digraph callgraph {"), "{}", dot);
+    let node = |name: &str| {
+        let label = format!(" [label=\"{}\\n", name);
+        let line = dot.lines().find(|line| line.contains(&label)).unwrap_or_else(|| panic!("no node for `{}`", name));
+        line.trim().split(' ').next().unwrap().to_owned()
+    };
+    let edge = |from: &str, to: &str| format!("  {} -> {}", node(from), node(to));
+    for (from, to) in [("main", "mid"), ("mid", "leaf"), ("main", "fact")] {
+        assert!(dot.contains(&format!("{};\n", edge(from, to))), "{}", dot);
+    }
+    for (from, to) in [("even", "odd"), ("odd", "even"), ("fact", "fact")] {
+        assert!(dot.contains(&format!("{} [color=red];\n", edge(from, to))), "{}", dot);
+    }
+    assert!(dot.contains("[label=\"putint\", style=dashed];"), "{}", dot);
+}
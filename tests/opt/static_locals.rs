@@ -0,0 +1,74 @@
+use crate::{ check, compile, exported };
+
+// static局部变量在调用之间保持其值, 不同函数(以及同一函数的不同作用域)中同名的static变量互不影响;
+// 文件作用域的static变量与函数为内部链接, 汇编中不导出
+
+const PROGRAM: &str = r#"
+static int total = 10;
+int shared = 1;
+
+static int helper(int x) {
+    return x * 2;
+}
+
+int count() {
+    static int n = 0;
+    n = n + 1;
+    return n;
+}
+
+int other() {
+    static int n[2] = {100};
+    n[0] = n[0] + 1;
+    return n[0];
+}
+
+int blocks(int first) {
+    if (first) {
+        static int k = 1;
+        k = k * 2;
+        return k;
+    }
+    static int k = 5;
+    k = k + 1;
+    return k;
+}
+
+int fib(int x) {
+    static int memo[30];
+    if (x < 2) return x;
+    if (memo[x]) return memo[x];
+    memo[x] = fib(x - 1) + fib(x - 2);
+    return memo[x];
+}
+
+int main() {
+    count();
+    count();
+    other();
+    putint(count() + other() + helper(total) + shared);
+    putch(32);
+    blocks(1);
+    blocks(0);
+    putint(blocks(1) * 100 + blocks(0));
+    putch(32);
+    putint(fib(29));
+    return 0;
+}
+"#;
+
+#[test]
+fn statics_keep_their_values() {
+    assert_eq!(check(PROGRAM, "", &[]).stdout, "126 407 514229");
+}
+
+#[test]
+fn internal_linkage() {
+    let asm = compile(PROGRAM, &[]);
+    for name in ["count", "other", "blocks", "fib", "main", "shared"] {
+        assert!(exported(&asm, name), "`{}` is not exported", name);
+    }
+    for name in ["total", "helper", "n", "k", "memo"] {
+        assert!(!exported(&asm, name), "`{}` is exported", name);
+    }
+}
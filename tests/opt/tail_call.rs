@@ -0,0 +1,82 @@
+use crate::check;
+
+// 后端生成的尾调用: 超过8个参数时栈上的参数要写入调用者的传入参数区(可能互相覆盖),
+// 放不下或参数指向本函数局部数组时退回普通调用。-O0同样生成尾调用, 因此检查输出的具体值
+
+#[test]
+fn stack_arguments() {
+    let src = r#"
+int weigh(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8 + i * 9 + j * 10;
+}
+
+int reversed(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return weigh(j, i, h, g, f, e, d, c, b, a);
+}
+
+int fewer(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return weigh(a, b, c, d, e, f, g, h, i, 0) + j;
+}
+
+int shifted(int a, int b, int c, int d, int e, int f, int g, int h, int i) {
+    return weigh(i, a, b, c, d, e, f, g, h, i);
+}
+
+int more(int a) {
+    return weigh(a, a, a, a, a, a, a, a, a, a);
+}
+
+int main() {
+    putint(reversed(1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+    putch(32);
+    putint(fewer(1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+    putch(32);
+    putint(shifted(1, 2, 3, 4, 5, 6, 7, 8, 9));
+    putch(32);
+    putint(more(getint()));
+    return 0;
+}
+"#;
+    let passes = ["-O2 --inline-threshold=0", "--passes=mem2reg,tail-recursion,sccp,dce,verify"];
+    assert_eq!(check(src, "2", &passes).stdout, "220 295 339 110");
+}
+
+#[test]
+fn mutual_recursion_and_local_arrays() {
+    let src = r#"
+int is_odd(int n);
+
+int is_even(int n) {
+    if (n == 0) return 1;
+    return is_odd(n - 1);
+}
+
+int is_odd(int n) {
+    if (n == 0) return 0;
+    return is_even(n - 1);
+}
+
+int first(int a[]) {
+    return a[0];
+}
+
+int local() {
+    int a[2] = {7, 8};
+    return first(a);
+}
+
+void show(int x) {
+    putint(x);
+}
+
+int main() {
+    show(is_even(getint()));
+    putch(32);
+    show(local());
+    return 0;
+}
+"#;
+    let passes = ["-O2 --inline-threshold=0"];
+    assert_eq!(check(src, "2001", &passes).stdout, "0 7");
+    assert_eq!(check(src, "20000", &passes).stdout, "1 7");
+}
@@ -0,0 +1,87 @@
+use crate::check;
+
+// 尾递归消除: 只有加法与乘法引入累加器, 不可交换的运算保持递归; 超过8个参数、void函数与数组参数
+
+#[test]
+fn non_commutative_recursion() {
+    let src = r#"
+int sub(int n) {
+    if (n == 0) return 0;
+    return n - sub(n - 1);
+}
+
+int rsub(int n) {
+    if (n == 0) return 100;
+    return rsub(n - 1) - n;
+}
+
+int div(int n, int d) {
+    if (n < d) return n;
+    return div(n / d, d) / 2;
+}
+
+int mixed(int n) {
+    if (n <= 0) return 1;
+    if (n % 2 == 0) return 2 * mixed(n - 1);
+    return mixed(n - 1) + 3;
+}
+
+int main() {
+    putint(sub(10));
+    putch(32);
+    putint(rsub(10));
+    putch(32);
+    putint(div(1000, 3));
+    putch(32);
+    putint(mixed(7));
+    return 0;
+}
+"#;
+    let passes = ["--passes=tail-recursion,verify", "--passes=mem2reg,tail-recursion,verify,sccp,dce,verify"];
+    assert_eq!(check(src, "", &passes).stdout, "5 45 0 53");
+}
+
+#[test]
+fn accumulators_and_many_arguments() {
+    let src = r#"
+int g[10];
+
+int sum(int n, int acc) {
+    if (n == 0) return acc;
+    return n + sum(n - 1, acc * 2 % 1000);
+}
+
+int prod(int n) {
+    if (n <= 1) return 1;
+    return prod(n - 1) * n;
+}
+
+int many(int a, int b, int c, int d, int e, int f, int h, int i, int j, int k) {
+    if (a <= 0) return b + c * 2 + d * 3 + e * 4 + f * 5 + h * 6 + i * 7 + j * 8 + k * 9;
+    return many(a - 1, c, d, e, f, h, i, j, k, b + a);
+}
+
+void fill(int a[], int n) {
+    if (n == 0) return;
+    a[n - 1] = n * n;
+    fill(a, n - 1);
+}
+
+int main() {
+    fill(g, 10);
+    int local[3] = {};
+    fill(local, 3);
+    putint(sum(20, 3));
+    putch(32);
+    putint(prod(10));
+    putch(32);
+    putint(many(getint(), 1, 2, 3, 4, 5, 6, 7, 8, 9));
+    putch(32);
+    putint(g[9] + local[2]);
+    return 0;
+}
+"#;
+    let passes = ["--passes=tail-recursion,verify", "--passes=mem2reg,tail-recursion,verify,inline,sccp,dce,verify"];
+    assert_eq!(check(src, "13", &passes).stdout, "938 3628800 700 109");
+    assert_eq!(check(src, "0", &passes).stdout, "938 3628800 285 109");
+}
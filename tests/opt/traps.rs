@@ -0,0 +1,54 @@
+use crate::{ compile, errors, riscv, warnings };
+
+// 运行时代码中操作数为常量的除零、INT_MIN / -1与溢出给出警告;
+// --hardened把它们替换为调用陷阱函数, 常量定义中出现时是错误
+
+const SRC: &str = r#"int main() {
+    int x = getint();
+    putint(x);
+    if (x == 1) return (-2147483647 - 1) / -1;
+    if (x == 2) return x % 0;
+    if (x == 3) return 2147483647 * 3 / 3;
+    if (x == 4) return -2147483647 - 2;
+    return x / 1 + x * 0;
+}
+"#;
+
+#[test]
+fn constant_trap_warnings() {
+    let stderr = warnings(SRC, &[]);
+    let expected = [
+        "test.c:4:24: warning: integer overflow in `-2147483648 / -1` [-Wconstant-overflow]",
+        "test.c:5:24: warning: division by zero [-Wconstant-overflow]",
+        "test.c:6:24: warning: integer overflow in `2147483647 * 3` [-Wconstant-overflow]",
+        "test.c:7:24: warning: integer overflow in `-2147483647 - 2` [-Wconstant-overflow]",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":0,"warnings":4,"lints":{"constant-overflow":4}}"#), "{}", stderr);
+}
+
+#[test]
+fn hardened_mode() {
+    for flags in [&["--hardened"][..], &["--hardened", "-O2"]] {
+        let asm = compile(SRC, flags);
+        let output = riscv::run(&asm, "5").unwrap();
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("5", 5));
+        for input in ["1", "2", "3", "4"] {
+            assert_eq!(riscv::run(&asm, input), Err("trap".to_owned()), "input {} with {:?}", input, flags);
+        }
+    }
+    // 不加--hardened时保持原来的运算, 溢出按补码回绕(退出码只保留低8位)
+    let asm = compile(SRC, &[]);
+    assert_eq!(riscv::run(&asm, "2"), Err("division by zero".to_owned()));
+    assert_eq!(riscv::run(&asm, "3").unwrap().exit_code, (i32::MAX.wrapping_mul(3) / 3) & 0xff);
+}
+
+#[test]
+fn constant_definitions() {
+    let src = "const int k = 1 / 0;\nconst int m[2] = {2147483647 + 1, 0};\nint main() { return k + m[0]; }\n";
+    let stderr = errors(src, &[]);
+    assert!(stderr.contains("test.c:1:15: error: division by zero in constant expression"), "{}", stderr);
+    assert!(stderr.contains("test.c:2:19: error: overflow in constant expression"), "{}", stderr);
+}
@@ -0,0 +1,50 @@
+use crate::{ check, warnings };
+
+// 没有初始化的标量局部变量在某条路径上先被读取时给出警告, 同时指出声明的位置;
+// 同一路径上只报告第一次读取, 数组与地址逃逸的变量不做分析
+
+const SRC: &str = r#"
+int g(int c) {
+    int a;
+    int b;
+    int d;
+    int arr[2];
+    if (c) {
+        a = 1;
+    }
+    d = 4;
+    while (c < 3) {
+        b = c;
+        c = c + 1;
+    }
+    arr[0] = c;
+    arr[1] = arr[0];
+    return a + b + d + a;
+}
+
+int main() {
+    int x;
+    x = getint();
+    int y;
+    if (x > 0) y = 1;
+    else y = 2;
+    return g(x) * 0 + y;
+}
+"#;
+
+#[test]
+fn maybe_uninitialized_reads() {
+    let stderr = warnings(SRC, &[]);
+    let expected = [
+        "test.c:17:12: warning: `a` may be used uninitialized [-Wuninit]\n",
+        "test.c:3:9: note: `a` declared here\n",
+        "test.c:17:16: warning: `b` may be used uninitialized [-Wuninit]\n",
+        "test.c:4:9: note: `b` declared here\n",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":0,"warnings":2,"lints":{"uninit":2}}"#), "{}", stderr);
+    assert_eq!(check(SRC, "1", &[]).exit_code, 1);
+    assert!(warnings(SRC, &["-Wno-uninit"]).is_empty());
+}
@@ -0,0 +1,59 @@
+use crate::{ check, warnings };
+
+// return/break/continue之后以及不会结束的循环之后的语句是不可达的, 每个块只报告第一条;
+// 存入局部变量之后在任何路径上都不会再读取的值是无用的存储
+
+const SRC: &str = r#"
+int f(int x) {
+    int scaled = x * 3;
+    int y = 1;
+    y = 2;
+    while (x > 0) {
+        x = x - 1;
+        if (x == 5) {
+            break;
+            y = 3;
+        }
+        if (x == 7) {
+            continue;
+            x = 100;
+        }
+        y = y + x;
+    }
+    return y;
+    y = 4;
+    return 0;
+}
+
+int main() {
+    int t = getint();
+    while (1) {
+        t = f(t);
+        if (t) break;
+    }
+    if (t > 10) return t;
+    while (1) {
+        t = t + 1;
+    }
+    return f(t);
+}
+"#;
+
+#[test]
+fn unreachable_and_dead_stores() {
+    let stderr = warnings(SRC, &[]);
+    let expected = [
+        "test.c:3:9: warning: value stored to `scaled` is never read [-Wdead-store]",
+        "test.c:4:9: warning: value stored to `y` is never read [-Wdead-store]",
+        "test.c:10:13: warning: unreachable code [-Wunreachable]",
+        "test.c:14:13: warning: unreachable code [-Wunreachable]",
+        "test.c:19:5: warning: unreachable code [-Wunreachable]",
+        "test.c:33:5: warning: unreachable code [-Wunreachable]",
+    ];
+    for expected in expected {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    // 不可达语句中的存储不再报告, 循环中被下一次迭代读取的存储不是无用的
+    assert!(stderr.contains(r#""dead-store":2,"#) && stderr.contains(r#""unreachable":4,"#), "{}", stderr);
+    assert_eq!(check(SRC, "9", &[]).exit_code, 16);
+}
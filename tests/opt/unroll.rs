@@ -0,0 +1,102 @@
+use crate::check;
+
+// 循环展开: 常量迭代次数的完全展开、运行时边界的部分展开与剩余迭代、不执行的循环、
+// 递减的归纳变量、<=与>=条件, 以及边界接近INT_MAX时计算剩余迭代不能溢出
+
+const PASSES: [&str; 4] = [
+    "--passes=mem2reg,unroll,verify,sccp,dce,verify",
+    "--unroll-factor=2",
+    "--unroll-factor=3",
+    "-O2 --unroll-factor=8",
+];
+
+#[test]
+fn constant_trip_counts() {
+    let src = r#"
+int main() {
+    int a[8] = {}, i = 0, s = 0;
+    while (i < 8) {
+        a[i] = i * i;
+        i = i + 1;
+    }
+    i = 7;
+    while (i >= 0) {
+        s = s * 3 + a[i];
+        i = i - 2;
+    }
+    i = 5;
+    while (i < 5) {
+        s = s + 1000;
+        i = i + 1;
+    }
+    i = 0;
+    while (i <= 12) {
+        s = s + i;
+        i = i + 3;
+    }
+    putint(s);
+    return a[7];
+}
+"#;
+    let output = check(src, "", &PASSES);
+    assert_eq!((output.stdout.as_str(), output.exit_code), ("1606", 49));
+}
+
+#[test]
+fn runtime_bounds() {
+    let src = r#"
+int main() {
+    int n = getint(), m = getint(), i = 0, s = 0;
+    while (i < n) {
+        s = s * 7 % 1009 + i;
+        i = i + 1;
+    }
+    putint(s);
+    putch(32);
+    i = n;
+    while (i > m) {
+        s = s - i;
+        i = i - 3;
+    }
+    putint(s);
+    putch(32);
+    i = m;
+    while (i <= n) {
+        s = s + i % 5;
+        i = i + 2;
+    }
+    putint(s);
+    return 0;
+}
+"#;
+    assert_eq!(check(src, "0 0", &PASSES).stdout, "0 0 0");
+    assert_eq!(check(src, "0 5", &PASSES).stdout, "0 0 0");
+    check(src, "1 -1", &PASSES);
+    check(src, "7 2", &PASSES);
+    check(src, "23 -4", &PASSES);
+    check(src, "-3 -10", &PASSES);
+}
+
+#[test]
+fn bounds_near_int_max() {
+    let src = r#"
+int main() {
+    int n = getint(), i = n - 9, c = 0;
+    while (i < n) {
+        c = c + 1;
+        i = i + 3;
+    }
+    putint(c);
+    putch(32);
+    i = n - 5;
+    while (i <= n - 1) {
+        c = c + 10;
+        i = i + 1;
+    }
+    putint(c);
+    return 0;
+}
+"#;
+    assert_eq!(check(src, "2147483647", &PASSES).stdout, "3 53");
+    assert_eq!(check(src, "2147483640", &PASSES).stdout, "3 53");
+}
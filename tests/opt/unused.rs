@@ -0,0 +1,60 @@
+use crate::{ compile_files, warnings };
+
+// 从未使用的局部变量、参数、常量、全局变量与函数(main除外)给出警告, 下划线开头的名字不报告;
+// 分别编译时其他文件可能使用外部链接的全局符号, 只报告static的
+
+const SRC: &str = r#"int used_global = 1;
+int unused_global;
+static int unused_static;
+static int _quiet_static;
+const int unused_const = 3;
+static int unused_func(int p) { return p; }
+int exported_func(int unused_param, int _ignored) { return 0; }
+static int helper(int used_param, int unused_param2) { return used_param; }
+int main() {
+    int unused_local;
+    int _also_quiet;
+    const int local_const = 2;
+    int used = helper(used_global, 0);
+    return used;
+}
+"#;
+
+const WHOLE_PROGRAM: [&str; 5] = [
+    "test.c:2:5: warning: unused variable `unused_global` [-Wunused]",
+    "test.c:5:11: warning: unused constant `unused_const` [-Wunused]",
+    "test.c:7:5: warning: unused function `exported_func` [-Wunused]",
+    "test.c:3:12: warning: unused variable `unused_static` [-Wunused]",
+    "test.c:6:12: warning: unused function `unused_func` [-Wunused]",
+];
+
+const ALWAYS: [&str; 4] = [
+    "test.c:7:23: warning: unused parameter `unused_param` [-Wunused]",
+    "test.c:8:39: warning: unused parameter `unused_param2` [-Wunused]",
+    "test.c:10:9: warning: unused variable `unused_local` [-Wunused]",
+    "test.c:12:15: warning: unused constant `local_const` [-Wunused]",
+];
+
+#[test]
+fn unused_symbols() {
+    let stderr = warnings(SRC, &[]);
+    for expected in WHOLE_PROGRAM.iter().chain(&ALWAYS) {
+        assert!(stderr.contains(expected), "expected `{}` in:\n{}", expected, stderr);
+    }
+    assert!(stderr.contains(r#"{"errors":0,"warnings":9,"lints":{"unused":9}}"#), "{}", stderr);
+    for name in ["`_quiet_static`", "`_ignored`", "`_also_quiet`", "`main`", "`p`", "`helper`"] {
+        assert!(!stderr.contains(name), "`{}` reported in:\n{}", name, stderr);
+    }
+}
+
+#[test]
+fn separate_compilation() {
+    let result = compile_files(&[("test.c", SRC)], &["-riscv", "-c", "test.c"]);
+    assert!(result.success, "{}", result.stderr);
+    for expected in WHOLE_PROGRAM[3..].iter().chain(&ALWAYS) {
+        assert!(result.stderr.contains(expected), "expected `{}` in:\n{}", expected, result.stderr);
+    }
+    for name in ["`unused_global`", "`unused_const`", "`exported_func`"] {
+        assert!(!result.stderr.contains(name), "`{}` reported in:\n{}", name, result.stderr);
+    }
+}